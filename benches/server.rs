@@ -0,0 +1,143 @@
+//! In-process benchmarks of the request/response path, over `tokio::io::duplex` so the numbers
+//! don't include the kernel's TCP stack. Run with `cargo bench`.
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use async_tiny::static_files::StaticFiles;
+use async_tiny::{Response, Server};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
+
+/// A keep-alive connection to an in-process server.
+struct Client {
+    stream: DuplexStream,
+    buf: Vec<u8>,
+}
+
+impl Client {
+    /// How long `iters` round trips of `request` take.
+    fn time(&mut self, rt: &Runtime, request: &[u8], iters: u64) -> Duration {
+        rt.block_on(async {
+            let started = Instant::now();
+            for _ in 0..iters {
+                self.round_trip(request).await;
+            }
+            started.elapsed()
+        })
+    }
+
+    /// Sends `request` and reads exactly one response, returning its length.
+    async fn round_trip(&mut self, request: &[u8]) -> usize {
+        self.stream.write_all(request).await.unwrap();
+        self.buf.clear();
+        let mut chunk = [0u8; 8192];
+        loop {
+            let n = self.stream.read(&mut chunk).await.unwrap();
+            assert!(n > 0, "server closed the connection");
+            self.buf.extend_from_slice(&chunk[..n]);
+            if let Some(len) = response_len(&self.buf) {
+                if self.buf.len() >= len {
+                    return len;
+                }
+            }
+        }
+    }
+}
+
+/// The full length of the response at the start of `buf`, once its head has arrived.
+fn response_len(buf: &[u8]) -> Option<usize> {
+    let head_end = buf.windows(4).position(|w| w == b"\r\n\r\n")? + 4;
+    let head = std::str::from_utf8(&buf[..head_end]).ok()?;
+    let body_len = head
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse::<usize>().ok())?
+        })
+        .unwrap_or(0);
+    Some(head_end + body_len)
+}
+
+/// Starts a server running `app` for every request and connects one client to it.
+fn start<F, Fut>(rt: &Runtime, app: F) -> Client
+where
+    F: Fn(async_tiny::Request) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = ()> + Send,
+{
+    rt.block_on(async {
+        let (streams, source) = mpsc::channel(1);
+        let mut server = Server::builder().silent(true).serve_streams(source);
+        tokio::spawn(async move {
+            while let Some(request) = server.next().await {
+                app(request).await;
+            }
+        });
+
+        let (client, stream) = tokio::io::duplex(64 * 1024);
+        streams.send(stream).await.unwrap();
+        Client {
+            stream: client,
+            buf: Vec::new(),
+        }
+    })
+}
+
+fn buffered(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("buffered");
+
+    let mut client = start(&rt, |request| async move {
+        let _ = request.respond(Response::from_string("ok"));
+    });
+    let get = b"GET / HTTP/1.1\r\nHost: bench\r\n\r\n";
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("get", |b| {
+        b.iter_custom(|iters| client.time(&rt, get, iters));
+    });
+
+    let mut client = start(&rt, |request| async move {
+        let len = request.body().len();
+        let _ = request.respond(Response::from_string(len.to_string()));
+    });
+    let body = vec![b'x'; 64 * 1024];
+    let mut post = format!(
+        "POST /upload HTTP/1.1\r\nHost: bench\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    post.extend_from_slice(&body);
+    group.throughput(Throughput::Bytes(body.len() as u64));
+    group.bench_function("post_64k", |b| {
+        b.iter_custom(|iters| client.time(&rt, &post, iters));
+    });
+
+    group.finish();
+}
+
+fn static_files(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let files = std::sync::Arc::new(StaticFiles::new(root));
+
+    let mut client = start(&rt, move |request| {
+        let files = files.clone();
+        async move {
+            let response = files
+                .serve(&request)
+                .await
+                .unwrap_or_else(|| Response::empty(404));
+            let _ = request.respond(response);
+        }
+    });
+    let get = b"GET /README.md HTTP/1.1\r\nHost: bench\r\n\r\n";
+    c.bench_function("static_files/readme", |b| {
+        b.iter_custom(|iters| client.time(&rt, get, iters));
+    });
+}
+
+criterion_group!(benches, buffered, static_files);
+criterion_main!(benches);
@@ -0,0 +1,127 @@
+//! A wrk-style load test against an in-process server, over real TCP.
+//!
+//! ```text
+//! cargo run --release --example selfbench -- [connections] [seconds] [path]
+//! ```
+//!
+//! `path` defaults to `/`, which answers a short string. Files in the crate root like
+//! `/README.md` are served by `StaticFiles`, and `/upload` posts a 64 KiB body.
+
+use std::time::{Duration, Instant};
+
+use async_tiny::static_files::StaticFiles;
+use async_tiny::{Response, Server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const ADDR: &str = "127.0.0.1:18080";
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let connections: usize = args.next().and_then(|a| a.parse().ok()).unwrap_or(32);
+    let seconds: u64 = args.next().and_then(|a| a.parse().ok()).unwrap_or(10);
+    let path = args.next().unwrap_or_else(|| "/".to_string());
+
+    let mut server = Server::http(ADDR, true).await?;
+    let files = StaticFiles::new(env!("CARGO_MANIFEST_DIR"));
+    tokio::spawn(async move {
+        while let Some(request) = server.next().await {
+            let response = match files.serve(&request).await {
+                Some(response) => response,
+                None => Response::from_string(format!("ok {}", request.body().len())),
+            };
+            let _ = request.respond(response);
+        }
+    });
+
+    let request = build_request(&path);
+    let until = Instant::now() + Duration::from_secs(seconds);
+    let clients: Vec<_> = (0..connections)
+        .map(|_| {
+            let request = request.clone();
+            tokio::spawn(async move { client(request, until).await })
+        })
+        .collect();
+
+    let mut latencies = Vec::new();
+    for client in clients {
+        latencies.extend(client.await.expect("client task")?);
+    }
+    latencies.sort();
+
+    let total = latencies.len();
+    let percentile = |p: f64| {
+        latencies
+            .get(((total as f64 * p) as usize).min(total.saturating_sub(1)))
+            .copied()
+            .unwrap_or_default()
+    };
+    println!("{} connections, {}s, {}", connections, seconds, path);
+    println!("requests: {}", total);
+    println!("req/s:    {:.0}", total as f64 / seconds as f64);
+    println!("p50:      {:?}", percentile(0.50));
+    println!("p99:      {:?}", percentile(0.99));
+    println!(
+        "max:      {:?}",
+        latencies.last().copied().unwrap_or_default()
+    );
+    Ok(())
+}
+
+fn build_request(path: &str) -> Vec<u8> {
+    if path == "/upload" {
+        let body = vec![b'x'; 64 * 1024];
+        let mut request = format!(
+            "POST /upload HTTP/1.1\r\nHost: bench\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        request.extend_from_slice(&body);
+        request
+    } else {
+        format!("GET {} HTTP/1.1\r\nHost: bench\r\n\r\n", path).into_bytes()
+    }
+}
+
+/// Sends `request` over one keep-alive connection until `until`, recording each latency.
+async fn client(request: Vec<u8>, until: Instant) -> std::io::Result<Vec<Duration>> {
+    let mut stream = TcpStream::connect(ADDR).await?;
+    stream.set_nodelay(true)?;
+    let mut latencies = Vec::new();
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 16 * 1024];
+
+    while Instant::now() < until {
+        let started = Instant::now();
+        stream.write_all(&request).await?;
+        buf.clear();
+        loop {
+            let n = stream.read(&mut chunk).await?;
+            if n == 0 {
+                return Ok(latencies);
+            }
+            buf.extend_from_slice(&chunk[..n]);
+            if matches!(response_len(&buf), Some(len) if buf.len() >= len) {
+                break;
+            }
+        }
+        latencies.push(started.elapsed());
+    }
+    Ok(latencies)
+}
+
+/// The full length of the response at the start of `buf`, once its head has arrived.
+fn response_len(buf: &[u8]) -> Option<usize> {
+    let head_end = buf.windows(4).position(|w| w == b"\r\n\r\n")? + 4;
+    let head = std::str::from_utf8(&buf[..head_end]).ok()?;
+    let body_len = head
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse::<usize>().ok())?
+        })
+        .unwrap_or(0);
+    Some(head_end + body_len)
+}
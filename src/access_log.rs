@@ -0,0 +1,169 @@
+//! Access logging with sampling and redaction, enabled via [`ServerBuilder::access_log`].
+//!
+//! Each logged request produces one line on stderr:
+//!
+//! ```text
+//! 127.0.0.1:51234 "GET /health" 200 2B 0.3ms req_body=0B
+//! ```
+//!
+//! [`ServerBuilder::access_log`]: crate::ServerBuilder::access_log
+
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use bytes::Bytes;
+use http::{HeaderMap, Method};
+
+use crate::Response;
+
+/// Headers whose values are never written to the log unless [`AccessLog::unredact`] is used.
+pub(crate) const DEFAULT_REDACTED: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+];
+
+/// Access log configuration.
+pub struct AccessLog {
+    sample: u64,
+    counter: AtomicU64,
+    headers: bool,
+    redacted: Vec<String>,
+    body: BodyLog,
+}
+
+/// How much of the request body ends up in the log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyLog {
+    /// Only the body length.
+    SizeOnly,
+    /// The length plus up to this many bytes of the body, decoded lossily as UTF-8.
+    Preview(usize),
+}
+
+impl Default for AccessLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AccessLog {
+    /// Log every request with its body size only; credentials and cookies are redacted.
+    pub fn new() -> Self {
+        Self {
+            sample: 1,
+            counter: AtomicU64::new(0),
+            headers: false,
+            redacted: DEFAULT_REDACTED.iter().map(|s| s.to_string()).collect(),
+            body: BodyLog::SizeOnly,
+        }
+    }
+
+    /// Log only one in every `n` requests. `0` and `1` log everything.
+    pub fn sample(mut self, n: u64) -> Self {
+        self.sample = n.max(1);
+        self
+    }
+
+    /// Include request headers in each line.
+    pub fn headers(mut self, enabled: bool) -> Self {
+        self.headers = enabled;
+        self
+    }
+
+    /// Replace the value of header `name` with `[redacted]`.
+    pub fn redact(mut self, name: &str) -> Self {
+        let name = name.to_ascii_lowercase();
+        if !self.redacted.contains(&name) {
+            self.redacted.push(name);
+        }
+        self
+    }
+
+    /// Stop redacting header `name`, including the defaults.
+    pub fn unredact(mut self, name: &str) -> Self {
+        let name = name.to_ascii_lowercase();
+        self.redacted.retain(|n| *n != name);
+        self
+    }
+
+    /// Choose how the request body is logged (default: [`BodyLog::SizeOnly`]).
+    pub fn body(mut self, body: BodyLog) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Shorthand for `body(BodyLog::SizeOnly)`.
+    pub fn body_size_only(self) -> Self {
+        self.body(BodyLog::SizeOnly)
+    }
+
+    /// Starts an entry for a request, or returns `None` if it is not sampled.
+    pub(crate) fn begin(
+        &self,
+        peer: Option<SocketAddr>,
+        method: &Method,
+        url: &str,
+        headers: &HeaderMap,
+        body: &Bytes,
+    ) -> Option<Entry> {
+        if !self
+            .counter
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(self.sample)
+        {
+            return None;
+        }
+
+        let mut suffix = format!(" req_body={}B", body.len());
+        if self.headers {
+            suffix.push_str(" headers={");
+            for (i, (name, value)) in headers.iter().enumerate() {
+                if i > 0 {
+                    suffix.push_str(", ");
+                }
+                let value = if self.redacted.iter().any(|r| r == name.as_str()) {
+                    "[redacted]".into()
+                } else {
+                    String::from_utf8_lossy(value.as_bytes())
+                };
+                let _ = write!(suffix, "{}: {}", name, value);
+            }
+            suffix.push('}');
+        }
+        if let BodyLog::Preview(max) = self.body {
+            let preview = &body[..body.len().min(max)];
+            let _ = write!(suffix, " body={:?}", String::from_utf8_lossy(preview));
+        }
+
+        Some(Entry {
+            prefix: match peer {
+                Some(peer) => format!("{} \"{} {}\"", peer, method, url),
+                None => format!("- \"{} {}\"", method, url),
+            },
+            suffix,
+        })
+    }
+}
+
+/// A sampled request waiting for its response.
+pub(crate) struct Entry {
+    prefix: String,
+    suffix: String,
+}
+
+impl Entry {
+    pub(crate) fn finish(self, response: &Response, elapsed: Duration) {
+        eprintln!(
+            "{} {} {}B {:.1}ms{}",
+            self.prefix,
+            response.status.as_u16(),
+            response.body.len(),
+            elapsed.as_secs_f64() * 1000.0,
+            self.suffix
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! Sticky client-to-backend mapping, for apps that fan work out to shards.
+//!
+//! [`Request::affinity_key`] gives each client a key that stays the same across requests and
+//! server restarts, and [`shard`] turns it into a backend index that mostly stays put when
+//! backends are added or removed.
+//!
+//! ```
+//! use async_tiny::{affinity, Header, Method, Request};
+//!
+//! let req = Request::fake(&Method::GET, "/", b"")
+//!     .with_header(Header::new("Cookie", "theme=dark; session=abc123").unwrap());
+//! let key = req.affinity_key(Some("session")).unwrap();
+//! assert_eq!(Some(key), affinity::key(None, Some("abc123")));
+//! assert!(affinity::shard(key, 8) < 8);
+//!
+//! // Without the cookie, or an address to fall back on, there's no key.
+//! assert_eq!(Request::fake(&Method::GET, "/", b"").affinity_key(Some("session")), None);
+//! ```
+//!
+//! [`Request::affinity_key`]: crate::Request::affinity_key
+
+use std::net::IpAddr;
+
+use http::header::COOKIE;
+use http::HeaderMap;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// The key for a client identified by `cookie` if it has one, otherwise by `ip`.
+///
+/// This is a fixed hash (64-bit FNV-1a) rather than `std`'s, so every server instance and
+/// build maps a given client to the same key.
+pub fn key(ip: Option<IpAddr>, cookie: Option<&str>) -> Option<u64> {
+    match (cookie, ip) {
+        (Some(value), _) => Some(fnv1a(b"c", value.as_bytes())),
+        (None, Some(IpAddr::V4(ip))) => Some(fnv1a(b"4", &ip.octets())),
+        (None, Some(IpAddr::V6(ip))) => Some(fnv1a(b"6", &ip.octets())),
+        (None, None) => None,
+    }
+}
+
+/// Picks one of `shards` backends for `key` with jump consistent hashing: growing from `n` to
+/// `n + 1` shards moves only about `1 / (n + 1)` of keys, all of them to the new shard.
+///
+/// # Panics
+///
+/// If `shards` is zero.
+pub fn shard(key: u64, shards: u32) -> u32 {
+    assert!(shards > 0, "at least one shard is required");
+    let mut key = key;
+    let mut b: i64 = -1;
+    let mut j: i64 = 0;
+    while j < i64::from(shards) {
+        b = j;
+        key = key.wrapping_mul(2_862_933_555_777_941_757).wrapping_add(1);
+        j = ((b + 1) as f64 * ((1u64 << 31) as f64 / ((key >> 33) + 1) as f64)) as i64;
+    }
+    b as u32
+}
+
+/// The value of cookie `name`, from any of the request's `Cookie` headers.
+pub(crate) fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(k, _)| *k == name)
+        .map(|(_, v)| v.trim_matches('"'))
+        .filter(|v| !v.is_empty())
+}
+
+/// Hashes `data` after a one-byte `domain` tag, so a cookie value never hashes like an address.
+fn fnv1a(domain: &[u8], data: &[u8]) -> u64 {
+    domain.iter().chain(data).fold(FNV_OFFSET, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(FNV_PRIME)
+    })
+}
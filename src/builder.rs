@@ -0,0 +1,799 @@
+use std::io;
+use std::net::SocketAddr;
+#[cfg(feature = "dev")]
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use http::{HeaderMap, HeaderName, Method};
+use hyper::server::conn::http1;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{lookup_host, ToSocketAddrs};
+use tokio::sync::mpsc;
+
+use crate::conn_error::{ConnectionError, ErrorChannel};
+use crate::cors::Cors;
+#[cfg(feature = "dev")]
+use crate::dev::LiveReload;
+use crate::digest::BodyDigest;
+use crate::header_policy::HeaderPolicy;
+use crate::honeypot::Honeypot;
+use crate::latency::LatencyStats;
+use crate::load_shed::LoadShedder;
+use crate::maintenance;
+use crate::pattern::Pattern;
+use crate::rate_limit::RateLimit;
+use crate::tarpit::Tarpit;
+use crate::{
+    into_io_error, stream_loop, AccessLog, ErrorPages, MapError, MethodPolicy, ParserProfile,
+    Priority, Request, Response, Server, SocketOptions,
+};
+
+/// The longest budget a client can ask for with [`ServerBuilder::deadline_header`].
+const MAX_HEADER_BUDGET: Duration = Duration::from_secs(60 * 60);
+
+/// Configures a [`Server`] before binding it.
+///
+/// ```no_run
+/// use async_tiny::{AccessLog, Server};
+///
+/// # async fn run() -> std::io::Result<()> {
+/// let mut server = Server::builder()
+///     .access_log(AccessLog::new().sample(10))
+///     .http("127.0.0.1:8080")
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ServerBuilder {
+    config: Config,
+}
+
+/// Settings shared by the accept loop and every connection task.
+#[derive(Default)]
+pub(crate) struct Config {
+    pub(crate) silent: bool,
+    pub(crate) access_log: Option<AccessLog>,
+    pub(crate) max_requests_per_connection: Option<u64>,
+    pub(crate) request_budget: Option<Duration>,
+    pub(crate) deadline_header: Option<HeaderName>,
+    pub(crate) max_body_size: Option<usize>,
+    pub(crate) upload_progress: Option<ProgressObserver>,
+    pub(crate) body_digest: Option<DigestFactory>,
+    pub(crate) parser_profile: ParserProfile,
+    pub(crate) method_policy: Option<MethodPolicy>,
+    pub(crate) error_pages: ErrorPages,
+    pub(crate) socket_options: SocketOptions,
+    pub(crate) writev: Option<bool>,
+    pub(crate) max_buf_size: Option<usize>,
+    pub(crate) pipeline_flush: bool,
+    pub(crate) acceptors: usize,
+    pub(crate) acceptor_threads: bool,
+    pub(crate) acceptor_cores: Vec<usize>,
+    pub(crate) slow_queue: Option<Duration>,
+    pub(crate) max_queue_time: Option<Duration>,
+    pub(crate) priority_prefixes: Vec<(String, Priority)>,
+    pub(crate) classifier: Option<Classifier>,
+    pub(crate) on_head: Option<HeadHook>,
+    pub(crate) fast_paths: Vec<(Pattern, FastHandler)>,
+    pub(crate) errors: ErrorChannel,
+    pub(crate) latency: Option<Arc<LatencyStats>>,
+    pub(crate) on_response: Option<Arc<ResponseHook>>,
+    pub(crate) rate_limit: Option<RateLimit>,
+    pub(crate) tarpit: Option<Tarpit>,
+    pub(crate) load_shed: Option<LoadShedder>,
+    pub(crate) honeypot: Option<Honeypot>,
+    pub(crate) maintenance: maintenance::Switch,
+    pub(crate) map_error: Option<Arc<dyn MapError>>,
+    pub(crate) cors: Option<Cors>,
+    pub(crate) header_policy: Option<HeaderPolicy>,
+    /// Headers from [`ServerBuilder::default_header`], kept apart from `header_policy` so
+    /// replacing the policy doesn't drop them.
+    pub(crate) default_headers: HeaderPolicy,
+    #[cfg(feature = "dev")]
+    pub(crate) live_reload: Option<Arc<LiveReload>>,
+    #[cfg(feature = "dev")]
+    pub(crate) restart_watch: Vec<PathBuf>,
+    #[cfg(feature = "dev")]
+    pub(crate) debug_pages: bool,
+}
+
+type ProgressObserver = Box<dyn Fn(&UploadProgress<'_>) + Send + Sync>;
+type DigestFactory = Box<dyn Fn() -> Box<dyn BodyDigest> + Send + Sync>;
+type Classifier = Box<dyn Fn(&RequestHead<'_>) -> Priority + Send + Sync>;
+type HeadHook = Box<dyn Fn(&RequestHead<'_>) -> Decision + Send + Sync>;
+type FastHandler = Box<dyn Fn(&RequestHead<'_>) -> Response + Send + Sync>;
+pub(crate) type ResponseHook = dyn Fn(&Request, Response) -> Response + Send + Sync;
+
+/// A request whose head has been read but whose body hasn't, as seen by builder callbacks.
+pub struct RequestHead<'a> {
+    pub method: &'a Method,
+    /// The path and query, e.g. `/upload?id=42`.
+    pub url: &'a str,
+    pub headers: &'a HeaderMap,
+    pub connection_id: u64,
+}
+
+/// How far along a request body upload is, reported to [`ServerBuilder::upload_progress`].
+pub struct UploadProgress<'a> {
+    /// The request path and query, for telling uploads apart (e.g. `/upload?id=42`).
+    pub url: &'a str,
+    pub headers: &'a HeaderMap,
+    pub connection_id: u64,
+    /// Body bytes received so far.
+    pub received: u64,
+    /// The expected total from `Content-Length`, unknown for chunked uploads.
+    pub total: Option<u64>,
+}
+
+/// What [`ServerBuilder::on_head`] decides about a request before its body is read.
+pub enum Decision {
+    /// Read the body as usual.
+    Allow,
+    /// Answer with this response without reading the body. The connection is closed if the
+    /// request had a body, since it can't be skipped over.
+    Reject(Response),
+    /// Read the body, but answer `413` if it's larger than this many bytes. Replaces
+    /// [`ServerBuilder::max_body_size`] for this request, in either direction.
+    Limit(usize),
+}
+
+impl ServerBuilder {
+    pub(crate) fn new() -> Self {
+        Self {
+            config: Config::default(),
+        }
+    }
+
+    /// Suppress internal logging (startup message, connection errors).
+    pub fn silent(mut self, silent: bool) -> Self {
+        self.config.silent = silent;
+        self
+    }
+
+    /// Write an access log line to stderr for requests, as configured by `log`.
+    pub fn access_log(mut self, log: AccessLog) -> Self {
+        self.config.access_log = Some(log);
+        self
+    }
+
+    /// Close each connection after it has served `n` requests.
+    pub fn max_requests_per_connection(mut self, n: u64) -> Self {
+        self.config.max_requests_per_connection = Some(n.max(1));
+        self
+    }
+
+    /// Give each request a time budget, exposed to handlers as [`Request::deadline`].
+    ///
+    /// [`Request::deadline`]: crate::Request::deadline
+    pub fn request_budget(mut self, budget: Duration) -> Self {
+        self.config.request_budget = Some(budget);
+        self
+    }
+
+    /// Take the request budget from a header such as `X-Request-Timeout`, in seconds.
+    ///
+    /// When a [`request_budget`](Self::request_budget) is also set, the shorter of the two wins.
+    /// Budgets from the header are capped at an hour, whatever the client asks for.
+    pub fn deadline_header(mut self, name: HeaderName) -> Self {
+        self.config.deadline_header = Some(name);
+        self
+    }
+
+    /// Reject request bodies larger than `bytes` with `413 Payload Too Large`.
+    ///
+    /// The limit applies to the decoded body, so chunked uploads are counted across chunks.
+    pub fn max_body_size(mut self, bytes: usize) -> Self {
+        self.config.max_body_size = Some(bytes);
+        self
+    }
+
+    /// Call `observer` each time a chunk of a request body arrives.
+    ///
+    /// Runs on the connection task while the body is buffered, before the request reaches
+    /// [`Server::next`], so keep it cheap (e.g. update a shared map or send on a channel).
+    pub fn upload_progress<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&UploadProgress<'_>) + Send + Sync + 'static,
+    {
+        self.config.upload_progress = Some(Box::new(observer));
+        self
+    }
+
+    /// Hash every request body with a digest from `digest` as it's buffered, for
+    /// [`Request::body_digest`](crate::Request::body_digest). See [`digest`](crate::digest).
+    pub fn body_digest<F, D>(mut self, digest: F) -> Self
+    where
+        F: Fn() -> D + Send + Sync + 'static,
+        D: BodyDigest + 'static,
+    {
+        self.config.body_digest = Some(Box::new(move || Box::new(digest())));
+        self
+    }
+
+    /// Choose how strictly request heads are parsed (default: [`ParserProfile::Lenient`]).
+    pub fn parser_profile(mut self, profile: ParserProfile) -> Self {
+        self.config.parser_profile = profile;
+        self
+    }
+
+    /// Answer methods denied by `policy` with `405 Method Not Allowed` and an `Allow` header,
+    /// without enqueueing the request. Without a policy every method is delivered.
+    pub fn method_policy(mut self, policy: MethodPolicy) -> Self {
+        self.config.method_policy = Some(policy);
+        self
+    }
+
+    /// Use `pages` for the error responses the server generates itself.
+    pub fn error_pages(mut self, pages: ErrorPages) -> Self {
+        self.config.error_pages = pages;
+        self
+    }
+
+    /// TCP options for the listening socket and accepted connections.
+    pub fn socket_options(mut self, options: SocketOptions) -> Self {
+        self.config.socket_options = options;
+        self
+    }
+
+    /// Whether responses are written with vectored writes (`writev`), sending the head and
+    /// body in one syscall without copying them together.
+    ///
+    /// By default Hyper decides per connection, which already coalesces small responses into
+    /// one write on TCP. Turn it off for transports that handle vectored writes poorly.
+    pub fn writev(mut self, enabled: bool) -> Self {
+        self.config.writev = Some(enabled);
+        self
+    }
+
+    /// The most a connection buffers while reading a request head or writing a response,
+    /// about 400 KiB by default.
+    ///
+    /// # Panics
+    ///
+    /// If `max` is below 8192, the least Hyper accepts.
+    pub fn max_buf_size(mut self, max: usize) -> Self {
+        assert!(max >= 8192, "max_buf_size must be at least 8192");
+        self.config.max_buf_size = Some(max);
+        self
+    }
+
+    /// Hold back flushes until a batch of pipelined responses is written, so clients that
+    /// pipeline get several responses per syscall. Experimental in Hyper; off by default.
+    pub fn pipeline_flush(mut self, enabled: bool) -> Self {
+        self.config.pipeline_flush = enabled;
+        self
+    }
+
+    /// Inspect each request's head before its body is read, to reject it cheaply or adjust
+    /// its body limit.
+    ///
+    /// Runs after the parser profile and method policy checks, on the connection's task, so
+    /// keep it fast and non-blocking.
+    ///
+    /// ```no_run
+    /// use async_tiny::{Decision, Response, Server};
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let server = Server::builder()
+    ///     .on_head(|head| {
+    ///         if !head.headers.contains_key("authorization") {
+    ///             Decision::Reject(Response::from_status_and_string(401, "Unauthorized"))
+    ///         } else if head.url.starts_with("/upload") {
+    ///             Decision::Limit(512 * 1024 * 1024)
+    ///         } else {
+    ///             Decision::Allow
+    ///         }
+    ///     })
+    ///     .http("127.0.0.1:8080")
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_head<F>(mut self, decide: F) -> Self
+    where
+        F: Fn(&RequestHead<'_>) -> Decision + Send + Sync + 'static,
+    {
+        self.config.on_head = Some(Box::new(decide));
+        self
+    }
+
+    /// Answer requests for paths matching `pattern` with `handler` on the connection task,
+    /// without queueing them for [`Server::next`].
+    ///
+    /// Meant for load balancer health checks and metrics scrapes, which then get answered
+    /// promptly however deep the queue is. They skip the rate limit, the access log and
+    /// everything else that runs after the request head is read, except maintenance mode.
+    /// The handler runs on the connection task, so it must be quick and must not block.
+    ///
+    /// ```no_run
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// use async_tiny::{Response, Server};
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let ready = Arc::new(AtomicBool::new(false));
+    /// let probe = ready.clone();
+    /// let mut server = Server::builder()
+    ///     .fast_path("/healthz", |_| Response::from_string("ok"))
+    ///     .fast_path("/readyz", move |_| match probe.load(Ordering::Relaxed) {
+    ///         true => Response::from_string("ready"),
+    ///         false => Response::from_status_and_string(503, "warming up"),
+    ///     })
+    ///     .http("0.0.0.0:8080")
+    ///     .await?;
+    /// ready.store(true, Ordering::Relaxed);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn fast_path<F>(mut self, pattern: &str, handler: F) -> Self
+    where
+        F: Fn(&RequestHead<'_>) -> Response + Send + Sync + 'static,
+    {
+        self.config
+            .fast_paths
+            .push((Pattern::parse(pattern), Box::new(handler)));
+        self
+    }
+
+    /// Rewrite every response passed to [`Request::respond`] before it's sent, with the
+    /// request it answers at hand: add headers, rewrite HTML, append a debug banner.
+    ///
+    /// Runs on the task calling `respond`. Responses the server makes up itself, like error
+    /// pages for rejected requests, don't go through it.
+    ///
+    /// ```no_run
+    /// use async_tiny::{Header, Server};
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let server = Server::builder()
+    ///     .on_response(|req, resp| {
+    ///         let html = resp
+    ///             .headers()
+    ///             .get("content-type")
+    ///             .is_some_and(|v| v.as_bytes().starts_with(b"text/html"));
+    ///         let resp = resp.with_header(Header::new("X-Served-Path", req.url()).unwrap());
+    ///         if html {
+    ///             let banner = format!("{}<!-- dev build -->", resp.body());
+    ///             resp.with_body(banner)
+    ///         } else {
+    ///             resp
+    ///         }
+    ///     })
+    ///     .http("127.0.0.1:8080")
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Request::respond`]: crate::Request::respond
+    pub fn on_response<F>(mut self, rewrite: F) -> Self
+    where
+        F: Fn(&Request, Response) -> Response + Send + Sync + 'static,
+    {
+        self.config.on_response = Some(Arc::new(rewrite));
+        self
+    }
+
+    /// Reload open browser tabs when files under the watched directories change; see
+    /// [`dev`](crate::dev). Requires the `dev` feature, and isn't meant for production.
+    #[cfg(feature = "dev")]
+    pub fn live_reload(mut self, live_reload: LiveReload) -> Self {
+        self.config.live_reload = Some(Arc::new(live_reload));
+        self
+    }
+
+    /// Drain the server (see [`Server::drain`]) as soon as a file under `dir` changes, so the
+    /// request loop ends and the process can exit to be restarted by its supervisor.
+    ///
+    /// Call it again to watch more directories. Requests already queued are still answered
+    /// first. Requires the `dev` feature.
+    ///
+    /// ```no_run
+    /// use async_tiny::{Response, Server};
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// // Under `cargo watch -x run`, or systemd with `Restart=always`.
+    /// let mut server = Server::builder()
+    ///     .restart_on_change("src")
+    ///     .restart_on_change("assets")
+    ///     .http("127.0.0.1:8080")
+    ///     .await?;
+    /// while let Some(request) = server.next().await {
+    ///     let _ = request.respond(Response::from_string("hello"));
+    /// }
+    /// // Back here once something changed and the queue emptied.
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "dev")]
+    pub fn restart_on_change(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.restart_watch.push(dir.into());
+        self
+    }
+
+    /// Answer panics and errors in [`Server::serve`] handlers with an HTML page showing the
+    /// message, backtrace (for panics) or error chain, and the request, instead of the error
+    /// page. Errors keep the status [`map_errors`](Self::map_errors) gives them.
+    ///
+    /// Installs a panic hook that records backtraces, on top of the existing one. Requires
+    /// the `dev` feature; never turn it on in production, as the page shows request headers.
+    #[cfg(feature = "dev")]
+    pub fn debug_pages(mut self) -> Self {
+        self.config.debug_pages = true;
+        self
+    }
+
+    /// Answer CORS preflights and add CORS headers to responses; see [`cors`](crate::cors).
+    pub fn cors(mut self, cors: Cors) -> Self {
+        self.config.cors = Some(cors);
+        self
+    }
+
+    /// Strip or set headers on every response; see [`header_policy`](crate::header_policy).
+    pub fn response_headers(mut self, policy: HeaderPolicy) -> Self {
+        self.config.header_policy = Some(policy);
+        self
+    }
+
+    /// Add `name: value` to every response that doesn't already set `name`, such as
+    /// `Cache-Control: no-store` or `Cross-Origin-Opener-Policy: same-origin`; see
+    /// [`HeaderPolicy::default_header`]. Works alongside
+    /// [`response_headers`](Self::response_headers), whichever is called first; headers that
+    /// policy strips stay stripped.
+    pub fn default_header(mut self, name: &str, value: &str) -> Self {
+        let defaults = std::mem::take(&mut self.config.default_headers);
+        self.config.default_headers = defaults.default_header(name, value);
+        self
+    }
+
+    /// Answer clients over their quota with `429`; see [`rate_limit`](crate::rate_limit).
+    pub fn rate_limit(mut self, limits: RateLimit) -> Self {
+        self.config.rate_limit = Some(limits);
+        self
+    }
+
+    /// Answer trap paths and rate-limited clients slowly; see [`tarpit`](crate::tarpit).
+    pub fn tarpit(mut self, tarpit: Tarpit) -> Self {
+        self.config.tarpit = Some(tarpit);
+        self
+    }
+
+    /// Turn errors returned by [`Server::serve`] handlers into responses with `mapper`
+    /// instead of an [`ErrorMapper`](crate::map_error::ErrorMapper) using the error pages.
+    pub fn map_errors(mut self, mapper: impl MapError) -> Self {
+        self.config.map_error = Some(Arc::new(mapper));
+        self
+    }
+
+    /// Report, and optionally ban, clients that request decoy paths; see
+    /// [`honeypot`](crate::honeypot).
+    pub fn honeypot(mut self, honeypot: Honeypot) -> Self {
+        self.config.honeypot = Some(honeypot);
+        self
+    }
+
+    /// Queue requests whose path starts with `prefix` in the `priority` lane.
+    ///
+    /// Prefixes are checked in the order they were added, before any
+    /// [`ServerBuilder::priority`] callback.
+    ///
+    /// ```no_run
+    /// use async_tiny::{Priority, Server};
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let server = Server::builder()
+    ///     .priority_prefix("/healthz", Priority::High)
+    ///     .priority_prefix("/admin/", Priority::High)
+    ///     .priority_prefix("/bulk/", Priority::Low)
+    ///     .http("127.0.0.1:8080")
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn priority_prefix(mut self, prefix: impl Into<String>, priority: Priority) -> Self {
+        self.config
+            .priority_prefixes
+            .push((prefix.into(), priority));
+        self
+    }
+
+    /// Choose each request's [`Priority`] lane from its head, before the body is read.
+    /// Requests without a matching [`ServerBuilder::priority_prefix`] go through `classify`;
+    /// without either, everything is `Normal`.
+    pub fn priority<F>(mut self, classify: F) -> Self
+    where
+        F: Fn(&RequestHead<'_>) -> Priority + Send + Sync + 'static,
+    {
+        self.config.classifier = Some(Box::new(classify));
+        self
+    }
+
+    /// Track response times overall and for the routes in `stats`, read back with
+    /// [`Server::stats`].
+    ///
+    /// Only requests that reach [`Server::next`] are counted, from the moment their head
+    /// arrived to the moment the response was handed back.
+    pub fn latency_stats(mut self, stats: LatencyStats) -> Self {
+        self.config.latency = Some(Arc::new(stats));
+        self
+    }
+
+    /// Refuse some requests with `503` while the process is saturated, by [`Priority`]; see
+    /// [`load_shed`](crate::load_shed).
+    pub fn load_shed(mut self, shedder: LoadShedder) -> Self {
+        self.config.load_shed = Some(shedder);
+        self
+    }
+
+    /// Log a warning when a request waits longer than `threshold` in the queue before
+    /// [`Server::next`] hands it out. Not logged in silent mode.
+    pub fn slow_queue_warning(mut self, threshold: Duration) -> Self {
+        self.config.slow_queue = Some(threshold);
+        self
+    }
+
+    /// Answer requests that have waited longer than `max` in the queue with `503` instead of
+    /// handing them to [`Server::next`]; by then the client has likely given up, and working
+    /// on them only delays the requests behind.
+    pub fn max_queue_time(mut self, max: Duration) -> Self {
+        self.config.max_queue_time = Some(max);
+        self
+    }
+
+    /// Accept on `n` listening sockets per address instead of one, each with its own accept
+    /// loop, so accepting scales across the runtime's worker threads on busy multi-core hosts.
+    ///
+    /// The sockets share the port through `SO_REUSEPORT`, which also has the kernel spread new
+    /// connections across them; binding fails where the platform lacks it (e.g. Windows).
+    /// Connections are handled on the Tokio runtime the server was started on, unless
+    /// [`acceptor_threads`](Self::acceptor_threads) gives each acceptor a thread of its own.
+    /// Applies to [`ServerBuilder::http`].
+    pub fn acceptors(mut self, n: usize) -> Self {
+        self.config.acceptors = n;
+        self
+    }
+
+    /// Run each acceptor on a thread of its own, with a single-threaded Tokio runtime that also
+    /// serves the connections it accepts, instead of on the runtime the server was started on.
+    ///
+    /// A connection then stays on one thread from accept to close, and with
+    /// [`pin_acceptors`](Self::pin_acceptors) on one core, which avoids cross-core traffic on
+    /// NUMA hosts. With [`acceptors`](Self::acceptors), this sets how many threads handle
+    /// connections. Requests still go to the one queue [`Server::next`] reads, wherever that
+    /// loop runs. An acceptor thread exits once its connections close after the server is
+    /// dropped or drained.
+    ///
+    /// ```no_run
+    /// use async_tiny::Server;
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// // Four connection threads, on cores 0 to 3.
+    /// let mut server = Server::builder()
+    ///     .acceptors(4)
+    ///     .pin_acceptors(0..4)
+    ///     .http("0.0.0.0:8080")
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn acceptor_threads(mut self, enabled: bool) -> Self {
+        self.config.acceptor_threads = enabled;
+        self
+    }
+
+    /// Pin acceptor threads to `cores`, as the OS numbers them: the first acceptor to the
+    /// first core, and so on, wrapping around. Turns on
+    /// [`acceptor_threads`](Self::acceptor_threads).
+    ///
+    /// Pinning is best effort: on platforms without it, such as macOS, the threads run
+    /// unpinned.
+    pub fn pin_acceptors(mut self, cores: impl IntoIterator<Item = usize>) -> Self {
+        self.config.acceptor_cores = cores.into_iter().collect();
+        self.config.acceptor_threads = true;
+        self
+    }
+
+    /// Bind an HTTP/1 server on addr like "127.0.0.1:8080", "localhost:8080",
+    /// `("0.0.0.0", 8080)` or a [`SocketAddr`].
+    ///
+    /// A hostname is resolved and every address it resolves to is bound, so "localhost:8080"
+    /// listens on both `127.0.0.1` and `::1` where both exist. Addresses that can't be bound
+    /// are skipped as long as one can, except when the port is already in use.
+    pub async fn http(self, addr: impl ToSocketAddrs) -> std::io::Result<Server> {
+        let mut addrs: Vec<SocketAddr> = lookup_host(addr).await?.collect();
+        addrs.sort();
+        addrs.dedup();
+
+        let acceptors = self.config.acceptors.max(1);
+        let mut options = self.config.socket_options.clone();
+        if acceptors > 1 {
+            options = options.reuse_port(true);
+        }
+
+        let mut listeners = Vec::new();
+        let mut last_err = None;
+        for addr in addrs {
+            match options.bind(addr) {
+                Ok(listener) => {
+                    // With port 0, the other acceptors join the port the first one was given.
+                    let addr = listener.local_addr()?;
+                    listeners.push(listener);
+                    for _ in 1..acceptors {
+                        listeners.push(options.bind(addr)?);
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::AddrInUse => return Err(e),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        if listeners.is_empty() {
+            return Err(last_err.unwrap_or_else(|| into_io_error("address resolved to nothing")));
+        }
+        Server::start(listeners, self.config)
+    }
+
+    /// Serve HTTP/1 over streams from `streams`; see [`Server::from_streams`].
+    pub fn serve_streams<S>(self, streams: mpsc::Receiver<S>) -> Server
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        Server::spawn(self.config, |tx, config| {
+            vec![tokio::spawn(stream_loop(
+                streams,
+                tx.clone(),
+                config.clone(),
+            ))]
+        })
+    }
+
+    /// Serve HTTP/1 on vsock port `port` of context `cid`.
+    ///
+    /// Use `VMADDR_CID_ANY` (`u32::MAX`) as `cid` to accept from any context. Requests have no
+    /// peer address, and [`SocketOptions`] don't apply. Requires the `vsock` feature.
+    #[cfg(feature = "vsock")]
+    pub async fn vsock(self, cid: u32, port: u32) -> std::io::Result<Server> {
+        crate::vsock::start(cid, port, self.config)
+    }
+
+    /// Serve HTTP/1 on a Windows named pipe like `r"\\.\pipe\myapp"`.
+    ///
+    /// Fails if another process already owns the pipe. [`Request`](crate::Request)s from pipe
+    /// clients have no peer address, so the access log shows `-` in its place, and
+    /// [`SocketOptions`] don't apply.
+    #[cfg(windows)]
+    pub async fn named_pipe(self, name: &str) -> std::io::Result<Server> {
+        crate::named_pipe::start(name, self.config)
+    }
+
+    /// Bind an HTTP/1 server on `port` for both IPv4 and IPv6.
+    ///
+    /// This is a single `[::]` socket accepting IPv4-mapped addresses where the platform allows
+    /// it, and separate `0.0.0.0` and `[::]` sockets where it doesn't (e.g. OpenBSD, or Linux
+    /// with `net.ipv6.bindv6only` set). On hosts without IPv6 it binds IPv4 only.
+    pub async fn http_dual_stack(self, port: u16) -> std::io::Result<Server> {
+        let listeners = self.config.socket_options.bind_dual_stack(port)?;
+        Server::start(listeners, self.config)
+    }
+}
+
+impl Config {
+    pub(crate) fn report(&self, error: ConnectionError) {
+        self.errors.report(error, self.silent);
+    }
+
+    /// The response from the first [`ServerBuilder::fast_path`] matching `head`, if any.
+    pub(crate) fn fast_path(&self, head: &RequestHead<'_>) -> Option<Response> {
+        let path = head.url.split('?').next().unwrap_or("");
+        self.fast_paths
+            .iter()
+            .find(|(pattern, _)| pattern.matches(path))
+            .map(|(_, handler)| handler(head))
+    }
+
+    pub(crate) fn priority(&self, head: &RequestHead<'_>) -> Priority {
+        let path = head.url.split('?').next().unwrap_or("");
+        if let Some((_, priority)) = self
+            .priority_prefixes
+            .iter()
+            .find(|(prefix, _)| path.starts_with(prefix.as_str()))
+        {
+            return *priority;
+        }
+        self.classifier
+            .as_ref()
+            .map_or(Priority::Normal, |classify| classify(head))
+    }
+
+    /// A connection builder with the configured HTTP/1 options.
+    pub(crate) fn http1(&self) -> http1::Builder {
+        let mut builder = http1::Builder::new();
+        self.parser_profile.configure(&mut builder);
+        if let Some(enabled) = self.writev {
+            builder.writev(enabled);
+        }
+        if let Some(max) = self.max_buf_size {
+            builder.max_buf_size(max);
+        }
+        builder.pipeline_flush(self.pipeline_flush);
+        builder
+    }
+
+    /// The deadline for a request that started at `started`, from the budget and deadline header.
+    pub(crate) fn deadline(&self, started: Instant, headers: &HeaderMap) -> Option<Instant> {
+        let from_header = self
+            .deadline_header
+            .as_ref()
+            .and_then(|name| headers.get(name))
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .filter(|secs| !secs.is_nan())
+            .and_then(|secs| {
+                Duration::try_from_secs_f64(secs.min(MAX_HEADER_BUDGET.as_secs_f64())).ok()
+            });
+
+        let budget = match (self.request_budget, from_header) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        budget.and_then(|b| started.checked_add(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deadline_for(config: &Config, timeout: &str) -> Option<Duration> {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-request-timeout", timeout.parse().unwrap());
+        let started = Instant::now();
+        config
+            .deadline(started, &headers)
+            .map(|deadline| deadline - started)
+    }
+
+    fn header_only() -> Config {
+        Config {
+            deadline_header: Some(HeaderName::from_static("x-request-timeout")),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn header_budget_is_used() {
+        assert_eq!(
+            deadline_for(&header_only(), "1.5"),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(deadline_for(&header_only(), "soon"), None);
+        assert_eq!(deadline_for(&header_only(), "-1"), None);
+        assert_eq!(deadline_for(&header_only(), "NaN"), None);
+    }
+
+    #[test]
+    fn huge_header_budget_is_capped_instead_of_overflowing() {
+        for huge in ["1.8e19", "1e300", "18446744073709551615"] {
+            assert_eq!(
+                deadline_for(&header_only(), huge),
+                Some(MAX_HEADER_BUDGET),
+                "{}",
+                huge
+            );
+        }
+    }
+
+    #[test]
+    fn shorter_of_budget_and_header_wins() {
+        let config = Config {
+            request_budget: Some(Duration::from_secs(2)),
+            ..header_only()
+        };
+        assert_eq!(deadline_for(&config, "1e19"), Some(Duration::from_secs(2)));
+        assert_eq!(
+            deadline_for(&config, "0.5"),
+            Some(Duration::from_millis(500))
+        );
+    }
+}
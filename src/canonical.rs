@@ -0,0 +1,150 @@
+//! Redirecting requests to one canonical scheme and host.
+//!
+//! ```
+//! use async_tiny::canonical::CanonicalHost;
+//!
+//! let canonical = CanonicalHost::new().strip_www(true).force_https(true).except("/healthz");
+//! # let _ = canonical;
+//! ```
+//!
+//! In the request loop, answer with `canonical.redirect(&request)` when it returns `Some`.
+
+use http::header::{HOST, LOCATION};
+use http::{HeaderName, HeaderValue, Method};
+
+use crate::{mount, Header, Request, Response};
+
+/// Redirect rules for sending clients to their canonical URL.
+///
+/// The current scheme is read from `X-Forwarded-Proto` (defaulting to `http`), since TLS is
+/// expected to terminate at a proxy in front of the server. With [`force_https`] enabled, make
+/// sure that proxy sets the header, or every request will be redirected.
+///
+/// [`force_https`]: CanonicalHost::force_https
+#[derive(Clone, Debug, Default)]
+pub struct CanonicalHost {
+    host: Option<String>,
+    strip_www: bool,
+    https: bool,
+    except: Vec<String>,
+}
+
+impl CanonicalHost {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Redirect every other host to `host` (which may include a port).
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+
+    /// Redirect `www.example.com` to `example.com`.
+    pub fn strip_www(mut self, enabled: bool) -> Self {
+        self.strip_www = enabled;
+        self
+    }
+
+    /// Redirect plain HTTP requests to HTTPS.
+    pub fn force_https(mut self, enabled: bool) -> Self {
+        self.https = enabled;
+        self
+    }
+
+    /// Never redirect paths under `prefix`, e.g. load balancer health checks. Like a
+    /// [`Mount`](crate::mount::Mount) prefix, it matches whole segments: `/healthz` covers
+    /// `/healthz` and `/healthz/db` but not `/healthz-admin`.
+    pub fn except(mut self, prefix: impl Into<String>) -> Self {
+        let prefix = prefix.into();
+        self.except.push(format!("/{}", prefix.trim_matches('/')));
+        self
+    }
+
+    /// The redirect for `req`, or `None` if it's already canonical or exempt.
+    ///
+    /// GET and HEAD get `301 Moved Permanently`; other methods get `308 Permanent Redirect`
+    /// so clients resend the body.
+    pub fn redirect(&self, req: &Request) -> Option<Response> {
+        let url = req.url();
+        if self
+            .except
+            .iter()
+            .any(|prefix| mount::strip(url, prefix).is_some())
+        {
+            return None;
+        }
+
+        let host = req.headers().get(HOST)?.to_str().ok()?;
+        let scheme = req
+            .headers()
+            .get(HeaderName::from_static("x-forwarded-proto"))
+            .and_then(|v| v.to_str().ok())
+            .map(|v| {
+                v.split(',')
+                    .next()
+                    .unwrap_or("")
+                    .trim()
+                    .to_ascii_lowercase()
+            })
+            .unwrap_or_else(|| "http".to_string());
+
+        let target_host = match &self.host {
+            Some(h) => h.as_str(),
+            None if self.strip_www => strip_www(host),
+            None => host,
+        };
+        let target_scheme = if self.https { "https" } else { scheme.as_str() };
+
+        if target_scheme == scheme && target_host.eq_ignore_ascii_case(host) {
+            return None;
+        }
+
+        let location = format!("{}://{}{}", target_scheme, target_host, url);
+        let status = if req.method() == Method::GET || req.method() == Method::HEAD {
+            301
+        } else {
+            308
+        };
+        Some(
+            Response::empty(status)
+                .with_header(Header(LOCATION, HeaderValue::from_str(&location).ok()?)),
+        )
+    }
+}
+
+fn strip_www(host: &str) -> &str {
+    match host.get(..4) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("www.") => &host[4..],
+        _ => host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redirects(canonical: &CanonicalHost, url: &str) -> bool {
+        let req = Request::fake(&Method::GET, url, b"")
+            .with_header(Header::new("Host", "www.example.com").unwrap());
+        canonical.redirect(&req).is_some()
+    }
+
+    #[test]
+    fn except_matches_whole_segments() {
+        let canonical = CanonicalHost::new().strip_www(true).except("/healthz");
+        assert!(!redirects(&canonical, "/healthz"));
+        assert!(!redirects(&canonical, "/healthz/db"));
+        assert!(!redirects(&canonical, "/healthz?verbose=1"));
+        assert!(redirects(&canonical, "/healthz-admin"));
+        assert!(redirects(&canonical, "/healthzzz"));
+        assert!(redirects(&canonical, "/"));
+    }
+
+    #[test]
+    fn except_ignores_surrounding_slashes() {
+        let canonical = CanonicalHost::new().strip_www(true).except("/static/");
+        assert!(!redirects(&canonical, "/static/app.js"));
+        assert!(redirects(&canonical, "/statics"));
+    }
+}
@@ -0,0 +1,72 @@
+//! CBOR (RFC 8949) bodies, enabled with the `cbor` feature: [`Request::cbor`] and
+//! [`Response::cbor`].
+//!
+//! Encoding and decoding are [`ciborium`]'s: structs become maps keyed by field name, and
+//! enums a variant-name string or a one-entry map, as in JSON. Tags are ignored when
+//! decoding.
+//!
+//! ```
+//! use async_tiny::{Method, Request, Response};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize, Debug, PartialEq)]
+//! struct Reading {
+//!     sensor: String,
+//!     celsius: f32,
+//! }
+//!
+//! let reading = Reading { sensor: "attic".to_string(), celsius: 21.5 };
+//! let response = Response::cbor(&reading).unwrap();
+//! assert_eq!(response.headers()["content-type"], "application/cbor");
+//! assert_eq!(
+//!     response.body_bytes().as_ref(),
+//!     b"\xa2\x66sensor\x65attic\x67celsius\xf9\x4d\x60",
+//! );
+//!
+//! let request = Request::fake(&Method::POST, "/readings", response.body_bytes());
+//! assert_eq!(request.cbor::<Reading>().unwrap(), reading);
+//! ```
+//!
+//! [`Request::cbor`]: crate::Request::cbor
+//! [`Response::cbor`]: crate::Response::cbor
+
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Why a value couldn't be encoded or decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cbor: {}", self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// `value` encoded as CBOR.
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    ciborium::into_writer(value, &mut out).map_err(|e| match e {
+        ciborium::ser::Error::Io(e) => Error(e.to_string()),
+        ciborium::ser::Error::Value(message) => Error(message),
+    })?;
+    Ok(out)
+}
+
+/// Decodes one value from `bytes`, which must hold nothing else.
+pub fn from_slice<T: DeserializeOwned>(mut bytes: &[u8]) -> Result<T, Error> {
+    let value = ciborium::from_reader(&mut bytes).map_err(|e| match e {
+        ciborium::de::Error::Io(_) => Error("unexpected end of input".to_string()),
+        ciborium::de::Error::Syntax(offset) => Error(format!("malformed at byte {}", offset)),
+        ciborium::de::Error::Semantic(_, message) => Error(message),
+        ciborium::de::Error::RecursionLimitExceeded => Error("nested too deeply".to_string()),
+    })?;
+    if !bytes.is_empty() {
+        return Err(Error("trailing bytes after the value".to_string()));
+    }
+    Ok(value)
+}
@@ -0,0 +1,214 @@
+//! Decoding and encoding for the handful of charsets seen in form posts and plain-text bodies.
+
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Charset {
+    Utf8,
+    Ascii,
+    Latin1,
+    Windows1252,
+}
+
+/// Windows-1252 code points for bytes 0x80..=0x9F; `None` marks the five unassigned bytes.
+#[rustfmt::skip]
+const WINDOWS_1252_HIGH: [Option<char>; 32] = [
+    Some('\u{20AC}'), None, Some('\u{201A}'), Some('\u{0192}'),
+    Some('\u{201E}'), Some('\u{2026}'), Some('\u{2020}'), Some('\u{2021}'),
+    Some('\u{02C6}'), Some('\u{2030}'), Some('\u{0160}'), Some('\u{2039}'),
+    Some('\u{0152}'), None, Some('\u{017D}'), None,
+    None, Some('\u{2018}'), Some('\u{2019}'), Some('\u{201C}'),
+    Some('\u{201D}'), Some('\u{2022}'), Some('\u{2013}'), Some('\u{2014}'),
+    Some('\u{02DC}'), Some('\u{2122}'), Some('\u{0161}'), Some('\u{203A}'),
+    Some('\u{0153}'), None, Some('\u{017E}'), Some('\u{0178}'),
+];
+
+impl Charset {
+    pub(crate) fn from_label(label: &str) -> Option<Self> {
+        match label.trim().trim_matches('"').to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Charset::Utf8),
+            "us-ascii" | "ascii" => Some(Charset::Ascii),
+            "iso-8859-1" | "iso8859-1" | "latin1" | "l1" => Some(Charset::Latin1),
+            "windows-1252" | "cp1252" => Some(Charset::Windows1252),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Charset::Utf8 => "utf-8",
+            Charset::Ascii => "us-ascii",
+            Charset::Latin1 => "iso-8859-1",
+            Charset::Windows1252 => "windows-1252",
+        }
+    }
+
+    /// Strict decoding: any byte that isn't valid in the charset is an error.
+    pub(crate) fn decode(self, bytes: &[u8]) -> Result<String, TextError> {
+        match self {
+            Charset::Utf8 => String::from_utf8(bytes.to_vec()).map_err(|_| TextError::Malformed),
+            Charset::Ascii if bytes.is_ascii() => Ok(bytes.iter().map(|&b| b as char).collect()),
+            Charset::Ascii => Err(TextError::Malformed),
+            Charset::Latin1 => Ok(bytes.iter().map(|&b| b as char).collect()),
+            Charset::Windows1252 => bytes
+                .iter()
+                .map(|&b| windows_1252_char(b).ok_or(TextError::Malformed))
+                .collect(),
+        }
+    }
+
+    /// Lossy decoding: invalid bytes become U+FFFD.
+    pub(crate) fn decode_lossy(self, bytes: &[u8]) -> String {
+        match self {
+            Charset::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Charset::Ascii => bytes
+                .iter()
+                .map(|&b| if b.is_ascii() { b as char } else { '\u{FFFD}' })
+                .collect(),
+            Charset::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+            Charset::Windows1252 => bytes
+                .iter()
+                .map(|&b| windows_1252_char(b).unwrap_or('\u{FFFD}'))
+                .collect(),
+        }
+    }
+
+    /// Encodes `text`, failing on characters the charset can't represent.
+    pub(crate) fn encode(self, text: &str) -> Result<Vec<u8>, TextError> {
+        match self {
+            Charset::Utf8 => Ok(text.as_bytes().to_vec()),
+            Charset::Ascii => text
+                .chars()
+                .map(|c| {
+                    if c.is_ascii() {
+                        Ok(c as u8)
+                    } else {
+                        Err(TextError::Unencodable(c))
+                    }
+                })
+                .collect(),
+            Charset::Latin1 => text
+                .chars()
+                .map(|c| u8::try_from(c).map_err(|_| TextError::Unencodable(c)))
+                .collect(),
+            Charset::Windows1252 => text
+                .chars()
+                .map(|c| {
+                    (0..=255u8)
+                        .find(|&b| windows_1252_char(b) == Some(c))
+                        .ok_or(TextError::Unencodable(c))
+                })
+                .collect(),
+        }
+    }
+}
+
+fn windows_1252_char(b: u8) -> Option<char> {
+    match b {
+        0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+        _ => Some(b as char),
+    }
+}
+
+/// Failure decoding or encoding text in a particular charset.
+#[derive(Debug)]
+pub enum TextError {
+    /// The charset label isn't one async_tiny knows how to handle.
+    UnsupportedCharset(String),
+    /// The bytes aren't valid in the declared charset.
+    Malformed,
+    /// The character can't be represented in the target charset.
+    Unencodable(char),
+}
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextError::UnsupportedCharset(label) => write!(f, "unsupported charset: {}", label),
+            TextError::Malformed => write!(f, "body is not valid in its declared charset"),
+            TextError::Unencodable(c) => write!(f, "character {:?} cannot be encoded", c),
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Charset; 4] = [
+        Charset::Utf8,
+        Charset::Ascii,
+        Charset::Latin1,
+        Charset::Windows1252,
+    ];
+
+    #[test]
+    fn labels() {
+        for charset in ALL {
+            assert_eq!(Charset::from_label(charset.label()), Some(charset));
+        }
+        assert_eq!(Charset::from_label(" \"UTF-8\" "), Some(Charset::Utf8));
+        assert_eq!(Charset::from_label("Latin1"), Some(Charset::Latin1));
+        assert_eq!(Charset::from_label("CP1252"), Some(Charset::Windows1252));
+        assert_eq!(Charset::from_label("utf-16"), None);
+        assert_eq!(Charset::from_label(""), None);
+    }
+
+    #[test]
+    fn strict_decoding() {
+        assert_eq!(Charset::Utf8.decode("né".as_bytes()).unwrap(), "né");
+        assert!(Charset::Utf8.decode(b"\xff").is_err());
+        assert!(Charset::Utf8.decode(b"\xc3").is_err());
+        assert!(Charset::Ascii.decode(b"\x80").is_err());
+        assert_eq!(Charset::Latin1.decode(b"n\xe9\x80").unwrap(), "né\u{80}");
+        assert_eq!(
+            Charset::Windows1252.decode(b"\x80\x93\x96\xe9").unwrap(),
+            "€“–é"
+        );
+        for unassigned in [0x81, 0x8D, 0x8F, 0x90, 0x9D] {
+            assert!(Charset::Windows1252.decode(&[unassigned]).is_err());
+        }
+    }
+
+    #[test]
+    fn lossy_decoding() {
+        assert_eq!(Charset::Utf8.decode_lossy(b"a\xffb"), "a\u{FFFD}b");
+        assert_eq!(Charset::Ascii.decode_lossy(b"a\xe9b"), "a\u{FFFD}b");
+        assert_eq!(Charset::Windows1252.decode_lossy(b"\x81\x80"), "\u{FFFD}€");
+    }
+
+    #[test]
+    fn encoding() {
+        assert!(matches!(
+            Charset::Ascii.encode("né"),
+            Err(TextError::Unencodable('é'))
+        ));
+        assert_eq!(Charset::Latin1.encode("né").unwrap(), b"n\xe9");
+        assert!(matches!(
+            Charset::Latin1.encode("€"),
+            Err(TextError::Unencodable('€'))
+        ));
+        assert_eq!(Charset::Windows1252.encode("€é").unwrap(), b"\x80\xe9");
+        // The C1 controls that Windows-1252 replaced can't be encoded in it.
+        assert!(Charset::Windows1252.encode("\u{80}").is_err());
+    }
+
+    #[test]
+    fn every_single_byte_round_trips() {
+        for charset in [Charset::Latin1, Charset::Windows1252] {
+            for b in 0..=255u8 {
+                if let Ok(text) = charset.decode(&[b]) {
+                    assert_eq!(
+                        charset.encode(&text).unwrap(),
+                        [b],
+                        "{:?} {:#x}",
+                        charset,
+                        b
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Precondition checks for optimistic concurrency on writes (RFC 9110 §13).
+//!
+//! ```
+//! use async_tiny::conditional::{self, Current};
+//! use async_tiny::{Header, Method, Request, Response};
+//!
+//! let req = Request::fake(&Method::PUT, "/doc/1", b"new contents")
+//!     .with_header(Header::new("If-Match", "\"v2\"").unwrap());
+//! let current = Current::exists().etag("\"v3\"");
+//!
+//! let response = match conditional::check_write(&req, current) {
+//!     Some(failed) => failed,
+//!     None => Response::from_string("saved"),
+//! };
+//! assert_eq!(response.status_code(), 412);
+//!
+//! // `If-None-Match: *` makes a PUT create-only.
+//! let req = Request::fake(&Method::PUT, "/doc/2", b"first draft")
+//!     .with_header(Header::new("If-None-Match", "*").unwrap());
+//! assert!(conditional::check_write(&req, Current::missing()).is_none());
+//! assert!(conditional::check_write(&req, Current::exists()).is_some());
+//! ```
+
+use std::time::SystemTime;
+
+use http::header::{IF_MATCH, IF_NONE_MATCH, IF_UNMODIFIED_SINCE};
+
+use crate::{date, etag, Request, Response};
+
+/// The state of the target resource, as [`check_write`] needs it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Current<'a> {
+    exists: bool,
+    etag: Option<&'a str>,
+    last_modified: Option<SystemTime>,
+}
+
+impl<'a> Current<'a> {
+    /// Nothing is stored at the target yet.
+    pub fn missing() -> Self {
+        Self::default()
+    }
+
+    /// The resource exists, with no validators unless given below.
+    pub fn exists() -> Self {
+        Self {
+            exists: true,
+            ..Self::default()
+        }
+    }
+
+    /// The resource's current `ETag`.
+    pub fn etag(mut self, etag: &'a str) -> Self {
+        self.exists = true;
+        self.etag = Some(etag);
+        self
+    }
+
+    /// When the resource last changed.
+    pub fn last_modified(mut self, time: SystemTime) -> Self {
+        self.exists = true;
+        self.last_modified = Some(time);
+        self
+    }
+
+    /// Whether an `If-Match` or `If-None-Match` value names this resource, comparing tags
+    /// with `eq`.
+    fn matches(&self, value: &str, eq: impl Fn(&str, &str) -> bool) -> bool {
+        if value.trim() == "*" {
+            return self.exists;
+        }
+        self.etag
+            .is_some_and(|etag| etag::list_matches(value, |candidate| eq(candidate, etag)))
+    }
+}
+
+/// Evaluates `If-Match`, `If-Unmodified-Since` and `If-None-Match` for a state-changing request.
+///
+/// Returns a ready `412 Precondition Failed` when a precondition fails, or `None` when the
+/// write may go ahead. `If-Unmodified-Since` is ignored when `If-Match` is present or the
+/// resource has no modification time.
+pub fn check_write(req: &Request, current: Current<'_>) -> Option<Response> {
+    let headers = req.headers();
+    let failed = || Some(Response::from_status_and_string(412, "Precondition Failed"));
+
+    if let Some(value) = headers.get(IF_MATCH).and_then(|v| v.to_str().ok()) {
+        if !current.matches(value, etag::strong_eq) {
+            return failed();
+        }
+    } else if let Some(since) = headers.get(IF_UNMODIFIED_SINCE).and_then(date::from_header) {
+        // HTTP dates have one-second resolution, so compare at that granularity.
+        if let Some(modified) = current.last_modified {
+            if whole_seconds(modified) > whole_seconds(since) {
+                return failed();
+            }
+        }
+    }
+
+    if let Some(value) = headers.get(IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+        if current.matches(value, etag::weak_eq) {
+            return failed();
+        }
+    }
+
+    None
+}
+
+fn whole_seconds(t: SystemTime) -> u64 {
+    t.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{Header, Method};
+
+    const MODIFIED: &str = "Sun, 06 Nov 1994 08:49:37 GMT";
+
+    fn modified() -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(784111777)
+    }
+
+    fn put(headers: &[(&str, &str)]) -> Request {
+        headers.iter().fold(
+            Request::fake(&Method::PUT, "/doc", b""),
+            |req, (name, value)| req.with_header(Header::new(name, value).unwrap()),
+        )
+    }
+
+    fn passes(headers: &[(&str, &str)], current: Current<'_>) -> bool {
+        check_write(&put(headers), current).is_none()
+    }
+
+    #[test]
+    fn no_preconditions_pass() {
+        assert!(passes(&[], Current::missing()));
+        assert!(passes(&[], Current::exists().etag("\"v1\"")));
+    }
+
+    #[test]
+    fn if_match_compares_strongly() {
+        let current = Current::exists().etag("\"v1\"");
+        assert!(passes(&[("If-Match", "\"v1\"")], current));
+        assert!(passes(&[("If-Match", "\"v0\", \"v1\"")], current));
+        assert!(!passes(&[("If-Match", "\"v2\"")], current));
+        assert!(!passes(&[("If-Match", "W/\"v1\"")], current));
+        assert!(!passes(&[("If-Match", "\"v1\"")], Current::exists()));
+        assert!(!passes(&[("If-Match", "\"v1\"")], Current::missing()));
+    }
+
+    #[test]
+    fn if_match_star_needs_the_resource_to_exist() {
+        assert!(passes(&[("If-Match", "*")], Current::exists()));
+        assert!(passes(
+            &[("If-Match", "*")],
+            Current::exists().etag("\"v1\"")
+        ));
+        assert!(!passes(&[("If-Match", "*")], Current::missing()));
+    }
+
+    #[test]
+    fn if_none_match_compares_weakly() {
+        let current = Current::exists().etag("\"v1\"");
+        assert!(!passes(&[("If-None-Match", "\"v1\"")], current));
+        assert!(!passes(&[("If-None-Match", "W/\"v1\"")], current));
+        assert!(passes(&[("If-None-Match", "\"v2\"")], current));
+        assert!(passes(&[("If-None-Match", "\"v1\"")], Current::exists()));
+    }
+
+    #[test]
+    fn if_none_match_star_refuses_existing_resources() {
+        assert!(passes(&[("If-None-Match", "*")], Current::missing()));
+        assert!(!passes(&[("If-None-Match", "*")], Current::exists()));
+        assert!(!passes(
+            &[("If-None-Match", "*")],
+            Current::exists().etag("\"v1\"")
+        ));
+    }
+
+    #[test]
+    fn if_unmodified_since_has_one_second_granularity() {
+        let since = [("If-Unmodified-Since", MODIFIED)];
+        let at = |time| Current::exists().last_modified(time);
+        assert!(passes(&since, at(modified())));
+        assert!(passes(&since, at(modified() + Duration::from_millis(999))));
+        assert!(passes(&since, at(modified() - Duration::from_secs(1))));
+        assert!(!passes(&since, at(modified() + Duration::from_secs(1))));
+        // Without a modification time there's nothing to compare.
+        assert!(passes(&since, Current::exists()));
+    }
+
+    #[test]
+    fn if_match_takes_precedence_over_if_unmodified_since() {
+        let current = Current::exists()
+            .etag("\"v1\"")
+            .last_modified(modified() + Duration::from_secs(60));
+        let since = ("If-Unmodified-Since", MODIFIED);
+        assert!(!passes(&[since], current));
+        assert!(passes(&[("If-Match", "\"v1\""), since], current));
+        assert!(!passes(&[("If-Match", "\"v2\""), since], current));
+    }
+}
@@ -0,0 +1,172 @@
+//! Connection and protocol errors, delivered through [`Server::errors`](crate::Server::errors).
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use tokio::sync::broadcast;
+
+/// How many errors a slow subscriber can fall behind before it misses some.
+const ERROR_CAPACITY: usize = 256;
+
+/// Something that went wrong below the request level, where there's no request to answer.
+#[derive(Clone, Debug)]
+pub struct ConnectionError {
+    pub kind: ConnectionErrorKind,
+    /// The remote address, when the transport has one and the connection got that far.
+    pub peer: Option<SocketAddr>,
+    /// Matches [`Request::connection_id`](crate::Request::connection_id); `None` for errors
+    /// before a connection existed, like a failed accept.
+    pub connection_id: Option<u64>,
+    pub at: SystemTime,
+    /// The underlying error, formatted.
+    pub message: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ConnectionErrorKind {
+    /// Accepting a connection failed, e.g. the process ran out of file descriptors.
+    Accept,
+    /// A [`SocketOptions`](crate::SocketOptions) setting couldn't be applied to a connection.
+    SocketOptions,
+    /// The client sent something that isn't HTTP/1, or a head that was too large, or one the
+    /// [`ParserProfile`](crate::ParserProfile) refused.
+    Parse,
+    /// The connection closed partway through a request.
+    Incomplete,
+    /// A read or write timed out.
+    Timeout,
+    /// The connection failed at the socket level, e.g. reset by the peer.
+    Io,
+    Other,
+}
+
+const KINDS: [ConnectionErrorKind; 7] = [
+    ConnectionErrorKind::Accept,
+    ConnectionErrorKind::SocketOptions,
+    ConnectionErrorKind::Parse,
+    ConnectionErrorKind::Incomplete,
+    ConnectionErrorKind::Timeout,
+    ConnectionErrorKind::Io,
+    ConnectionErrorKind::Other,
+];
+
+fn index(kind: ConnectionErrorKind) -> usize {
+    KINDS
+        .iter()
+        .position(|k| *k == kind)
+        .unwrap_or(KINDS.len() - 1)
+}
+
+/// How many errors of each kind the server has seen, from
+/// [`Server::error_counts`](crate::Server::error_counts). Counted whether or not anyone is
+/// subscribed to [`Server::errors`](crate::Server::errors).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ErrorCounts([u64; KINDS.len()]);
+
+impl ErrorCounts {
+    pub fn get(&self, kind: ConnectionErrorKind) -> u64 {
+        self.0[index(kind)]
+    }
+
+    pub fn total(&self) -> u64 {
+        self.0.iter().sum()
+    }
+
+    /// Each kind with its count, including zeros, e.g. to export as metrics.
+    pub fn iter(&self) -> impl Iterator<Item = (ConnectionErrorKind, u64)> + '_ {
+        KINDS.iter().copied().zip(self.0.iter().copied())
+    }
+}
+
+impl ConnectionError {
+    pub(crate) fn new(kind: ConnectionErrorKind, message: impl fmt::Display) -> Self {
+        Self {
+            kind,
+            peer: None,
+            connection_id: None,
+            at: SystemTime::now(),
+            message: message.to_string(),
+        }
+    }
+
+    pub(crate) fn from_hyper(err: &hyper::Error) -> Self {
+        let kind = if err.is_parse() || err.is_parse_too_large() || err.is_parse_status() {
+            ConnectionErrorKind::Parse
+        } else if err.is_incomplete_message() {
+            ConnectionErrorKind::Incomplete
+        } else if err.is_timeout() {
+            ConnectionErrorKind::Timeout
+        } else if std::error::Error::source(err).is_some_and(|e| e.is::<std::io::Error>()) {
+            ConnectionErrorKind::Io
+        } else {
+            ConnectionErrorKind::Other
+        };
+        Self::new(kind, format_args!("{:?}", err))
+    }
+
+    pub(crate) fn on(mut self, peer: Option<SocketAddr>, connection_id: u64) -> Self {
+        self.peer = peer;
+        self.connection_id = Some(connection_id);
+        self
+    }
+}
+
+impl fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self.kind {
+            ConnectionErrorKind::Accept => "Accept error",
+            ConnectionErrorKind::SocketOptions => "Socket option error",
+            _ => "Connection error",
+        };
+        match self.peer {
+            Some(peer) => write!(f, "{} from {}: {}", what, peer, self.message),
+            None => write!(f, "{}: {}", what, self.message),
+        }
+    }
+}
+
+impl std::error::Error for ConnectionError {}
+
+/// Counts errors and fans them out to every [`Server::errors`](crate::Server::errors)
+/// subscriber.
+#[derive(Clone)]
+pub(crate) struct ErrorChannel {
+    tx: broadcast::Sender<ConnectionError>,
+    counts: Arc<[AtomicU64; KINDS.len()]>,
+}
+
+impl Default for ErrorChannel {
+    fn default() -> Self {
+        Self {
+            tx: broadcast::channel(ERROR_CAPACITY).0,
+            counts: Arc::new(Default::default()),
+        }
+    }
+}
+
+impl ErrorChannel {
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<ConnectionError> {
+        self.tx.subscribe()
+    }
+
+    pub(crate) fn counts(&self) -> ErrorCounts {
+        ErrorCounts(std::array::from_fn(|i| {
+            self.counts[i].load(Ordering::Relaxed)
+        }))
+    }
+
+    /// Counts `error` and hands it to subscribers, or prints it unless `silent` when there
+    /// are none.
+    pub(crate) fn report(&self, error: ConnectionError, silent: bool) {
+        self.counts[index(error.kind)].fetch_add(1, Ordering::Relaxed);
+        if self.tx.receiver_count() > 0 {
+            let _ = self.tx.send(error);
+        } else if !silent {
+            eprintln!("{}", error);
+        }
+    }
+}
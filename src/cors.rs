@@ -0,0 +1,409 @@
+//! Cross-origin resource sharing, enabled via [`ServerBuilder::cors`].
+//!
+//! Preflight requests (`OPTIONS` with `Access-Control-Request-Method`) are answered with
+//! `204 No Content` and never reach the application. Other requests from an allowed origin
+//! get `Access-Control-Allow-Origin` and friends added to whatever the application responds.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use async_tiny::cors::Cors;
+//! use async_tiny::{Method, Server};
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! // Public, cookie-less data may be read from anywhere.
+//! let cors = Cors::new()
+//!     .allow_any_origin()
+//!     .max_age(Duration::from_secs(600))
+//!     // The account API takes cookies, so only the company's own sites may call it.
+//!     .route(
+//!         "/account/*",
+//!         Cors::new()
+//!             .allow_origin_fn(|origin| {
+//!                 origin == "https://example.com"
+//!                     || (origin.starts_with("https://") && origin.ends_with(".example.com"))
+//!             })
+//!             .allow_methods(&[Method::GET, Method::POST, Method::DELETE])
+//!             .allow_headers(&["content-type", "x-csrf-token"])
+//!             .allow_credentials(true),
+//!     );
+//! let mut server = Server::builder().cors(cors).http("0.0.0.0:8080").await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Routes are matched like [`LatencyStats::route`](crate::LatencyStats::route): `:name`
+//! matches one path segment and a trailing `*` the rest. The first matching route's policy
+//! replaces the top-level one entirely; requests matching none use the top-level one.
+//!
+//! [`Cors::allow_any_origin`] and [`Cors::allow_credentials`] can't be combined, since that
+//! would let any website read responses with its visitors' cookies. Credentialed requests
+//! need a list of origins or an [`allow_origin_fn`](Cors::allow_origin_fn) predicate.
+//!
+//! [`ServerBuilder::cors`]: crate::ServerBuilder::cors
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use http::header::{
+    ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+    ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE,
+    ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+};
+use http::{HeaderMap, HeaderName, HeaderValue, Method};
+
+use crate::pattern::Pattern;
+use crate::Response;
+
+const REQUEST_PRIVATE_NETWORK: HeaderName =
+    HeaderName::from_static("access-control-request-private-network");
+const ALLOW_PRIVATE_NETWORK: HeaderName =
+    HeaderName::from_static("access-control-allow-private-network");
+
+/// Which origins may call the server, and how.
+#[derive(Clone, Debug)]
+pub struct Cors {
+    origins: Origins,
+    methods: Vec<Method>,
+    headers: Headers,
+    expose: Vec<String>,
+    credentials: bool,
+    max_age: Option<Duration>,
+    private_network: bool,
+    routes: Vec<(Pattern, Cors)>,
+}
+
+type OriginPredicate = dyn Fn(&str) -> bool + Send + Sync;
+
+#[derive(Clone)]
+enum Origins {
+    Any,
+    List(Vec<String>),
+    Predicate(Arc<OriginPredicate>),
+}
+
+impl fmt::Debug for Origins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origins::Any => f.write_str("Any"),
+            Origins::List(list) => f.debug_tuple("List").field(list).finish(),
+            Origins::Predicate(_) => f.write_str("Predicate(..)"),
+        }
+    }
+}
+
+const ANY_WITH_CREDENTIALS: &str = "CORS: `allow_any_origin` can't be combined with \
+     `allow_credentials(true)`, which would let any site make credentialed requests; list \
+     the origins or use `allow_origin_fn`";
+
+#[derive(Clone, Debug)]
+enum Headers {
+    Any,
+    List(Vec<String>),
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cors {
+    /// Allow no origins yet; GET, HEAD and POST once some are allowed, with no extra headers.
+    pub fn new() -> Self {
+        Self {
+            origins: Origins::List(Vec::new()),
+            methods: vec![Method::GET, Method::HEAD, Method::POST],
+            headers: Headers::List(Vec::new()),
+            expose: Vec::new(),
+            credentials: false,
+            max_age: None,
+            private_network: false,
+            routes: Vec::new(),
+        }
+    }
+
+    /// Allow requests from `origin`, e.g. `https://app.example.com`. Can be repeated.
+    pub fn allow_origin(mut self, origin: &str) -> Self {
+        let origin = origin.trim_end_matches('/').to_ascii_lowercase();
+        match &mut self.origins {
+            Origins::List(list) => list.push(origin),
+            _ => self.origins = Origins::List(vec![origin]),
+        }
+        self
+    }
+
+    /// Allow requests from origins for which `allowed` returns `true`, such as every
+    /// subdomain of a site. It's given the `Origin` header as sent, e.g.
+    /// `https://app.example.com`, and the allowed origin is echoed back.
+    pub fn allow_origin_fn<F>(mut self, allowed: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.origins = Origins::Predicate(Arc::new(allowed));
+        self
+    }
+
+    /// Allow requests from every origin, answering with `Access-Control-Allow-Origin: *`.
+    ///
+    /// Panics if [`Cors::allow_credentials`] is on: that would let any site make requests
+    /// with its visitors' cookies and read the answers.
+    ///
+    /// ```should_panic
+    /// use async_tiny::cors::Cors;
+    ///
+    /// let cors = Cors::new().allow_credentials(true).allow_any_origin();
+    /// ```
+    pub fn allow_any_origin(mut self) -> Self {
+        assert!(!self.credentials, "{}", ANY_WITH_CREDENTIALS);
+        self.origins = Origins::Any;
+        self
+    }
+
+    /// The methods preflights may ask for, replacing the default GET, HEAD and POST.
+    pub fn allow_methods(mut self, methods: &[Method]) -> Self {
+        self.methods = methods.to_vec();
+        self
+    }
+
+    /// Request headers preflights may ask for, beyond the CORS-safelisted ones.
+    pub fn allow_headers(mut self, names: &[&str]) -> Self {
+        self.headers = Headers::List(names.iter().map(|n| n.to_ascii_lowercase()).collect());
+        self
+    }
+
+    /// Allow whatever request headers a preflight asks for.
+    pub fn allow_any_header(mut self) -> Self {
+        self.headers = Headers::Any;
+        self
+    }
+
+    /// Response headers scripts may read, beyond the CORS-safelisted ones.
+    pub fn expose_headers(mut self, names: &[&str]) -> Self {
+        self.expose = names.iter().map(|n| n.to_string()).collect();
+        self
+    }
+
+    /// Let browsers send cookies and HTTP authentication along.
+    ///
+    /// Panics if [`Cors::allow_any_origin`] is on; allow specific origins instead.
+    pub fn allow_credentials(mut self, enabled: bool) -> Self {
+        assert!(
+            !(enabled && matches!(self.origins, Origins::Any)),
+            "{}",
+            ANY_WITH_CREDENTIALS
+        );
+        self.credentials = enabled;
+        self
+    }
+
+    /// Let browsers cache a preflight result for `max_age` (`Access-Control-Max-Age`), so
+    /// they don't send one before every request. Browsers cap this, at two hours for Chrome.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Answer Private Network Access preflights, which browsers send before a public site
+    /// calls a server on a private or local address, such as a device on the LAN.
+    pub fn allow_private_network(mut self, enabled: bool) -> Self {
+        self.private_network = enabled;
+        self
+    }
+
+    /// Use `policy` instead of this one for paths matching `pattern`. Earlier routes win.
+    pub fn route(mut self, pattern: &str, policy: Cors) -> Self {
+        self.routes.push((Pattern::parse(pattern), policy));
+        self
+    }
+
+    /// The policy for `url`: the first matching route's, or this one.
+    pub(crate) fn policy_for(&self, url: &str) -> &Cors {
+        let path = url.split('?').next().unwrap_or("");
+        self.routes
+            .iter()
+            .find(|(pattern, _)| pattern.matches(path))
+            .map_or(self, |(_, policy)| policy)
+    }
+
+    /// The preflight response for a request under this policy, or `None` if it isn't a
+    /// preflight.
+    pub(crate) fn preflight(&self, method: &Method, headers: &HeaderMap) -> Option<Response> {
+        if method != Method::OPTIONS {
+            return None;
+        }
+        let requested = headers.get(ACCESS_CONTROL_REQUEST_METHOD)?;
+        let origin = headers.get(ORIGIN)?;
+
+        let mut resp = Response::empty(204);
+        resp.headers.insert(VARY, vary_preflight());
+        let Some(allow_origin) = self.origin_value(origin) else {
+            // No CORS headers is how a preflight is refused.
+            return Some(resp);
+        };
+        let method_ok = Method::from_bytes(requested.as_bytes())
+            .is_ok_and(|m| self.methods.contains(&m) || is_safelisted_method(&m));
+        let requested_headers = headers
+            .get(ACCESS_CONTROL_REQUEST_HEADERS)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        let headers_ok = match &self.headers {
+            Headers::Any => true,
+            Headers::List(allowed) => requested_headers
+                .split(',')
+                .map(str::trim)
+                .filter(|h| !h.is_empty())
+                .all(|h| allowed.iter().any(|a| a.eq_ignore_ascii_case(h))),
+        };
+        if !method_ok || !headers_ok {
+            return Some(resp);
+        }
+
+        resp.headers
+            .insert(ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        let methods = join(self.methods.iter().map(Method::as_str));
+        if let Ok(value) = HeaderValue::from_str(&methods) {
+            resp.headers.insert(ACCESS_CONTROL_ALLOW_METHODS, value);
+        }
+        let allow_headers = match &self.headers {
+            Headers::Any => requested_headers.to_string(),
+            Headers::List(allowed) => join(allowed.iter().map(String::as_str)),
+        };
+        if !allow_headers.is_empty() {
+            if let Ok(value) = HeaderValue::from_str(&allow_headers) {
+                resp.headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, value);
+            }
+        }
+        if self.credentials {
+            resp.headers.insert(
+                ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+        if let Some(max_age) = self.max_age {
+            resp.headers
+                .insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from(max_age.as_secs()));
+        }
+        let private_network = headers
+            .get(REQUEST_PRIVATE_NETWORK)
+            .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"true"));
+        if private_network && self.private_network {
+            resp.headers
+                .insert(ALLOW_PRIVATE_NETWORK, HeaderValue::from_static("true"));
+        }
+        Some(resp)
+    }
+
+    /// Adds this policy's headers to the response for an actual (non-preflight) request from
+    /// `origin`.
+    pub(crate) fn apply(&self, origin: Option<&HeaderValue>, resp: Response) -> Response {
+        let mut resp = match self.origins {
+            // The answer depends on the origin, so caches must keep them apart.
+            Origins::List(_) | Origins::Predicate(_) => resp.with_vary(&["origin"]),
+            Origins::Any => resp,
+        };
+        let Some(allow_origin) = origin.and_then(|o| self.origin_value(o)) else {
+            return resp;
+        };
+        resp.headers
+            .insert(ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        if self.credentials {
+            resp.headers.insert(
+                ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+        if !self.expose.is_empty() {
+            if let Ok(value) = HeaderValue::from_str(&join(self.expose.iter().map(String::as_str)))
+            {
+                resp.headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, value);
+            }
+        }
+        resp
+    }
+
+    /// The `Access-Control-Allow-Origin` value for `origin`, if it's allowed.
+    fn origin_value(&self, origin: &HeaderValue) -> Option<HeaderValue> {
+        match &self.origins {
+            Origins::Any => Some(HeaderValue::from_static("*")),
+            Origins::Predicate(allowed) => {
+                let origin_str = origin.to_str().ok()?;
+                allowed(origin_str).then(|| origin.clone())
+            }
+            Origins::List(list) => {
+                let origin_str = origin.to_str().ok()?;
+                list.iter()
+                    .any(|o| o.eq_ignore_ascii_case(origin_str))
+                    .then(|| origin.clone())
+            }
+        }
+    }
+}
+
+fn is_safelisted_method(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::POST)
+}
+
+fn vary_preflight() -> HeaderValue {
+    HeaderValue::from_static(
+        "origin, access-control-request-method, access-control-request-headers",
+    )
+}
+
+fn join<'a>(items: impl Iterator<Item = &'a str>) -> String {
+    items.collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin(s: &str) -> HeaderValue {
+        HeaderValue::from_str(s).unwrap()
+    }
+
+    #[test]
+    #[should_panic(expected = "allow_any_origin")]
+    fn any_origin_then_credentials_panics() {
+        let _ = Cors::new().allow_any_origin().allow_credentials(true);
+    }
+
+    #[test]
+    #[should_panic(expected = "allow_any_origin")]
+    fn credentials_then_any_origin_panics() {
+        let _ = Cors::new().allow_credentials(true).allow_any_origin();
+    }
+
+    #[test]
+    fn any_origin_answers_star_without_credentials() {
+        let cors = Cors::new().allow_any_origin();
+        let resp = cors.apply(Some(&origin("https://evil.test")), Response::empty(200));
+        assert_eq!(resp.headers[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert!(resp.headers.get(ACCESS_CONTROL_ALLOW_CREDENTIALS).is_none());
+    }
+
+    #[test]
+    fn predicate_echoes_allowed_origins_only() {
+        let cors = Cors::new()
+            .allow_origin_fn(|o| o.starts_with("https://") && o.ends_with(".example.com"))
+            .allow_credentials(true);
+
+        let resp = cors.apply(
+            Some(&origin("https://app.example.com")),
+            Response::empty(200),
+        );
+        assert_eq!(
+            resp.headers[ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+        assert_eq!(resp.headers[ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(resp.headers[VARY], "origin");
+
+        let resp = cors.apply(
+            Some(&origin("https://example.com.evil.test")),
+            Response::empty(200),
+        );
+        assert!(resp.headers.get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+        assert!(resp.headers.get(ACCESS_CONTROL_ALLOW_CREDENTIALS).is_none());
+    }
+}
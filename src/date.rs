@@ -0,0 +1,39 @@
+//! HTTP dates (RFC 9110 §5.6.7), as used by `Date`, `Last-Modified`, `If-Modified-Since` and
+//! friends.
+//!
+//! ```
+//! use std::time::{Duration, UNIX_EPOCH};
+//! use async_tiny::date::{fmt_http_date, parse_http_date};
+//!
+//! let time = UNIX_EPOCH + Duration::from_secs(784111777);
+//! assert_eq!(fmt_http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+//!
+//! // The obsolete RFC 850 and asctime forms are accepted too.
+//! assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), Some(time));
+//! assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), Some(time));
+//! assert_eq!(parse_http_date("yesterday"), None);
+//! ```
+
+use std::time::SystemTime;
+
+use http::HeaderValue;
+
+/// Formats `time` as an IMF-fixdate, truncated to the second.
+pub fn fmt_http_date(time: SystemTime) -> String {
+    httpdate::fmt_http_date(time)
+}
+
+/// Parses any of the three HTTP date formats; `None` if `s` is none of them.
+pub fn parse_http_date(s: &str) -> Option<SystemTime> {
+    httpdate::parse_http_date(s.trim()).ok()
+}
+
+/// `time` as a header value.
+pub(crate) fn header_value(time: SystemTime) -> HeaderValue {
+    HeaderValue::from_str(&fmt_http_date(time)).expect("HTTP dates are valid header values")
+}
+
+/// The date in a header value, if it holds one.
+pub(crate) fn from_header(value: &HeaderValue) -> Option<SystemTime> {
+    parse_http_date(value.to_str().ok()?)
+}
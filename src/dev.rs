@@ -0,0 +1,251 @@
+//! Development helpers, behind the `dev` feature.
+//!
+//! [`LiveReload`] reloads open browser tabs when files change: HTML responses get a small
+//! script that long-polls an endpoint the server answers itself, and the endpoint answers as
+//! soon as anything under the watched directories changes.
+//!
+//! ```no_run
+//! use async_tiny::dev::LiveReload;
+//! use async_tiny::Server;
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let mut server = Server::builder()
+//!     .live_reload(LiveReload::new("site").watch("templates"))
+//!     .http("127.0.0.1:8080")
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Tabs also reload when the server comes back after a restart, so this pairs with
+//! [`ServerBuilder::restart_on_change`], which drains the server when sources change so a
+//! supervisor like cargo-watch or systemd can start a fresh one.
+//!
+//! [`ServerBuilder::debug_pages`] replaces the bare `500` for a panicking or failing
+//! [`Server::serve`](crate::Server::serve) handler with a page showing the message, the
+//! backtrace and the request.
+//!
+//! [`ServerBuilder::restart_on_change`]: crate::ServerBuilder::restart_on_change
+//! [`ServerBuilder::debug_pages`]: crate::ServerBuilder::debug_pages
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use http::header::{CACHE_CONTROL, CONTENT_ENCODING, CONTENT_TYPE, ETAG, LAST_MODIFIED};
+use http::HeaderValue;
+use tokio::sync::watch;
+
+use crate::Response;
+
+pub(crate) mod debug_page;
+
+/// How long a poll is held open before it's answered with no change.
+const POLL_TIMEOUT: Duration = Duration::from_secs(25);
+
+/// How often watched directories are rescanned unless configured otherwise.
+pub(crate) const SCAN_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Live reload configuration, enabled via
+/// [`ServerBuilder::live_reload`](crate::ServerBuilder::live_reload).
+pub struct LiveReload {
+    dirs: Vec<PathBuf>,
+    endpoint: String,
+    interval: Duration,
+    version: watch::Sender<u64>,
+}
+
+impl LiveReload {
+    /// Watch `dir` (recursively) and serve the reload endpoint at `/__livereload`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        // Start from the clock so a restarted server never repeats a version a tab has seen.
+        let start = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        Self {
+            dirs: vec![dir.into()],
+            endpoint: "/__livereload".to_string(),
+            interval: SCAN_INTERVAL,
+            version: watch::channel(start).0,
+        }
+    }
+
+    /// Watch another directory too.
+    pub fn watch(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dirs.push(dir.into());
+        self
+    }
+
+    /// Serve the endpoint the script polls at `path` instead.
+    pub fn endpoint(mut self, path: &str) -> Self {
+        self.endpoint = path.to_string();
+        self
+    }
+
+    /// How often to look for changes; every 300ms by default.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Whether `url` is the reload endpoint; the server answers those itself.
+    pub(crate) fn is_endpoint(&self, url: &str) -> bool {
+        url.split('?').next() == Some(self.endpoint.as_str())
+    }
+
+    /// Answers a poll once the version differs from the `since` the script sent, or after
+    /// the poll timeout either way. The script reloads when the answer changes.
+    pub(crate) async fn poll(&self, url: &str) -> Response {
+        let since = url
+            .split_once('?')
+            .and_then(|(_, query)| query.split('&').find_map(|p| p.strip_prefix("since=")))
+            .and_then(|v| v.parse::<u64>().ok());
+        let mut rx = self.version.subscribe();
+        if since == Some(*rx.borrow_and_update()) {
+            let _ = tokio::time::timeout(POLL_TIMEOUT, rx.changed()).await;
+        }
+        let version = *self.version.borrow();
+        let mut response = Response::from_string(version.to_string());
+        response
+            .headers
+            .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        response
+    }
+
+    /// Adds the reload script to full HTML pages. Compressed ones, like precompressed files
+    /// from [`StaticFiles`](crate::static_files::StaticFiles), are left alone, since plain
+    /// text spliced into them would corrupt them.
+    pub(crate) fn inject(&self, mut response: Response) -> Response {
+        let is_html = response
+            .headers
+            .get(CONTENT_TYPE)
+            .is_some_and(|v| v.as_bytes().starts_with(b"text/html"));
+        let encoded = response.headers.contains_key(CONTENT_ENCODING);
+        if response.status != 200 || !is_html || encoded {
+            return response;
+        }
+        let script = format!(
+            concat!(
+                "<script>(function(){{var v=null;function poll(){{",
+                "fetch(\"{}\"+(v===null?\"\":\"?since=\"+v),{{cache:\"no-store\"}})",
+                ".then(function(r){{return r.text()}}).then(function(t){{",
+                "if(v!==null&&t!==v){{location.reload();return}}v=t;poll()}})",
+                ".catch(function(){{setTimeout(poll,1000)}})}}poll()}})();</script>"
+            ),
+            self.endpoint
+        );
+        let body = &response.body;
+        let at = find_last_ignore_case(body, b"</body>").unwrap_or(body.len());
+        let mut html = Vec::with_capacity(body.len() + script.len());
+        html.extend_from_slice(&body[..at]);
+        html.extend_from_slice(script.as_bytes());
+        html.extend_from_slice(&body[at..]);
+        response.body = html.into();
+        // The page no longer matches validators computed for the original body.
+        response.headers.remove(ETAG);
+        response.headers.remove(LAST_MODIFIED);
+        response
+    }
+
+    /// Bumps the version whenever something in the watched directories changes.
+    pub(crate) async fn run(self: std::sync::Arc<Self>) {
+        watch_dirs(self.dirs.clone(), self.interval, || {
+            self.version.send_modify(|v| *v += 1)
+        })
+        .await
+    }
+}
+
+/// Calls `on_change` each time a file under `dirs` is added, removed or modified, checking
+/// every `interval`. Never returns; abort the task to stop watching.
+pub(crate) async fn watch_dirs(
+    dirs: Vec<PathBuf>,
+    interval: Duration,
+    mut on_change: impl FnMut(),
+) {
+    let dirs = std::sync::Arc::new(dirs);
+    let scan = || {
+        let dirs = dirs.clone();
+        async move {
+            tokio::task::spawn_blocking(move || fingerprint(&dirs))
+                .await
+                .unwrap_or(0)
+        }
+    };
+    let mut last = scan().await;
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let current = scan().await;
+        if current != last {
+            last = current;
+            on_change();
+        }
+    }
+}
+
+/// A hash over the path, size and modification time of every file under `dirs`.
+fn fingerprint(dirs: &[PathBuf]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for dir in dirs {
+        visit(dir, &mut hasher);
+    }
+    hasher.finish()
+}
+
+fn visit(dir: &Path, hasher: &mut DefaultHasher) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    // `read_dir` order isn't stable, so sort before hashing.
+    let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+    entries.sort_by_key(|e| e.path());
+    for entry in entries {
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        let path = entry.path();
+        if meta.is_dir() {
+            visit(&path, hasher);
+        } else {
+            path.hash(hasher);
+            meta.len().hash(hasher);
+            meta.modified().ok().hash(hasher);
+        }
+    }
+}
+
+fn find_last_ignore_case(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .rposition(|w| w.eq_ignore_ascii_case(needle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Header;
+
+    fn page() -> Response {
+        Response::from_string("<html><body>hi</body></html>")
+            .with_header(Header::new("Content-Type", "text/html; charset=utf-8").unwrap())
+    }
+
+    #[test]
+    fn injects_into_plain_html() {
+        let body = LiveReload::new("site").inject(page()).body_bytes().clone();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.starts_with("<html><body>hi<script>"), "{}", body);
+        assert!(body.ends_with("</script></body></html>"), "{}", body);
+    }
+
+    #[test]
+    fn leaves_compressed_html_alone() {
+        let gzipped = page()
+            .with_body(&b"\x1f\x8b\x08\x00"[..])
+            .with_header(Header::new("Content-Encoding", "gzip").unwrap());
+        let response = LiveReload::new("site").inject(gzipped);
+        assert_eq!(response.body_bytes().as_ref(), b"\x1f\x8b\x08\x00");
+    }
+}
@@ -0,0 +1,173 @@
+//! The HTML pages [`ServerBuilder::debug_pages`](crate::ServerBuilder::debug_pages) shows for
+//! panics and handler errors.
+
+use std::backtrace::Backtrace;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use http::{HeaderMap, Method, StatusCode};
+use tokio::task;
+
+use crate::{MapError, Request, Response};
+
+/// Panics recorded past this count are dropped, in case they come from tasks nobody awaits.
+const MAX_PANICS: usize = 64;
+
+/// What the page shows about the request that failed.
+pub(crate) struct RequestInfo {
+    method: Method,
+    url: String,
+    headers: HeaderMap,
+    body_len: usize,
+}
+
+impl RequestInfo {
+    pub(crate) fn of(request: &Request) -> Arc<Self> {
+        Arc::new(Self {
+            method: request.method.clone(),
+            url: request.original_url().to_string(),
+            headers: request.headers.clone(),
+            body_len: request.body.len(),
+        })
+    }
+}
+
+/// Renders handler errors as a debug page, with the status `fallback` would have used.
+pub(crate) struct DebugErrors {
+    pub(crate) request: Arc<RequestInfo>,
+    pub(crate) fallback: Arc<dyn MapError>,
+}
+
+impl MapError for DebugErrors {
+    fn map_error(&self, error: &(dyn Error + 'static)) -> Response {
+        let status = self.fallback.map_error(error).status_code();
+        let mut chain = String::new();
+        let mut source = error.source();
+        while let Some(e) = source {
+            let _ = write!(chain, "<li>{}</li>", escape(&e.to_string()));
+            source = e.source();
+        }
+        let detail = if chain.is_empty() {
+            String::new()
+        } else {
+            format!("<h2>Caused by</h2><ol>{}</ol>", chain)
+        };
+        page(
+            status,
+            "Handler error",
+            &error.to_string(),
+            &detail,
+            &self.request,
+        )
+    }
+}
+
+/// A panic seen by the hook: where it happened and the stack at that point.
+struct Panic {
+    message: String,
+    backtrace: String,
+}
+
+fn panics() -> &'static Mutex<HashMap<task::Id, Panic>> {
+    static PANICS: OnceLock<Mutex<HashMap<task::Id, Panic>>> = OnceLock::new();
+    PANICS.get_or_init(Default::default)
+}
+
+/// Records the message and backtrace of panics in tasks, on top of the existing hook.
+pub(crate) fn install_panic_hook() {
+    static INSTALLED: OnceLock<()> = OnceLock::new();
+    INSTALLED.get_or_init(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if let Some(id) = task::try_id() {
+                let message = match info.location() {
+                    Some(at) => format!("{} (at {})", payload_message(info.payload()), at),
+                    None => payload_message(info.payload()),
+                };
+                let backtrace = Backtrace::force_capture().to_string();
+                let mut panics = panics().lock().unwrap_or_else(|e| e.into_inner());
+                if panics.len() >= MAX_PANICS {
+                    panics.clear();
+                }
+                panics.insert(id, Panic { message, backtrace });
+            }
+            previous(info);
+        }));
+    });
+}
+
+/// The page for the handler task that ended with `error`.
+pub(crate) fn panic_page(error: task::JoinError, request: &RequestInfo) -> Response {
+    let recorded = panics()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&error.id());
+    let (message, backtrace) = match recorded {
+        Some(panic) => (panic.message, panic.backtrace),
+        None if error.is_panic() => (payload_message(&*error.into_panic()), String::new()),
+        None => ("the handler task was cancelled".to_string(), String::new()),
+    };
+    let detail = format!("<h2>Backtrace</h2><pre>{}</pre>", escape(&backtrace));
+    page(500, "Handler panicked", &message, &detail, request)
+}
+
+fn payload_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+fn page(status: u16, title: &str, message: &str, detail: &str, request: &RequestInfo) -> Response {
+    let reason = StatusCode::from_u16(status)
+        .ok()
+        .and_then(|s| s.canonical_reason())
+        .unwrap_or("Error");
+    let mut headers = String::new();
+    for (name, value) in &request.headers {
+        let _ = write!(
+            headers,
+            "<tr><th>{}</th><td>{}</td></tr>",
+            name,
+            escape(&String::from_utf8_lossy(value.as_bytes()))
+        );
+    }
+    let html = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{status} {reason}: {title}</title>\n<style>\n\
+         body {{ margin: 0; padding: 2rem; font-family: system-ui, sans-serif; background: #fff8f8; color: #24292f; }}\n\
+         h1 {{ color: #cf222e; margin-top: 0; }}\n\
+         pre {{ background: #f6f8fa; padding: 1rem; overflow-x: auto; font-size: 0.85rem; }}\n\
+         th {{ text-align: left; padding-right: 1rem; vertical-align: top; }}\n\
+         </style>\n</head>\n<body>\n\
+         <h1>{status} {reason}: {title}</h1>\n<pre>{message}</pre>\n{detail}\n\
+         <h2>Request</h2>\n<p><code>{method} {url}</code>, {body_len} byte body</p>\n\
+         <table>{headers}</table>\n\
+         <p><small>Shown because debug pages are on. Turn them off in production.</small></p>\n\
+         </body>\n</html>\n",
+        message = escape(message),
+        method = request.method,
+        url = escape(&request.url),
+        body_len = request.body_len,
+    );
+    Response::from_status_and_string(status, html).with_content_type("text/html; charset=utf-8")
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,170 @@
+//! Digests of request bodies, computed while they're buffered, enabled via
+//! [`ServerBuilder::body_digest`].
+//!
+//! Each chunk is hashed as it arrives, so by the time the request reaches the application
+//! its digest is ready in [`Request::body_digest`] without a second pass over the body:
+//! enough to content-address an upload in a blob store.
+//!
+//! ```no_run
+//! use async_tiny::digest::Sha256;
+//! use async_tiny::{Response, Server};
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let mut server = Server::builder().body_digest(Sha256::new).http("0.0.0.0:8080").await?;
+//! while let Some(request) = server.next().await {
+//!     let hex: String = request
+//!         .body_digest()
+//!         .unwrap_or_default()
+//!         .iter()
+//!         .map(|b| format!("{:02x}", b))
+//!         .collect();
+//!     // ... store request.body() under `hex` ...
+//!     let _ = request.respond(Response::from_status_and_string(201, hex));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Any hash can be plugged in by implementing [`BodyDigest`]; SHA-256 comes with the `sha2`
+//! feature.
+//!
+//! [`ServerBuilder::body_digest`]: crate::ServerBuilder::body_digest
+//! [`Request::body_digest`]: crate::Request::body_digest
+
+/// A hash fed a request body chunk by chunk.
+pub trait BodyDigest: Send {
+    fn update(&mut self, data: &[u8]);
+
+    /// The digest of everything passed to [`update`](Self::update).
+    fn finish(self: Box<Self>) -> Vec<u8>;
+}
+
+/// SHA-256, computed by the [`sha2`](https://docs.rs/sha2) crate. Requires the `sha2`
+/// feature, which is on by default.
+///
+/// ```
+/// use async_tiny::digest::Sha256;
+///
+/// let hex: String = Sha256::digest(b"abc").iter().map(|b| format!("{:02x}", b)).collect();
+/// assert_eq!(hex, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+/// ```
+#[cfg(feature = "sha2")]
+#[derive(Clone, Default)]
+pub struct Sha256(sha2::Sha256);
+
+#[cfg(feature = "sha2")]
+impl Sha256 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The digest of `data` in one go.
+    pub fn digest(data: &[u8]) -> [u8; 32] {
+        <sha2::Sha256 as sha2::Digest>::digest(data).into()
+    }
+}
+
+#[cfg(feature = "sha2")]
+impl BodyDigest for Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(&mut self.0, data);
+    }
+
+    fn finish(self: Box<Self>) -> Vec<u8> {
+        sha2::Digest::finalize(self.0).to_vec()
+    }
+}
+
+#[cfg(all(test, feature = "sha2"))]
+mod tests {
+    use super::*;
+
+    fn hex(digest: [u8; 32]) -> String {
+        digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn nist_vectors() {
+        let cases: [(&[u8], &str); 4] = [
+            (
+                b"",
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            ),
+            (
+                b"abc",
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            ),
+            (
+                b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+                "cf5b16a778af8380036ce59e7b0492370b249b11e8f07a51afac45037afee9d1",
+            ),
+        ];
+        for (message, expected) in cases {
+            assert_eq!(hex(Sha256::digest(message)), expected);
+        }
+    }
+
+    #[test]
+    fn million_a() {
+        assert_eq!(
+            hex(Sha256::digest(&vec![b'a'; 1_000_000])),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+
+    #[test]
+    fn padding_boundaries() {
+        // 55 bytes leave just room for the length in the last block; 56 need another block.
+        let cases = [
+            (
+                55,
+                "9f4390f8d30c2dd92ec9f095b65e2b9ae9b0a925a5258e241c9f1e910f734318",
+            ),
+            (
+                56,
+                "b35439a4ac6f0948b6d6f9e3c6af0f5f590ce20f1bde7090ef7970686ec6738a",
+            ),
+            (
+                63,
+                "7d3e74a05d7db15bce4ad9ec0658ea98e3f06eeecf16b4c6fff2da457ddc2f34",
+            ),
+            (
+                64,
+                "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb",
+            ),
+            (
+                65,
+                "635361c48bb9eab14198e76ea8ab7f1a41685d6ad62aa9146d301d4f17eb0ae0",
+            ),
+        ];
+        for (len, expected) in cases {
+            assert_eq!(
+                hex(Sha256::digest(&vec![b'a'; len])),
+                expected,
+                "{} bytes",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn split_updates_match_one_shot() {
+        let message: Vec<u8> = (0..200u32).map(|i| (i * 31 % 251) as u8).collect();
+        let whole = Sha256::digest(&message);
+        for split in 0..=message.len() {
+            let mut hasher = Box::new(Sha256::new());
+            hasher.update(&message[..split]);
+            hasher.update(&message[split..]);
+            assert_eq!(hasher.finish(), whole, "split at {}", split);
+        }
+        let mut hasher = Box::new(Sha256::new());
+        for byte in message.chunks(1) {
+            hasher.update(byte);
+        }
+        assert_eq!(hasher.finish(), whole);
+    }
+}
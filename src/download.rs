@@ -0,0 +1,161 @@
+//! Serving one file with everything a resumable download needs, in the right order.
+//!
+//! [`file()`] answers `304` when the client's copy is current, `206` for the byte ranges
+//! asked for (reading only those bytes), `416` for ranges past the end, and `200` with the
+//! whole file otherwise, which is also what a resumed download of a file that has since
+//! changed gets. `HEAD` gets the `200` headers without reading the file.
+//!
+//! ```no_run
+//! use async_tiny::{download, Header, Response, Server};
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let mut server = Server::http("127.0.0.1:8080", false).await?;
+//! while let Some(request) = server.next().await {
+//!     let response = match download::file(&request, "./releases/app-1.4.2.tar.gz").await {
+//!         Ok(response) => response.with_header(
+//!             Header::new("Content-Disposition", "attachment; filename=\"app-1.4.2.tar.gz\"")
+//!                 .unwrap(),
+//!         ),
+//!         Err(_) => Response::from_status_and_string(404, "Not Found"),
+//!     };
+//!     let _ = request.respond(response);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{self, SeekFrom};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use http::header::{
+    ACCEPT_RANGES, ALLOW, CONTENT_LENGTH, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use http::{HeaderMap, HeaderValue, Method};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::range::{self, Window};
+use crate::static_files::content_type;
+use crate::{date, etag, Header, Request, Response};
+
+/// The response to `req` for the file at `path`.
+///
+/// The `ETag` is strong and derived from the file's size and modification time, so a
+/// resumed download of a rewritten file starts over. Methods other than GET and HEAD get
+/// `405`. Fails only if the file can't be opened or read, e.g. `NotFound`.
+///
+/// ```
+/// use async_tiny::{download, Header, Method, Request};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// let path = std::env::temp_dir().join("async_tiny_download_doc.txt");
+/// std::fs::write(&path, "0123456789")?;
+/// let get = || Request::fake(&Method::GET, "/file", b"");
+///
+/// let full = download::file(&get(), &path).await?;
+/// assert_eq!(full.status_code(), 200);
+/// let etag = full.headers()["etag"].to_str().unwrap().to_string();
+///
+/// let resume = get()
+///     .with_header(Header::new("Range", "bytes=6-").unwrap())
+///     .with_header(Header::new("If-Range", &etag).unwrap());
+/// let rest = download::file(&resume, &path).await?;
+/// assert_eq!((rest.status_code(), rest.body().as_str()), (206, "6789"));
+///
+/// let revalidate = get().with_header(Header::new("If-None-Match", &etag).unwrap());
+/// assert_eq!(download::file(&revalidate, &path).await?.status_code(), 304);
+///
+/// let past_end = get().with_header(Header::new("Range", "bytes=20-").unwrap());
+/// assert_eq!(download::file(&past_end, &path).await?.status_code(), 416);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn file(req: &Request, path: impl AsRef<Path>) -> io::Result<Response> {
+    let path = path.as_ref();
+    let method = req.method();
+    if method != Method::GET && method != Method::HEAD {
+        return Ok(
+            Response::empty(405).with_header(Header(ALLOW, HeaderValue::from_static("GET, HEAD")))
+        );
+    }
+
+    let mut file = tokio::fs::File::open(path).await?;
+    let meta = file.metadata().await?;
+    if !meta.is_file() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "not a file"));
+    }
+    let len = meta.len();
+    let modified = meta.modified().unwrap_or(UNIX_EPOCH);
+
+    let mut validators = HeaderMap::new();
+    let tag = etag::from_metadata(len, modified);
+    validators.insert(ETAG, HeaderValue::from_str(&tag).expect("valid etag"));
+    if modified != UNIX_EPOCH {
+        validators.insert(LAST_MODIFIED, date::header_value(modified));
+    }
+
+    if not_modified(req.headers(), &tag, modified) {
+        let mut response = Response::empty(304);
+        response.headers = validators;
+        return Ok(response);
+    }
+
+    let mut response = Response::empty(200).with_content_type(content_type(path));
+    response.headers.extend(validators);
+    response
+        .headers
+        .insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+
+    // Range only applies to GET (RFC 9110 §14.2).
+    let window = match *method {
+        Method::GET => range::window(req.headers(), &response.headers, len),
+        _ => Window::Full,
+    };
+    match window {
+        Window::Full if method == Method::HEAD => {
+            response
+                .headers
+                .insert(CONTENT_LENGTH, HeaderValue::from(len));
+            Ok(response)
+        }
+        Window::Full => {
+            let mut data = Vec::with_capacity(len as usize);
+            file.read_to_end(&mut data).await?;
+            response.body = Bytes::from(data);
+            Ok(response)
+        }
+        Window::Unsatisfiable => Ok(range::unsatisfiable(len)),
+        Window::Partial(ranges) => {
+            let mut pieces = Vec::with_capacity(ranges.len());
+            for range in ranges {
+                file.seek(SeekFrom::Start(range.start)).await?;
+                let mut piece = vec![0; (range.end - range.start) as usize];
+                file.read_exact(&mut piece).await?;
+                pieces.push((range, Bytes::from(piece)));
+            }
+            Ok(range::partial(response, pieces, len))
+        }
+    }
+}
+
+/// `If-None-Match`, or `If-Modified-Since` without it, says the client's copy is current.
+fn not_modified(headers: &HeaderMap, tag: &str, modified: SystemTime) -> bool {
+    if let Some(value) = headers.get(IF_NONE_MATCH) {
+        return value
+            .to_str()
+            .is_ok_and(|v| etag::list_matches(v, |candidate| etag::weak_eq(candidate, tag)));
+    }
+    match headers.get(IF_MODIFIED_SINCE).and_then(date::from_header) {
+        // HTTP dates have one-second resolution.
+        Some(since) => whole_seconds(modified) <= whole_seconds(since),
+        None => false,
+    }
+}
+
+fn whole_seconds(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
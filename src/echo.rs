@@ -0,0 +1,122 @@
+//! A handler that answers with the request itself, as JSON, like httpbin's `/anything`.
+//!
+//! Handy while developing a client: point it at the server and see exactly what arrived.
+//! Mount it somewhere out of the way, and not in production, since it reflects headers
+//! such as cookies back.
+//!
+//! ```no_run
+//! use async_tiny::{echo, Mount, Server};
+//!
+//! # async fn app(request: async_tiny::Request) -> async_tiny::Response { unimplemented!() }
+//! # async fn run() -> std::io::Result<()> {
+//! let app = Mount::new("/anything", echo::anything).fallback(app);
+//! Server::http("127.0.0.1:8080", false).await?.serve(app.into_handler()).await;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Write as _;
+
+use crate::static_files::percent_decode;
+use crate::{json, Request, Response};
+
+/// How much of the body [`anything`] shows.
+const BODY_PREVIEW: usize = 4096;
+
+/// The request as a JSON object: method, version, URL, decoded query arguments, headers,
+/// client address and a preview of the body.
+///
+/// Repeated query arguments and headers become arrays. The body preview is the first 4 KiB,
+/// decoded as UTF-8 with invalid bytes replaced.
+///
+/// ```
+/// use async_tiny::{echo, Header, Method, Request};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let request = Request::fake(&Method::POST, "/anything?tag=a&tag=b&q=hello+world", b"{}")
+///     .with_header(Header::new("Content-Type", "application/json").unwrap());
+/// let response = echo::anything(request).await;
+/// assert_eq!(response.headers()["content-type"], "application/json");
+///
+/// let body = response.body();
+/// assert!(body.contains(r#""method":"POST""#));
+/// assert!(body.contains(r#""args":{"tag":["a","b"],"q":"hello world"}"#));
+/// assert!(body.contains(r#""headers":{"content-type":"application/json"}"#));
+/// assert!(body.contains(r#""body":{"length":2,"preview":"{}","truncated":false}"#));
+/// # }
+/// ```
+pub async fn anything(request: Request) -> Response {
+    let url = request.original_url();
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+
+    let args = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode_component(name), decode_component(value))
+        });
+    let headers = request.headers().iter().map(|(name, value)| {
+        let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+        (name.as_str().to_string(), value)
+    });
+    let origin = request.remote_addr().map_or("null".to_string(), |addr| {
+        json::string(&addr.ip().to_string())
+    });
+
+    let body = request.body();
+    let preview = String::from_utf8_lossy(&body[..body.len().min(BODY_PREVIEW)]);
+
+    let json = format!(
+        "{{\"method\":{},\"version\":{},\"url\":{},\"path\":{},\"args\":{},\"headers\":{},\
+         \"origin\":{},\"connection_id\":{},\"sequence\":{},\
+         \"body\":{{\"length\":{},\"preview\":{},\"truncated\":{}}}}}",
+        json::string(request.method().as_str()),
+        json::string(&format!("{:?}", request.version())),
+        json::string(url),
+        json::string(path),
+        object(args),
+        object(headers),
+        origin,
+        request.connection_id(),
+        request.sequence(),
+        body.len(),
+        json::string(&preview),
+        body.len() > BODY_PREVIEW,
+    );
+    Response::from_string(json).with_content_type("application/json")
+}
+
+/// A query component with `+` as space and percent-escapes decoded, or as sent if they
+/// don't decode to UTF-8.
+fn decode_component(s: &str) -> String {
+    let s = s.replace('+', " ");
+    percent_decode(&s).unwrap_or(s)
+}
+
+/// A JSON object of `pairs` in first-seen order, with repeated names as arrays.
+fn object(pairs: impl Iterator<Item = (String, String)>) -> String {
+    let mut grouped: Vec<(String, Vec<String>)> = Vec::new();
+    for (name, value) in pairs {
+        match grouped.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, values)) => values.push(value),
+            None => grouped.push((name, vec![value])),
+        }
+    }
+    let mut out = String::from("{");
+    for (i, (name, values)) in grouped.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let _ = write!(out, "{}:", json::string(name));
+        if let [value] = values.as_slice() {
+            out.push_str(&json::string(value));
+        } else {
+            let values: Vec<String> = values.iter().map(|v| json::string(v)).collect();
+            let _ = write!(out, "[{}]", values.join(","));
+        }
+    }
+    out.push('}');
+    out
+}
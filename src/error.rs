@@ -0,0 +1,56 @@
+use std::fmt;
+
+use crate::{HeaderParseError, RespondError};
+
+/// Crate-wide error type.
+///
+/// The narrower error enums convert into it, so `?` works across the crate's APIs and with
+/// `Box<dyn std::error::Error>` or anyhow.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// A header could not be parsed.
+    HeaderParse(HeaderParseError),
+    /// A response could not be delivered.
+    Respond(RespondError),
+    /// A [`Store`](crate::store::Store) operation failed.
+    Store(std::io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::HeaderParse(e) => write!(f, "header parse error: {}", e),
+            Error::Respond(e) => write!(f, "respond failed: {}", e),
+            Error::Store(e) => write!(f, "store error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::HeaderParse(e) => Some(e),
+            Error::Respond(e) => Some(e),
+            Error::Store(e) => Some(e),
+        }
+    }
+}
+
+impl From<HeaderParseError> for Error {
+    fn from(e: HeaderParseError) -> Self {
+        Error::HeaderParse(e)
+    }
+}
+
+impl From<RespondError> for Error {
+    fn from(e: RespondError) -> Self {
+        Error::Respond(e)
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        std::io::Error::other(e)
+    }
+}
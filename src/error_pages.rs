@@ -0,0 +1,112 @@
+//! Error pages for the responses the server generates itself.
+//!
+//! These back the `500` sent when a request is dropped without a response, the `503` sent
+//! when the request queue is closed, and the `400`/`405`/`413` rejections. Configure them
+//! with [`ServerBuilder::error_pages`](crate::ServerBuilder::error_pages), or call
+//! [`ErrorPages::response`] to reuse the same look for your own errors.
+//!
+//! Requests Hyper can't parse at all (a garbled request line, an unknown HTTP version, an
+//! oversized head) are answered by Hyper itself with an empty `400`, `414` or `431` before
+//! the server sees them, so those don't use these pages. They are still counted in
+//! [`Server::error_counts`](crate::Server::error_counts).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use http::StatusCode;
+
+use crate::Response;
+
+type Renderer = Arc<dyn Fn(u16, &str) -> String + Send + Sync>;
+
+/// A set of error pages: per-status overrides, an optional renderer, and a built-in theme.
+#[derive(Clone, Default)]
+pub struct ErrorPages {
+    pages: HashMap<u16, String>,
+    renderer: Option<Renderer>,
+    plain: bool,
+}
+
+impl ErrorPages {
+    /// The built-in HTML theme for every status.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Plain-text bodies such as `Service Unavailable`, for API-only servers.
+    pub fn plain() -> Self {
+        Self {
+            plain: true,
+            ..Self::default()
+        }
+    }
+
+    /// Serve `html` for `status`, ahead of any renderer.
+    pub fn page(mut self, status: u16, html: impl Into<String>) -> Self {
+        self.pages.insert(status, html.into());
+        self
+    }
+
+    /// Render pages without an override with `render(status, reason)`, e.g. through the
+    /// app's own layout.
+    pub fn render_with<F>(mut self, render: F) -> Self
+    where
+        F: Fn(u16, &str) -> String + Send + Sync + 'static,
+    {
+        self.renderer = Some(Arc::new(render));
+        self
+    }
+
+    /// The error response for `status`.
+    pub fn response(&self, status: u16) -> Response {
+        let reason = StatusCode::from_u16(status)
+            .ok()
+            .and_then(|s| s.canonical_reason())
+            .unwrap_or("Error");
+
+        if self.plain {
+            return Response::from_status_and_string(status, reason)
+                .with_content_type("text/plain; charset=utf-8");
+        }
+
+        let html = match (self.pages.get(&status), &self.renderer) {
+            (Some(page), _) => page.clone(),
+            (None, Some(render)) => render(status, reason),
+            (None, None) => default_page(status, reason),
+        };
+        Response::from_status_and_string(status, html).with_content_type("text/html; charset=utf-8")
+    }
+}
+
+impl fmt::Debug for ErrorPages {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ErrorPages")
+            .field("pages", &self.pages.keys().collect::<Vec<_>>())
+            .field("renderer", &self.renderer.is_some())
+            .field("plain", &self.plain)
+            .finish()
+    }
+}
+
+fn default_page(status: u16, reason: &str) -> String {
+    let detail = match status {
+        404 => "The page you were looking for doesn't exist.",
+        500 => "Something went wrong on our end.",
+        503 => "The server is temporarily unable to handle this request. Please try again shortly.",
+        _ => "The request could not be completed.",
+    };
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{status} {reason}</title>\n<style>\n\
+         body {{ margin: 0; min-height: 100vh; display: flex; align-items: center; \
+         justify-content: center; font-family: system-ui, sans-serif; \
+         background: #f6f7f9; color: #24292f; }}\n\
+         main {{ text-align: center; padding: 2rem; }}\n\
+         h1 {{ font-size: 4rem; margin: 0; color: #57606a; }}\n\
+         p {{ margin: 0.5rem 0; }}\n\
+         </style>\n</head>\n<body>\n<main>\n<h1>{status}</h1>\n\
+         <p><strong>{reason}</strong></p>\n<p>{detail}</p>\n</main>\n</body>\n</html>\n"
+    )
+}
@@ -0,0 +1,96 @@
+//! Entity tags (RFC 9110 §8.8.3), as used by `ETag`, `If-Match`, `If-None-Match` and
+//! `If-Range`.
+//!
+//! These are the helpers [`StaticFiles`](crate::static_files::StaticFiles) uses, so tags made
+//! by handlers for dynamic content look and compare the same way.
+//!
+//! ```
+//! use std::time::{Duration, UNIX_EPOCH};
+//! use async_tiny::etag;
+//!
+//! let tag = etag::from_bytes(b"<h1>hello</h1>");
+//! assert!(tag.starts_with('"') && tag.ends_with('"'));
+//! assert_eq!(tag, etag::from_bytes(b"<h1>hello</h1>"));
+//!
+//! let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+//! let weak = etag::weak_from_metadata(1024, modified);
+//! assert!(weak.starts_with("W/\""));
+//!
+//! // Weak comparison ignores the `W/`; strong comparison never matches a weak tag.
+//! assert!(etag::weak_eq(&weak, &etag::from_metadata(1024, modified)));
+//! assert!(!etag::strong_eq(&weak, &weak));
+//! ```
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "sha2")]
+use crate::digest::Sha256;
+
+/// A strong tag from a hash of `data`: identical bytes, identical tag.
+///
+/// The hash is the first 64 bits of SHA-256, so tags stay the same across processes,
+/// instances and Rust versions, and caches holding them stay valid after a deploy. Requires
+/// the `sha2` feature, which is on by default.
+#[cfg(feature = "sha2")]
+pub fn from_bytes(data: &[u8]) -> String {
+    let hash: String = Sha256::digest(data)[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("\"{}\"", hash)
+}
+
+/// A strong tag from a file's size and modification time, without reading it.
+///
+/// Strong so that `If-Range` works, which assumes a file isn't rewritten with the same size
+/// within the timestamp's resolution. Use [`weak_from_metadata`] where that's not safe.
+pub fn from_metadata(len: u64, modified: SystemTime) -> String {
+    let nanos = modified
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    format!("\"{:x}-{:x}\"", len, nanos)
+}
+
+/// [`from_metadata`] marked weak: good for `If-None-Match` revalidation, never for ranges.
+pub fn weak_from_metadata(len: u64, modified: SystemTime) -> String {
+    format!("W/{}", from_metadata(len, modified))
+}
+
+/// Whether `tag` is weak (`W/"..."`).
+pub fn is_weak(tag: &str) -> bool {
+    tag.starts_with("W/")
+}
+
+/// Strong comparison: both tags strong and identical.
+pub fn strong_eq(a: &str, b: &str) -> bool {
+    !is_weak(a) && !is_weak(b) && a == b
+}
+
+/// Weak comparison: identical once any `W/` is dropped.
+pub fn weak_eq(a: &str, b: &str) -> bool {
+    a.trim_start_matches("W/") == b.trim_start_matches("W/")
+}
+
+/// Whether `*` or any tag in a comma-separated header value satisfies `eq`.
+pub(crate) fn list_matches(value: &str, eq: impl Fn(&str) -> bool) -> bool {
+    value
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || eq(candidate))
+}
+
+#[cfg(all(test, feature = "sha2"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_bytes_is_stable() {
+        // The first 64 bits of SHA-256("<h1>hello</h1>").
+        assert_eq!(from_bytes(b"<h1>hello</h1>"), "\"4db7ef630005c462\"");
+        assert_ne!(
+            from_bytes(b"<h1>hello</h1>"),
+            from_bytes(b"<h1>hello</h1>\n")
+        );
+    }
+}
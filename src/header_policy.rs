@@ -0,0 +1,121 @@
+//! Headers stripped from or set on every response, enabled via
+//! [`ServerBuilder::response_headers`].
+//!
+//! The policy runs last, on the application's responses after [`ServerBuilder::on_response`]
+//! and on the server's own (error pages, CORS preflights and the like), so a handler can't
+//! override or forget what it sets. Headers given to [`HeaderPolicy::default_header`] are the
+//! exception: they're only added to responses that don't already have them.
+//!
+//! ```no_run
+//! use async_tiny::header_policy::HeaderPolicy;
+//! use async_tiny::{HeaderName, Server};
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let policy = HeaderPolicy::new()
+//!     .strip("x-powered-by")
+//!     .set("server", "edge")
+//!     .set("x-content-type-options", "nosniff")
+//!     .default_header("cache-control", "no-store")
+//!     .request_id(HeaderName::from_static("x-request-id"));
+//! let mut server = Server::builder().response_headers(policy).http("0.0.0.0:8080").await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`ServerBuilder::response_headers`]: crate::ServerBuilder::response_headers
+//! [`ServerBuilder::on_response`]: crate::ServerBuilder::on_response
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use http::{HeaderMap, HeaderName, HeaderValue};
+
+/// Which response headers to remove and which to force.
+#[derive(Clone, Debug, Default)]
+pub struct HeaderPolicy {
+    strip: Vec<HeaderName>,
+    set: Vec<(HeaderName, HeaderValue)>,
+    defaults: Vec<(HeaderName, HeaderValue)>,
+    request_id: Option<HeaderName>,
+}
+
+impl HeaderPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remove `name` from every response. Panics if `name` isn't a valid header name.
+    pub fn strip(mut self, name: &str) -> Self {
+        self.strip
+            .push(HeaderName::from_bytes(name.as_bytes()).expect("valid header name"));
+        self
+    }
+
+    /// Set `name` to `value` on every response, replacing whatever the handler sent. Panics
+    /// if either isn't valid in a header.
+    pub fn set(mut self, name: &str, value: &str) -> Self {
+        let name = HeaderName::from_bytes(name.as_bytes()).expect("valid header name");
+        let value = HeaderValue::from_str(value).expect("valid header value");
+        self.set.push((name, value));
+        self
+    }
+
+    /// Set `name` to `value` on responses that don't have `name`, so handlers can still
+    /// override it, e.g. `Cache-Control: no-store` for an API whose cacheable routes say so.
+    /// Panics if either isn't valid in a header.
+    pub fn default_header(mut self, name: &str, value: &str) -> Self {
+        let name = HeaderName::from_bytes(name.as_bytes()).expect("valid header name");
+        let value = HeaderValue::from_str(value).expect("valid header value");
+        self.defaults.push((name, value));
+        self
+    }
+
+    /// Send each request's ID back in header `name`: the one the client (or a proxy) sent in
+    /// the same header, or a new one unique to this process's lifetime.
+    pub fn request_id(mut self, name: HeaderName) -> Self {
+        self.request_id = Some(name);
+        self
+    }
+
+    /// The request ID to echo for a request with `headers`, if IDs are on.
+    pub(crate) fn request_id_for(
+        &self,
+        headers: &HeaderMap,
+        connection_id: u64,
+        sequence: u64,
+    ) -> Option<HeaderValue> {
+        let name = self.request_id.as_ref()?;
+        if let Some(value) = headers.get(name) {
+            return Some(value.clone());
+        }
+        let id = format!("{:08x}-{:x}-{:x}", process_nonce(), connection_id, sequence);
+        Some(HeaderValue::from_str(&id).expect("hex request id"))
+    }
+
+    pub(crate) fn apply(&self, headers: &mut HeaderMap, request_id: Option<HeaderValue>) {
+        for name in &self.strip {
+            headers.remove(name);
+        }
+        for (name, value) in &self.defaults {
+            if !headers.contains_key(name) {
+                headers.insert(name, value.clone());
+            }
+        }
+        for (name, value) in &self.set {
+            headers.insert(name, value.clone());
+        }
+        if let (Some(name), Some(id)) = (&self.request_id, request_id) {
+            headers.insert(name, id);
+        }
+    }
+}
+
+/// Sets this process's request IDs apart from those of earlier runs.
+fn process_nonce() -> u32 {
+    static NONCE: std::sync::OnceLock<u32> = std::sync::OnceLock::new();
+    *NONCE.get_or_init(|| {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        (now.as_secs() as u32) ^ now.subsec_nanos() ^ std::process::id().rotate_left(16)
+    })
+}
@@ -0,0 +1,239 @@
+//! Decoy paths that no legitimate client requests, enabled via [`ServerBuilder::honeypot`].
+//!
+//! A request for a decoy is reported to [`Honeypot::on_hit`] with whatever is known about
+//! the client, answered with `404`, and can get the client's address banned: with
+//! [`Honeypot::ban_for`], its later requests are refused with `403` before anything else
+//! looks at them.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use async_tiny::honeypot::Honeypot;
+//! use async_tiny::Server;
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let honeypot = Honeypot::new()
+//!     .path("/wp-login.php")
+//!     .path("/phpmyadmin/*")
+//!     .ban_for(Duration::from_secs(60 * 60))
+//!     .on_hit(|hit| {
+//!         eprintln!("honeypot: {:?} {} {} {:?}", hit.peer, hit.method, hit.url, hit.user_agent());
+//!     });
+//! let mut server = Server::builder().honeypot(honeypot.clone()).http("0.0.0.0:8080").await?;
+//! // Later, e.g. from an admin endpoint:
+//! println!("banned: {:?}", honeypot.banned());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Clones share their bans, so keep one to inspect or lift them. Bans are per process and
+//! are lost on restart. Behind a reverse proxy every client has the proxy's address, so
+//! don't ban there.
+//!
+//! A decoy that's also a [`Tarpit`](crate::tarpit::Tarpit) path is reported, then tarpitted.
+//!
+//! [`ServerBuilder::honeypot`]: crate::ServerBuilder::honeypot
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use http::header::USER_AGENT;
+use http::{HeaderMap, Method};
+
+use crate::pattern::Pattern;
+
+/// Don't bother sweeping fewer bans than this.
+const MIN_SWEEP: usize = 1024;
+
+/// The most addresses banned at once. While this many are, new ones aren't banned.
+const MAX_BANS: usize = 100_000;
+
+/// How often a full table is checked for bans that have run out.
+const FULL_SWEEP_EVERY: Duration = Duration::from_secs(1);
+
+type HitHook = dyn Fn(&Hit<'_>) + Send + Sync;
+
+/// Decoy paths, and what to do about clients that request them.
+#[derive(Clone, Default)]
+pub struct Honeypot {
+    paths: Vec<Pattern>,
+    on_hit: Option<Arc<HitHook>>,
+    ban_for: Option<Duration>,
+    bans: Arc<Mutex<Bans>>,
+}
+
+/// Banned addresses and when their bans end. Expired bans are swept out each time the table
+/// doubles in size since the last sweep, so a scanner with many addresses costs amortized
+/// constant time per ban.
+#[derive(Default)]
+struct Bans {
+    map: HashMap<IpAddr, Instant>,
+    /// How many bans to let in before the next sweep.
+    sweep_at: usize,
+    swept: Option<Instant>,
+}
+
+impl Bans {
+    fn insert(&mut self, ip: IpAddr, until: Instant, now: Instant) {
+        let full = self.map.len() >= MAX_BANS;
+        if self.map.len() >= self.sweep_at
+            && (!full || self.swept.is_none_or(|at| now - at >= FULL_SWEEP_EVERY))
+        {
+            self.map.retain(|_, until| *until > now);
+            self.sweep_at = (self.map.len() * 2).clamp(MIN_SWEEP, MAX_BANS);
+            self.swept = Some(now);
+        }
+        if self.map.len() >= MAX_BANS && !self.map.contains_key(&ip) {
+            return;
+        }
+        self.map.insert(ip, until);
+    }
+}
+
+/// A request for a decoy path, as passed to [`Honeypot::on_hit`].
+pub struct Hit<'a> {
+    /// `None` for transports without addresses, like named pipes.
+    pub peer: Option<SocketAddr>,
+    pub method: &'a Method,
+    /// The path and query, e.g. `/wp-login.php?action=register`.
+    pub url: &'a str,
+    pub headers: &'a HeaderMap,
+    pub connection_id: u64,
+    pub time: SystemTime,
+}
+
+impl Hit<'_> {
+    pub fn user_agent(&self) -> Option<&str> {
+        self.headers.get(USER_AGENT).and_then(|v| v.to_str().ok())
+    }
+}
+
+impl Honeypot {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Treat paths matching `pattern`, such as `/.env` or `/wp-admin/*`, as decoys.
+    pub fn path(mut self, pattern: &str) -> Self {
+        self.paths.push(Pattern::parse(pattern));
+        self
+    }
+
+    /// Call `hook` for each decoy request, e.g. to log it or record it in a store.
+    ///
+    /// Runs on the connection task before the response is sent, so keep it cheap.
+    pub fn on_hit<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Hit<'_>) + Send + Sync + 'static,
+    {
+        self.on_hit = Some(Arc::new(hook));
+        self
+    }
+
+    /// Refuse every request from a client's IP address for `duration` after it requests a
+    /// decoy.
+    ///
+    /// At most 100,000 addresses are banned at once; past that, decoy requests from new
+    /// addresses are still reported and answered with `404`, but not banned.
+    pub fn ban_for(mut self, duration: Duration) -> Self {
+        self.ban_for = Some(duration);
+        self
+    }
+
+    /// The addresses banned right now.
+    pub fn banned(&self) -> Vec<IpAddr> {
+        let now = Instant::now();
+        let bans = self.bans.lock().unwrap();
+        bans.map
+            .iter()
+            .filter(|(_, until)| **until > now)
+            .map(|(ip, _)| *ip)
+            .collect()
+    }
+
+    /// Lift the ban on `ip`, if there is one.
+    pub fn unban(&self, ip: IpAddr) {
+        self.bans.lock().unwrap().map.remove(&ip);
+    }
+
+    pub(crate) fn is_banned(&self, peer: Option<SocketAddr>) -> bool {
+        let Some(peer) = peer else {
+            return false;
+        };
+        let bans = self.bans.lock().unwrap();
+        bans.map
+            .get(&peer.ip())
+            .is_some_and(|until| *until > Instant::now())
+    }
+
+    pub(crate) fn is_decoy(&self, url: &str) -> bool {
+        let path = url.split('?').next().unwrap_or(url);
+        self.paths.iter().any(|pattern| pattern.matches(path))
+    }
+
+    /// Reports `hit` and bans its client if bans are on.
+    pub(crate) fn record(&self, hit: &Hit<'_>) {
+        if let Some(hook) = &self.on_hit {
+            hook(hit);
+        }
+        if let (Some(duration), Some(peer)) = (self.ban_for, hit.peer) {
+            let now = Instant::now();
+            let mut bans = self.bans.lock().unwrap();
+            bans.insert(peer.ip(), now + duration, now);
+        }
+    }
+}
+
+impl fmt::Debug for Honeypot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Honeypot")
+            .field("paths", &self.paths)
+            .field("ban_for", &self.ban_for)
+            .field("banned", &self.banned().len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+
+    use super::*;
+
+    fn ip(i: usize) -> IpAddr {
+        IpAddr::V6(Ipv6Addr::new(
+            0x2001,
+            0xdb8,
+            0,
+            0,
+            0,
+            0,
+            (i >> 16) as u16,
+            i as u16,
+        ))
+    }
+
+    #[test]
+    fn ban_table_is_capped() {
+        let mut bans = Bans::default();
+        let now = Instant::now();
+        let later = now + Duration::from_secs(60);
+        for i in 0..MAX_BANS + 10 {
+            bans.insert(ip(i), later, now);
+        }
+        assert_eq!(bans.map.len(), MAX_BANS);
+        assert!(!bans.map.contains_key(&ip(MAX_BANS)));
+        // Renewing an existing ban still works while full.
+        bans.insert(ip(0), later + Duration::from_secs(1), now);
+        assert_eq!(bans.map[&ip(0)], later + Duration::from_secs(1));
+
+        // Once they run out, the next ban after the full-table interval sweeps them.
+        let after = later + Duration::from_secs(2);
+        bans.insert(ip(MAX_BANS), after + Duration::from_secs(60), after);
+        assert_eq!(bans.map.len(), 1);
+        assert_eq!(bans.sweep_at, MIN_SWEEP);
+    }
+}
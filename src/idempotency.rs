@@ -0,0 +1,302 @@
+//! Replaying stored responses for retried requests that carry an `Idempotency-Key` header.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use async_tiny::idempotency::Idempotency;
+//! use async_tiny::{Response, Server};
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let idempotency = Idempotency::new(Duration::from_secs(24 * 60 * 60));
+//! let mut server = Server::http("127.0.0.1:8080", false).await?;
+//!
+//! while let Some(request) = server.next().await {
+//!     if let Some(stored) = idempotency.lookup(&request).await? {
+//!         let _ = request.respond(stored);
+//!         continue;
+//!     }
+//!     let response = Response::from_status_and_string(201, "charged");
+//!     idempotency.respond(request, response).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A key counts as seen from the first [`Idempotency::lookup`], so a duplicate sent while the
+//! original is still being handled gets `409 Conflict` rather than running twice.
+//!
+//! Requires the `sha2` feature, which is on by default: keys are scoped to the caller by a
+//! hash of their credentials.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use http::header::{AUTHORIZATION, RETRY_AFTER};
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+
+use crate::digest::Sha256;
+use crate::store::{MemoryStore, Store};
+use crate::{Error, Header, Request, Response};
+
+type Scope = dyn Fn(&Request) -> String + Send + Sync;
+
+/// Stores responses by idempotency key and replays them for duplicate deliveries.
+///
+/// Keys are scoped to the caller (see [`scope`](Self::scope)), the request method and the
+/// path, so one client can't replay another's response by reusing its key. Server errors
+/// (`5xx`) are not stored, so clients can retry them. Replayed responses carry
+/// `Idempotent-Replayed: true`.
+pub struct Idempotency<S = MemoryStore> {
+    store: S,
+    ttl: Duration,
+    pending: Duration,
+    header: HeaderName,
+    scope: Arc<Scope>,
+}
+
+impl Idempotency<MemoryStore> {
+    /// Keep responses in memory for `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        Self::with_store(MemoryStore::new(), ttl)
+    }
+}
+
+impl<S: Store> Idempotency<S> {
+    /// Keep responses in `store` for `ttl`, e.g. to replay them from any instance.
+    pub fn with_store(store: S, ttl: Duration) -> Self {
+        Self {
+            store,
+            ttl,
+            pending: Duration::from_secs(60),
+            header: HeaderName::from_static("idempotency-key"),
+            scope: Arc::new(default_scope),
+        }
+    }
+
+    /// Read the key from `name` instead of `Idempotency-Key`.
+    pub fn header(mut self, name: HeaderName) -> Self {
+        self.header = name;
+        self
+    }
+
+    /// Tell callers apart with `scope` instead of by their `Authorization` header (hashed),
+    /// or their IP address when they send none.
+    ///
+    /// Requests with the same key from different scopes are unrelated.
+    pub fn scope<F>(mut self, scope: F) -> Self
+    where
+        F: Fn(&Request) -> String + Send + Sync + 'static,
+    {
+        self.scope = Arc::new(scope);
+        self
+    }
+
+    /// How long a key stays claimed by a request that hasn't been answered through
+    /// [`respond`](Self::respond), 60 seconds by default. Duplicates get `409` until then,
+    /// so make it longer than the slowest handler.
+    pub fn pending_timeout(mut self, timeout: Duration) -> Self {
+        self.pending = timeout;
+        self
+    }
+
+    /// The stored response for a duplicate of `req`, or `409 Conflict` with `Retry-After`
+    /// if the original is still being handled. `None` means `req` is the first with its key
+    /// (or has none), and should be handled and answered with [`respond`](Self::respond).
+    pub async fn lookup(&self, req: &Request) -> Result<Option<Response>, Error> {
+        let Some(key) = self.key(req) else {
+            return Ok(None);
+        };
+        let stored = self.store.get(&key).await.map_err(Error::Store)?;
+        if let Some(stored) = stored.and_then(decode) {
+            return Ok(Some(stored.with_header(Header(
+                HeaderName::from_static("idempotent-replayed"),
+                HeaderValue::from_static("true"),
+            ))));
+        }
+        // The increment is the claim: whoever takes the counter to 1 handles the request.
+        let claims = self
+            .store
+            .increment(&pending_key(&key), Some(self.pending))
+            .await
+            .map_err(Error::Store)?;
+        if claims == 1 {
+            return Ok(None);
+        }
+        Ok(Some(
+            Response::from_status_and_string(409, "a request with this key is in progress")
+                .with_header(Header(RETRY_AFTER, HeaderValue::from_static("1"))),
+        ))
+    }
+
+    /// Store `response` under the request's key (if any) and send it.
+    ///
+    /// The response is sent even if storing it fails; the error is returned afterwards. A
+    /// server error releases the key's claim, so the client's retry is handled afresh.
+    pub async fn respond(&self, req: Request, response: Response) -> Result<(), Error> {
+        let stored = match self.key(&req) {
+            Some(key) if response.status.is_server_error() => {
+                self.store.delete(&pending_key(&key)).await
+            }
+            Some(key) => {
+                self.store
+                    .set(&key, encode(&response), Some(self.ttl))
+                    .await
+            }
+            None => Ok(()),
+        };
+        req.respond(response)?;
+        stored.map_err(Error::Store)
+    }
+
+    fn key(&self, req: &Request) -> Option<String> {
+        let key = req.headers().get(&self.header)?.to_str().ok()?;
+        let path = req.url().split('?').next().unwrap_or("");
+        let scope = (self.scope)(req);
+        Some(format!(
+            "idempotency:{} {} {} {}",
+            scope,
+            req.method(),
+            path,
+            key
+        ))
+    }
+}
+
+/// Where the claim on `key` is counted.
+fn pending_key(key: &str) -> String {
+    format!("{}:pending", key)
+}
+
+/// The caller's credentials, hashed so they aren't written to the store, or failing that
+/// their IP address.
+fn default_scope(req: &Request) -> String {
+    if let Some(auth) = req.headers().get(AUTHORIZATION) {
+        let hash = Sha256::digest(auth.as_bytes());
+        let hex: String = hash[..16].iter().map(|b| format!("{:02x}", b)).collect();
+        return format!("auth:{}", hex);
+    }
+    match req.remote_addr() {
+        Some(peer) => format!("ip:{}", peer.ip()),
+        None => "local".to_string(),
+    }
+}
+
+/// Status, header count, length-prefixed names and values, then the body. Custom reason
+/// phrases aren't kept.
+fn encode(response: &Response) -> Bytes {
+    let mut out = BytesMut::with_capacity(response.body.len() + 256);
+    out.put_u16(response.status.as_u16());
+    out.put_u32(response.headers.len() as u32);
+    for (name, value) in &response.headers {
+        out.put_u32(name.as_str().len() as u32);
+        out.put_slice(name.as_str().as_bytes());
+        out.put_u32(value.len() as u32);
+        out.put_slice(value.as_bytes());
+    }
+    out.put_slice(&response.body);
+    out.freeze()
+}
+
+/// The response [`encode`] wrote, or `None` if `data` is something else.
+fn decode(mut data: Bytes) -> Option<Response> {
+    fn take(data: &mut Bytes, n: usize) -> Option<Bytes> {
+        (data.len() >= n).then(|| data.split_to(n))
+    }
+    fn take_u32(data: &mut Bytes) -> Option<usize> {
+        let b = take(data, 4)?;
+        Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    }
+
+    let status = take(&mut data, 2)?;
+    let status = StatusCode::from_u16(u16::from_be_bytes([status[0], status[1]])).ok()?;
+    let count = take_u32(&mut data)?;
+    let mut headers = HeaderMap::with_capacity(count.min(64));
+    for _ in 0..count {
+        let len = take_u32(&mut data)?;
+        let name = HeaderName::from_bytes(&take(&mut data, len)?).ok()?;
+        let len = take_u32(&mut data)?;
+        let value = HeaderValue::from_maybe_shared(take(&mut data, len)?).ok()?;
+        headers.append(name, value);
+    }
+    Some(Response {
+        status,
+        headers,
+        body: data,
+        reason: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Method;
+
+    fn charge(key: &str, auth: &str) -> Request {
+        Request::fake(&Method::POST, "/charges", b"")
+            .with_header(Header::new("Idempotency-Key", key).unwrap())
+            .with_header(Header::new("Authorization", auth).unwrap())
+    }
+
+    #[tokio::test]
+    async fn duplicate_in_flight_gets_409() {
+        let idempotency = Idempotency::new(Duration::from_secs(60));
+        assert!(idempotency
+            .lookup(&charge("k1", "a"))
+            .await
+            .unwrap()
+            .is_none());
+        let duplicate = idempotency
+            .lookup(&charge("k1", "a"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(duplicate.status, 409);
+        assert_eq!(duplicate.headers()["retry-after"], "1");
+    }
+
+    #[tokio::test]
+    async fn answered_request_is_replayed() {
+        let idempotency = Idempotency::new(Duration::from_secs(60));
+        let first = charge("k1", "a");
+        assert!(idempotency.lookup(&first).await.unwrap().is_none());
+        let response = Response::from_status_and_string(201, "charged");
+        // A fake request has nowhere to send the response, but it's stored first.
+        let _ = idempotency.respond(first, response).await;
+        let replay = idempotency
+            .lookup(&charge("k1", "a"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(replay.status, 201);
+        assert_eq!(replay.body_bytes().as_ref(), b"charged");
+        assert_eq!(replay.headers()["idempotent-replayed"], "true");
+    }
+
+    #[tokio::test]
+    async fn keys_are_scoped_to_the_caller() {
+        let idempotency = Idempotency::new(Duration::from_secs(60));
+        let first = charge("k1", "a");
+        assert!(idempotency.lookup(&first).await.unwrap().is_none());
+        let _ = idempotency
+            .respond(first, Response::from_status_and_string(201, "charged"))
+            .await;
+        assert!(idempotency
+            .lookup(&charge("k1", "b"))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn server_error_releases_the_claim() {
+        let idempotency = Idempotency::new(Duration::from_secs(60));
+        let first = charge("k1", "a");
+        assert!(idempotency.lookup(&first).await.unwrap().is_none());
+        let _ = idempotency.respond(first, Response::empty(503)).await;
+        assert!(idempotency
+            .lookup(&charge("k1", "a"))
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,158 @@
+//! `Debug` for [`Request`] and [`Response`], and with the `serde` feature, `Serialize` for
+//! their metadata.
+//!
+//! Neither shows bodies, only their length, and the values of credential headers
+//! (`Authorization`, `Proxy-Authorization`, `Cookie` and `Set-Cookie`) are replaced with
+//! `[redacted]`, so requests can be logged as they are.
+
+use std::fmt;
+
+use http::{HeaderMap, HeaderValue};
+
+use crate::access_log::DEFAULT_REDACTED;
+use crate::{Request, Response};
+
+const REDACTED: &str = "[redacted]";
+
+fn is_redacted(name: &str) -> bool {
+    DEFAULT_REDACTED.contains(&name)
+}
+
+fn value_text(value: &HeaderValue) -> String {
+    String::from_utf8_lossy(value.as_bytes()).into_owned()
+}
+
+/// A header map in `Debug` and `Serialize` output, with credentials redacted.
+struct Headers<'a>(&'a HeaderMap);
+
+impl fmt::Debug for Headers<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for (name, value) in self.0 {
+            if is_redacted(name.as_str()) {
+                map.entry(&name.as_str(), &REDACTED);
+            } else {
+                map.entry(&name.as_str(), &value_text(value));
+            }
+        }
+        map.finish()
+    }
+}
+
+/// A body in `Debug` output: its length only.
+struct BodyLen(usize);
+
+impl fmt::Debug for BodyLen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{} bytes>", self.0)
+    }
+}
+
+/// Shows the request line, headers, body length and where the request came from.
+///
+/// ```
+/// use async_tiny::{Header, Method, Request};
+///
+/// let request = Request::fake(&Method::POST, "/login", b"user=ann&password=hunter2")
+///     .with_header(Header::new("Authorization", "Bearer secret").unwrap());
+/// let debug = format!("{:?}", request);
+/// assert!(debug.contains(r#"url: "/login""#));
+/// assert!(debug.contains(r#""authorization": "[redacted]""#));
+/// assert!(debug.contains("body: <25 bytes>"));
+/// assert!(!debug.contains("secret") && !debug.contains("hunter2"));
+/// ```
+impl fmt::Debug for Request {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Request")
+            .field("method", &self.method)
+            .field("version", &self.version)
+            .field("url", &self.original_url())
+            .field("headers", &Headers(&self.headers))
+            .field("body", &BodyLen(self.body.len()))
+            .field("peer", &self.peer)
+            .field("connection_id", &self.connection_id)
+            .field("sequence", &self.sequence)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Shows the status, headers and body length.
+///
+/// ```
+/// use async_tiny::{Header, Response};
+///
+/// let response = Response::from_string("welcome back")
+///     .with_header(Header::new("Set-Cookie", "session=abc123").unwrap());
+/// let debug = format!("{:?}", response);
+/// assert!(debug.contains("status: 200"));
+/// assert!(debug.contains(r#""set-cookie": "[redacted]""#));
+/// assert!(debug.contains("body: <12 bytes>"));
+/// ```
+impl fmt::Debug for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("Response");
+        s.field("status", &self.status.as_u16());
+        if let Some(reason) = &self.reason {
+            s.field("reason", &String::from_utf8_lossy(reason.as_bytes()));
+        }
+        s.field("headers", &Headers(&self.headers))
+            .field("body", &BodyLen(self.body.len()))
+            .finish()
+    }
+}
+
+#[cfg(feature = "serde")]
+mod ser {
+    use serde::ser::{SerializeMap, SerializeStruct};
+    use serde::{Serialize, Serializer};
+
+    use super::{is_redacted, value_text, Headers, REDACTED};
+    use crate::{Request, Response};
+
+    /// Each header once, in first-seen order, with repeated headers as arrays.
+    impl Serialize for Headers<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut map = serializer.serialize_map(Some(self.0.keys_len()))?;
+            for name in self.0.keys() {
+                if is_redacted(name.as_str()) {
+                    map.serialize_entry(name.as_str(), REDACTED)?;
+                    continue;
+                }
+                let values: Vec<String> = self.0.get_all(name).iter().map(value_text).collect();
+                match values.as_slice() {
+                    [value] => map.serialize_entry(name.as_str(), value)?,
+                    values => map.serialize_entry(name.as_str(), values)?,
+                }
+            }
+            map.end()
+        }
+    }
+
+    /// The request's metadata: `method`, `version`, `url`, `headers`, `body_len`, `peer`
+    /// (`null` for transports without addresses), `connection_id` and `sequence`.
+    impl Serialize for Request {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut s = serializer.serialize_struct("Request", 8)?;
+            s.serialize_field("method", self.method.as_str())?;
+            s.serialize_field("version", &format!("{:?}", self.version))?;
+            s.serialize_field("url", self.original_url())?;
+            s.serialize_field("headers", &Headers(&self.headers))?;
+            s.serialize_field("body_len", &self.body.len())?;
+            s.serialize_field("peer", &self.peer.map(|peer| peer.to_string()))?;
+            s.serialize_field("connection_id", &self.connection_id)?;
+            s.serialize_field("sequence", &self.sequence)?;
+            s.end()
+        }
+    }
+
+    /// The response's metadata: `status`, `headers` and `body_len`.
+    impl Serialize for Response {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut s = serializer.serialize_struct("Response", 3)?;
+            s.serialize_field("status", &self.status.as_u16())?;
+            s.serialize_field("headers", &Headers(&self.headers))?;
+            s.serialize_field("body_len", &self.body.len())?;
+            s.end()
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use tokio::sync::mpsc;
+
+use crate::Request;
+
+/// How many requests each lane holds before readers wait.
+const LANE_CAPACITY: usize = 1024;
+
+/// Which queue a request waits in, set with [`ServerBuilder::priority`].
+///
+/// [`Server::next`](crate::Server::next) always hands out a waiting `High` request before any
+/// `Normal` one, and `Normal` before `Low`. Lower lanes only move when higher ones are empty,
+/// so keep `High` for cheap, rare requests like health checks and admin calls.
+///
+/// [`ServerBuilder::priority`]: crate::ServerBuilder::priority
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+/// The sending side of the request queue, one channel per [`Priority`].
+#[derive(Clone)]
+pub(crate) struct Lanes {
+    high: mpsc::Sender<Request>,
+    normal: mpsc::Sender<Request>,
+    low: mpsc::Sender<Request>,
+}
+
+/// The receiving side, held by the [`Server`](crate::Server).
+pub(crate) struct Queue {
+    high: mpsc::Receiver<Request>,
+    normal: mpsc::Receiver<Request>,
+    low: mpsc::Receiver<Request>,
+}
+
+pub(crate) fn channel() -> (Lanes, Queue) {
+    let (high_tx, high_rx) = mpsc::channel(LANE_CAPACITY);
+    let (normal_tx, normal_rx) = mpsc::channel(LANE_CAPACITY);
+    let (low_tx, low_rx) = mpsc::channel(LANE_CAPACITY);
+    let lanes = Lanes {
+        high: high_tx,
+        normal: normal_tx,
+        low: low_tx,
+    };
+    let queue = Queue {
+        high: high_rx,
+        normal: normal_rx,
+        low: low_rx,
+    };
+    (lanes, queue)
+}
+
+impl Lanes {
+    /// Queues `request`, handing it back if the server has stopped taking requests.
+    pub(crate) async fn send(&self, priority: Priority, request: Request) -> Result<(), Request> {
+        let lane = match priority {
+            Priority::High => &self.high,
+            Priority::Normal => &self.normal,
+            Priority::Low => &self.low,
+        };
+        lane.send(request).await.map_err(|e| e.0)
+    }
+
+    /// Resolves once the server is dropped or drained. The lanes always close together.
+    pub(crate) async fn closed(&self) {
+        self.normal.closed().await
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.normal.is_closed()
+    }
+}
+
+impl Queue {
+    pub(crate) async fn recv(&mut self) -> Option<Request> {
+        // `biased` polls the lanes in order, so a waiting high-priority request always wins.
+        // A lane that is closed and empty drops out; `None` once they all have.
+        tokio::select! {
+            biased;
+            Some(request) = self.high.recv() => Some(request),
+            Some(request) = self.normal.recv() => Some(request),
+            Some(request) = self.low.recv() => Some(request),
+            else => None,
+        }
+    }
+
+    pub(crate) fn close(&mut self) {
+        self.high.close();
+        self.normal.close();
+        self.low.close();
+    }
+}
@@ -0,0 +1,208 @@
+//! Response time tracking per route, enabled via [`ServerBuilder::latency_stats`] and read
+//! back with [`Server::stats`].
+//!
+//! Latencies land in log-spaced buckets (four per doubling), so percentiles are estimates
+//! within about 20%, which is plenty for checking an SLO and costs two atomic adds a request.
+//!
+//! ```
+//! use std::time::Duration;
+//! use async_tiny::latency::LatencyStats;
+//!
+//! let stats = LatencyStats::new().route("/users/:id").route("/static/*");
+//! stats.record("/users/42?full=1", Duration::from_millis(3));
+//! stats.record("/static/app.js", Duration::from_micros(400));
+//! stats.record("/", Duration::from_millis(1));
+//!
+//! let snapshot = stats.snapshot();
+//! assert_eq!(snapshot.total.count(), 3);
+//! let users = snapshot.route("/users/:id").unwrap();
+//! assert_eq!(users.count(), 1);
+//! assert!(users.percentile(99.0) >= Duration::from_millis(3));
+//! assert_eq!(users.within(Duration::from_millis(10)), 1.0);
+//! ```
+//!
+//! [`ServerBuilder::latency_stats`]: crate::ServerBuilder::latency_stats
+//! [`Server::stats`]: crate::Server::stats
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::pattern::Pattern;
+
+/// Four buckets per power of two from 1µs, topping out past two minutes.
+const BUCKETS: usize = 108;
+const PER_DOUBLING: f64 = 4.0;
+
+/// Which routes to track. Requests matching none of them only count towards the total.
+pub struct LatencyStats {
+    total: Histogram,
+    routes: Vec<(Pattern, Histogram)>,
+}
+
+impl Default for LatencyStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyStats {
+    /// Track the total only.
+    pub fn new() -> Self {
+        Self {
+            total: Histogram::new(),
+            routes: Vec::new(),
+        }
+    }
+
+    /// Track requests whose path matches `pattern` separately.
+    ///
+    /// Segments starting with `:` match any one segment, and a final `*` matches the rest of
+    /// the path. When patterns overlap the first one added wins.
+    pub fn route(mut self, pattern: &str) -> Self {
+        self.routes
+            .push((Pattern::parse(pattern), Histogram::new()));
+        self
+    }
+
+    /// Count one response to `url` (path and optional query) that took `elapsed`.
+    ///
+    /// The server does this itself for every request the application answers; call it
+    /// directly to feed in latencies from elsewhere, or in tests.
+    pub fn record(&self, url: &str, elapsed: Duration) {
+        self.record_route(self.route_for(url), elapsed);
+    }
+
+    /// The index of the route `url` counts towards, worked out before the URL moves into the
+    /// request.
+    pub(crate) fn route_for(&self, url: &str) -> Option<usize> {
+        let path = url.split('?').next().unwrap_or("");
+        self.routes.iter().position(|(p, _)| p.matches(path))
+    }
+
+    pub(crate) fn record_route(&self, route: Option<usize>, elapsed: Duration) {
+        self.total.record(elapsed);
+        if let Some((_, histogram)) = route.and_then(|i| self.routes.get(i)) {
+            histogram.record(elapsed);
+        }
+    }
+
+    /// The latencies recorded so far.
+    pub fn snapshot(&self) -> Stats {
+        Stats {
+            total: self.total.snapshot(),
+            routes: self
+                .routes
+                .iter()
+                .map(|(p, h)| (p.as_str().to_string(), h.snapshot()))
+                .collect(),
+        }
+    }
+}
+
+/// A point-in-time copy of the tracked latencies.
+#[derive(Clone, Debug)]
+pub struct Stats {
+    /// Every request, matched to a route or not.
+    pub total: LatencySnapshot,
+    /// Each pattern passed to [`LatencyStats::route`], in order.
+    pub routes: Vec<(String, LatencySnapshot)>,
+}
+
+impl Stats {
+    /// The snapshot for `pattern`, spelled as it was passed to [`LatencyStats::route`].
+    pub fn route(&self, pattern: &str) -> Option<&LatencySnapshot> {
+        self.routes
+            .iter()
+            .find(|(p, _)| p == pattern)
+            .map(|(_, snapshot)| snapshot)
+    }
+}
+
+/// Latencies for one route, or all of them.
+#[derive(Clone, Debug)]
+pub struct LatencySnapshot {
+    counts: Vec<u64>,
+    count: u64,
+    max: Duration,
+}
+
+impl LatencySnapshot {
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The slowest response seen, exactly.
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// The latency `p` percent of responses came in under, e.g. `99.0` for p99. Zero when
+    /// nothing has been recorded.
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((p.clamp(0.0, 100.0) / 100.0 * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, &n) in self.counts.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return upper_bound(i).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    /// The fraction of responses that took at most `target`, for an SLO like "99% under
+    /// 200ms". Responses in the same bucket as `target` count as within it. One when nothing
+    /// has been recorded.
+    pub fn within(&self, target: Duration) -> f64 {
+        if self.count == 0 {
+            return 1.0;
+        }
+        let fast: u64 = self.counts[..=bucket(target)].iter().sum();
+        fast as f64 / self.count as f64
+    }
+}
+
+struct Histogram {
+    counts: Vec<AtomicU64>,
+    max_micros: AtomicU64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            counts: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            max_micros: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, elapsed: Duration) {
+        self.counts[bucket(elapsed)].fetch_add(1, Ordering::Relaxed);
+        let micros = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+        self.max_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> LatencySnapshot {
+        let counts: Vec<u64> = self
+            .counts
+            .iter()
+            .map(|c| c.load(Ordering::Relaxed))
+            .collect();
+        LatencySnapshot {
+            count: counts.iter().sum(),
+            counts,
+            max: Duration::from_micros(self.max_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+fn bucket(elapsed: Duration) -> usize {
+    let micros = elapsed.as_micros().max(1) as f64;
+    ((micros.log2() * PER_DOUBLING) as usize).min(BUCKETS - 1)
+}
+
+fn upper_bound(bucket: usize) -> Duration {
+    Duration::from_secs_f64(2f64.powf((bucket + 1) as f64 / PER_DOUBLING) / 1e6)
+}
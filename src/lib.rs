@@ -5,7 +5,11 @@
 //!
 //! ## How it works
 //! - Hyper accepts connections and parses requests.
-//! - Each request body is fully buffered into `Bytes`.
+//! - Each request body is fully buffered into `Bytes`. Bodies that can't be read intact are
+//!   answered with `400` (or `413` above the size limit) and never reach your loop, so an
+//!   empty `body()` always means the client sent an empty body.
+//! - `OPTIONS *` asks about the server itself, so it's answered directly with an `Allow`
+//!   header (see [`MethodPolicy`]) and never reaches your loop either.
 //! - A simplified `Request` (method, headers, URL, body) is sent over an `mpsc` channel.
 //! - You receive it via `Server::next().await` and respond using `req.respond(Response)`.
 //! - The response is translated back into Hyper and sent to the client.
@@ -14,125 +18,944 @@
 //! It's ideal for small web apps, embedded tools, or frameworks like [Velto](https://github.com/pjdur/velto).
 
 use std::convert::Infallible;
+use std::future::Future;
 use std::net::SocketAddr;
+use std::pin::Pin;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
 
-use bytes::Bytes;
-use http::{HeaderMap, Method, StatusCode, Uri};
-pub use http::{HeaderName, HeaderValue};
+use bytes::{Bytes, BytesMut};
+use http::header::{
+    ACCEPT, CONNECTION, CONTENT_LENGTH, CONTENT_TYPE, HOST, IF_NONE_MATCH, LOCATION, ORIGIN,
+    REFERER, RETRY_AFTER, TRANSFER_ENCODING, USER_AGENT, VARY,
+};
+use http::{HeaderMap, StatusCode, Uri};
+pub use http::{HeaderName, HeaderValue, Method, Version};
 use http_body_util::{BodyExt, Full};
-use hyper::body::Incoming as HyperBody;
+use hyper::body::{Body, Frame, Incoming as HyperBody, SizeHint};
+use hyper::ext::ReasonPhrase;
 use hyper::{Request as HyperRequest, Response as HyperResponse};
 use hyper_util::rt::TokioIo;
-use tokio::net::TcpListener;
-use tokio::sync::{mpsc, oneshot};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+pub mod access_log;
+pub mod affinity;
+mod builder;
+pub mod canonical;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+mod charset;
+pub mod conditional;
+mod conn_error;
+pub mod cors;
+pub mod date;
+#[cfg(feature = "dev")]
+pub mod dev;
+pub mod digest;
+pub mod download;
+pub mod echo;
+mod error;
+pub mod error_pages;
+pub mod etag;
+pub mod header_policy;
+pub mod honeypot;
+#[cfg(feature = "sha2")]
+pub mod idempotency;
+mod inspect;
+mod json;
+mod lanes;
+pub mod latency;
+pub mod load_shed;
+pub mod maintenance;
+pub mod map_error;
+mod media_type;
+mod method_policy;
+pub mod mount;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+pub mod multipart;
+#[cfg(windows)]
+mod named_pipe;
+pub mod parser;
+mod pattern;
+mod range;
+pub mod rate_limit;
+mod redirect;
+mod socket;
+pub mod static_files;
+pub mod store;
+mod target;
+pub mod tarpit;
+#[cfg(feature = "template")]
+pub mod template;
+#[cfg(feature = "testutil")]
+pub mod testutil;
+pub mod upgrade;
+#[cfg(feature = "vsock")]
+mod vsock;
+#[cfg(feature = "template")]
+pub use template::TemplateEngine;
+
+pub use access_log::AccessLog;
+use builder::{Config, ResponseHook};
+pub use builder::{Decision, RequestHead, ServerBuilder, UploadProgress};
+use charset::Charset;
+pub use charset::TextError;
+use conn_error::ErrorChannel;
+pub use conn_error::{ConnectionError, ConnectionErrorKind, ErrorCounts};
+pub use cors::Cors;
+pub use error::Error;
+pub use error_pages::ErrorPages;
+pub use lanes::Priority;
+use lanes::{Lanes, Queue};
+pub use latency::LatencyStats;
+use latency::Stats;
+use maintenance::Maintenance;
+use map_error::{ErrorMapper, HandlerOutput};
+pub use map_error::{HttpError, MapError};
+pub use media_type::MediaType;
+pub use method_policy::MethodPolicy;
+pub use mount::Mount;
+pub use parser::ParserProfile;
+pub use rate_limit::RateLimit;
+pub use socket::SocketOptions;
+pub use target::TargetForm;
 
 /// The main server: bind with Server::http(...).await?, then loop server.next().await.
 pub struct Server {
-    rx: mpsc::Receiver<Request>,
-    _join: tokio::task::JoinHandle<()>,
+    queue: Queue,
+    /// Accept loops and background tasks such as the load-shed monitor, aborted by
+    /// [`Server::drain`] and when the server is dropped.
+    accept: Vec<JoinHandle<()>>,
+    slow_queue: Option<Duration>,
+    max_queue_time: Option<Duration>,
+    error_pages: ErrorPages,
+    silent: bool,
+    errors: ErrorChannel,
+    latency: Option<Arc<LatencyStats>>,
+    maintenance: maintenance::Switch,
+    map_error: Arc<dyn MapError>,
+    /// Fires when a [`ServerBuilder::restart_on_change`] directory changes.
+    #[cfg(feature = "dev")]
+    restart: Option<oneshot::Receiver<()>>,
+    #[cfg(feature = "dev")]
+    debug_pages: bool,
 }
 
 impl Server {
-    /// Bind an HTTP/1 server on addr like "127.0.0.1:8080".
-    pub async fn http(addr: &str, silent: bool) -> std::io::Result<Self> {
-        let (tx, rx) = mpsc::channel::<Request>(1024);
-        let addr: SocketAddr = addr.parse().map_err(into_io_error)?;
-
-        let tx_clone = tx.clone();
-
-        let join = tokio::spawn({
-            let silent = silent;
-            async move {
-                let listener = TcpListener::bind(addr).await.expect("bind failed");
-                if !silent {
-                    eprintln!("async_tiny listening on http://{}", addr);
+    /// Bind an HTTP/1 server on addr like "127.0.0.1:8080", "localhost:8080",
+    /// `("0.0.0.0", 8080)` or a [`SocketAddr`].
+    pub async fn http(addr: impl ToSocketAddrs, silent: bool) -> std::io::Result<Self> {
+        Self::builder().silent(silent).http(addr).await
+    }
+
+    /// Bind an HTTP/1 server on `port` for both IPv4 and IPv6.
+    ///
+    /// See [`ServerBuilder::http_dual_stack`].
+    pub async fn http_dual_stack(port: u16, silent: bool) -> std::io::Result<Self> {
+        Self::builder().silent(silent).http_dual_stack(port).await
+    }
+
+    /// Serve HTTP/1 on a Windows named pipe like `r"\\.\pipe\myapp"`, for local IPC.
+    ///
+    /// See [`ServerBuilder::named_pipe`].
+    #[cfg(windows)]
+    pub async fn named_pipe(name: &str, silent: bool) -> std::io::Result<Self> {
+        Self::builder().silent(silent).named_pipe(name).await
+    }
+
+    /// Serve HTTP/1 on vsock port `port`, for guest agents and microVM services.
+    ///
+    /// See [`ServerBuilder::vsock`].
+    #[cfg(feature = "vsock")]
+    pub async fn vsock(cid: u32, port: u32, silent: bool) -> std::io::Result<Self> {
+        Self::builder().silent(silent).vsock(cid, port).await
+    }
+
+    /// Serve HTTP/1 over each stream received from `streams`, without binding a socket.
+    ///
+    /// Any `AsyncRead + AsyncWrite` works: `tokio::io::duplex` for tests and fuzzing, or an
+    /// exotic transport accepted elsewhere. The server stops taking new streams once every
+    /// sender is dropped. Requests from these streams have no peer address.
+    ///
+    /// ```
+    /// use async_tiny::{Response, Server};
+    /// use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (streams, source) = tokio::sync::mpsc::channel(1);
+    /// let mut server = Server::from_streams(source);
+    ///
+    /// let (mut client, stream) = tokio::io::duplex(4096);
+    /// streams.send(stream).await.unwrap();
+    /// client
+    ///     .write_all(b"GET /ping HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n")
+    ///     .await
+    ///     .unwrap();
+    ///
+    /// let request = server.next().await.unwrap();
+    /// assert_eq!(request.url(), "/ping");
+    /// request.respond(Response::from_string("pong")).unwrap();
+    ///
+    /// let mut response = String::new();
+    /// client.read_to_string(&mut response).await.unwrap();
+    /// assert!(response.starts_with("HTTP/1.1 200 OK"));
+    /// assert!(response.ends_with("pong"));
+    /// # }
+    /// ```
+    pub fn from_streams<S>(streams: mpsc::Receiver<S>) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        Self::builder().serve_streams(streams)
+    }
+
+    /// Start configuring a server with more options than [`Server::http`] takes.
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::new()
+    }
+
+    pub(crate) fn start(listeners: Vec<TcpListener>, config: Config) -> std::io::Result<Self> {
+        if !config.silent {
+            let mut addrs: Vec<_> = listeners
+                .iter()
+                .filter_map(|l| l.local_addr().ok())
+                .collect();
+            // Several acceptors can share an address.
+            addrs.dedup();
+            for addr in addrs {
+                eprintln!("async_tiny listening on http://{}", addr);
+            }
+        }
+
+        if config.acceptor_threads {
+            let cores = &config.acceptor_cores;
+            let threads = listeners
+                .into_iter()
+                .enumerate()
+                .map(|(index, listener)| {
+                    let core = (!cores.is_empty()).then(|| cores[index % cores.len()]);
+                    AcceptorThread::spawn(index, listener, core)
+                })
+                .collect::<std::io::Result<Vec<_>>>()?;
+            return Ok(Self::spawn(config, |tx, config| {
+                for thread in threads {
+                    let _ = thread.start.send((tx.clone(), config.clone()));
                 }
+                Vec::new()
+            }));
+        }
+
+        Ok(Self::spawn(config, |tx, config| {
+            listeners
+                .into_iter()
+                .map(|listener| tokio::spawn(accept_loop(listener, tx.clone(), config.clone())))
+                .collect()
+        }))
+    }
+
+    /// Sets up the request queue and spawns the accept loops `accept` returns.
+    pub(crate) fn spawn<F>(config: Config, accept: F) -> Self
+    where
+        F: FnOnce(&Lanes, &Arc<Config>) -> Vec<JoinHandle<()>>,
+    {
+        let (lanes, queue) = lanes::channel();
+        let config = Arc::new(config);
+        #[allow(unused_mut)]
+        let mut accept = accept(&lanes, &config);
+        if let Some(shedder) = &config.load_shed {
+            accept.push(tokio::spawn(shedder.clone().monitor()));
+        }
+        #[cfg(feature = "dev")]
+        if let Some(live_reload) = &config.live_reload {
+            accept.push(tokio::spawn(live_reload.clone().run()));
+        }
+        #[cfg(feature = "dev")]
+        let restart = (!config.restart_watch.is_empty()).then(|| {
+            let (restart_tx, restart_rx) = oneshot::channel();
+            let mut restart_tx = Some(restart_tx);
+            let dirs = config.restart_watch.clone();
+            accept.push(tokio::spawn(dev::watch_dirs(
+                dirs,
+                dev::SCAN_INTERVAL,
+                move || {
+                    if let Some(tx) = restart_tx.take() {
+                        let _ = tx.send(());
+                    }
+                },
+            )));
+            restart_rx
+        });
+        Server {
+            queue,
+            accept,
+            slow_queue: config.slow_queue,
+            max_queue_time: config.max_queue_time,
+            error_pages: config.error_pages.clone(),
+            silent: config.silent,
+            errors: config.errors.clone(),
+            latency: config.latency.clone(),
+            maintenance: config.maintenance.clone(),
+            map_error: config.map_error.clone().unwrap_or_else(|| {
+                Arc::new(ErrorMapper::new().error_pages(config.error_pages.clone()))
+            }),
+            #[cfg(feature = "dev")]
+            restart,
+            #[cfg(feature = "dev")]
+            debug_pages: config.debug_pages,
+        }
+    }
+
+    /// Await the next incoming request from any connection.
+    ///
+    /// With [`ServerBuilder::priority`] configured, waiting requests come out by [`Priority`]
+    /// first and arrival order second.
+    ///
+    /// Requests arrive in order across connections, but a connection only ever has one request
+    /// in the queue: HTTP/1 answers pipelined requests in order, so the next one isn't read
+    /// until the current one has been responded to. A client pipelining hundreds of requests
+    /// therefore takes turns with everyone else rather than starving them; only opening more
+    /// connections gets it a larger share.
+    ///
+    /// With [`ServerBuilder::max_queue_time`] set, requests that waited too long are answered
+    /// here with `503` and skipped.
+    pub async fn next(&mut self) -> Option<Request> {
+        loop {
+            let request = self.recv().await?;
+            if let Some(request) = self.dequeue(request) {
+                return Some(request);
+            }
+        }
+    }
+
+    async fn recv(&mut self) -> Option<Request> {
+        #[cfg(feature = "dev")]
+        if let Some(restart) = &mut self.restart {
+            tokio::select! {
+                biased;
+                _ = restart => {
+                    if !self.silent {
+                        eprintln!("async_tiny: watched files changed, draining for restart");
+                    }
+                    self.drain();
+                }
+                request = self.queue.recv() => return request,
+            }
+        }
+        self.queue.recv().await
+    }
+
+    /// Stamps a request on its way out of the queue, or answers it if it has expired.
+    fn dequeue(&self, mut request: Request) -> Option<Request> {
+        let now = Instant::now();
+        request.dequeued = Some(now);
+
+        if let Some(max) = self.max_queue_time {
+            if now - request.enqueued > max {
+                let _ = request.respond(self.error_pages.response(503));
+                return None;
+            }
+        }
+
+        if let Some(threshold) = self.slow_queue {
+            let queued = now - request.enqueued;
+            if queued > threshold && !self.silent {
+                eprintln!(
+                    "async_tiny: {} {} waited {:.1}ms in the queue",
+                    request.method,
+                    request.url,
+                    queued.as_secs_f64() * 1000.0
+                );
+            }
+        }
+        Some(request)
+    }
+
+    /// Subscribe to connection and protocol errors: failed accepts, malformed requests,
+    /// connections reset mid-request and the like.
+    ///
+    /// These are printed to stderr (unless silent) while nobody is subscribed, and sent here
+    /// instead once someone is. A subscriber that falls more than 256 errors behind skips the
+    /// oldest and sees [`RecvError::Lagged`](tokio::sync::broadcast::error::RecvError::Lagged).
+    ///
+    /// ```no_run
+    /// # async fn run() -> std::io::Result<()> {
+    /// use async_tiny::Server;
+    ///
+    /// let server = Server::http("127.0.0.1:8080", false).await?;
+    /// let mut errors = server.errors();
+    /// tokio::spawn(async move {
+    ///     while let Ok(error) = errors.recv().await {
+    ///         println!("{:?} from {:?}: {}", error.kind, error.peer, error.message);
+    ///     }
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn errors(&self) -> broadcast::Receiver<ConnectionError> {
+        self.errors.subscribe()
+    }
+
+    /// How many connection and protocol errors of each kind there have been so far.
+    pub fn error_counts(&self) -> ErrorCounts {
+        self.errors.counts()
+    }
+
+    /// Response times so far, if [`ServerBuilder::latency_stats`] is on.
+    pub fn stats(&self) -> Option<Stats> {
+        self.latency.as_ref().map(|stats| stats.snapshot())
+    }
+
+    /// A handle for controlling the server from elsewhere, e.g. an admin endpoint or a
+    /// signal handler, while this `Server` is busy in [`Server::next`] or [`Server::serve`].
+    pub fn handle(&self) -> ServerHandle {
+        ServerHandle {
+            maintenance: self.maintenance.clone(),
+        }
+    }
+
+    /// Stop accepting connections and wind down for a rolling deploy.
+    ///
+    /// Requests already queued are still delivered by [`Server::next`], which returns `None`
+    /// once the queue is empty. Anything arriving afterwards on an existing keep-alive
+    /// connection is answered with `503` and `Connection: close`.
+    pub fn drain(&mut self) {
+        #[cfg(feature = "dev")]
+        {
+            self.restart = None;
+        }
+        for task in &self.accept {
+            task.abort();
+        }
+        self.queue.close();
+    }
+
+    /// Answer every request with `handler`, each on its own task, until the server is drained.
+    ///
+    /// The handler returns the response rather than calling [`Request::respond`], so it can be
+    /// an `async fn` that matches on the method and path. It may also return
+    /// `Result<Response, E>`, with errors turned into responses by
+    /// [`ServerBuilder::map_errors`]; see [`map_error`]. A handler that
+    /// panics leaves the client with a `500`.
+    ///
+    /// ```no_run
+    /// use async_tiny::{Method, Request, Response, Server};
+    ///
+    /// async fn app(request: Request) -> Response {
+    ///     match (request.method(), request.url()) {
+    ///         (&Method::GET, "/") => Response::from_string("home"),
+    ///         _ => Response::from_status_and_string(404, "not found"),
+    ///     }
+    /// }
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// Server::http("127.0.0.1:8080", false).await?.serve(app).await;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn serve<F, Fut>(mut self, handler: F)
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: HandlerOutput,
+    {
+        #[cfg(feature = "dev")]
+        if self.debug_pages {
+            dev::debug_page::install_panic_hook();
+        }
+        let handler = Arc::new(handler);
+        while let Some(mut request) = self.next().await {
+            let reply = request.split_reply();
+            let handler = handler.clone();
+            let errors = self.map_error.clone();
+            #[cfg(feature = "dev")]
+            if self.debug_pages {
+                tokio::spawn(debug_serve(handler, request, reply, errors));
+                continue;
+            }
+            tokio::spawn(async move {
+                let response = handler(request).await.into_response(errors.as_ref());
+                let _ = reply.respond(response);
+            });
+        }
+    }
+}
+
+/// Stops accepting and ends the background tasks: dropping a [`JoinHandle`] would leave them
+/// running for the life of the runtime.
+impl Drop for Server {
+    fn drop(&mut self) {
+        for task in &self.accept {
+            task.abort();
+        }
+    }
+}
+
+/// Answers `request` like [`Server::serve`], but with debug pages for panics and errors.
+#[cfg(feature = "dev")]
+async fn debug_serve<F, Fut>(
+    handler: Arc<F>,
+    request: Request,
+    reply: Request,
+    fallback: Arc<dyn MapError>,
+) where
+    F: Fn(Request) -> Fut + Send + Sync + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: HandlerOutput,
+{
+    use dev::debug_page::{self, DebugErrors, RequestInfo};
+
+    let info = RequestInfo::of(&request);
+    let errors = DebugErrors {
+        request: info.clone(),
+        fallback,
+    };
+    // On a task of its own, so a panic ends that task and can be shown here.
+    let task = tokio::spawn(async move { handler(request).await.into_response(&errors) });
+    let response = match task.await {
+        Ok(response) => response,
+        Err(e) => debug_page::panic_page(e, &info),
+    };
+    let _ = reply.respond(response);
+}
+
+/// Bind `addr` and answer every request with `handler`: the one-call version of the
+/// [`Server::next`] loop for applications that don't need it.
+///
+/// Requests are logged to stderr with [`AccessLog::new`] and bodies over 2 MiB are refused;
+/// use [`Server::builder`] and [`Server::serve`] for anything else.
+///
+/// ```no_run
+/// use async_tiny::{Request, Response};
+///
+/// #[tokio::main]
+/// async fn main() -> std::io::Result<()> {
+///     async_tiny::serve("127.0.0.1:8080", |request: Request| async move {
+///         Response::from_string(format!("you asked for {}", request.url()))
+///     })
+///     .await
+/// }
+/// ```
+pub async fn serve<F, Fut>(addr: impl ToSocketAddrs, handler: F) -> std::io::Result<()>
+where
+    F: Fn(Request) -> Fut + Send + Sync + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: HandlerOutput,
+{
+    let server = Server::builder()
+        .access_log(AccessLog::new())
+        .max_body_size(SERVE_MAX_BODY_SIZE)
+        .http(addr)
+        .await?;
+    server.serve(handler).await;
+    Ok(())
+}
 
-                loop {
-                    let (stream, _) = match listener.accept().await {
-                        Ok(s) => s,
+/// The body limit [`serve`] sets.
+const SERVE_MAX_BODY_SIZE: usize = 2 << 20;
+
+/// Like [`accept_loop`], for streams handed over by the application.
+pub(crate) async fn stream_loop<S>(mut streams: mpsc::Receiver<S>, tx: Lanes, config: Arc<Config>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    loop {
+        let stream = tokio::select! {
+            stream = streams.recv() => stream,
+            _ = tx.closed() => break,
+        };
+        let Some(stream) = stream else { break };
+        tokio::spawn(serve_connection(
+            TokioIo::new(stream),
+            None,
+            tx.clone(),
+            config.clone(),
+        ));
+    }
+}
+
+async fn accept_loop(listener: TcpListener, tx: Lanes, config: Arc<Config>) {
+    accept_each(&listener, &tx, &config, |io, peer| {
+        tokio::spawn(serve_connection(io, Some(peer), tx.clone(), config.clone()));
+    })
+    .await;
+}
+
+/// [`accept_loop`] for an acceptor thread, where connections run on the thread's own
+/// runtime. Returns once they've all finished, since they'd be cancelled with the runtime.
+async fn thread_accept_loop(listener: TcpListener, tx: Lanes, config: Arc<Config>) {
+    let mut connections = tokio::task::JoinSet::new();
+    accept_each(&listener, &tx, &config, |io, peer| {
+        while connections.try_join_next().is_some() {}
+        connections.spawn(serve_connection(io, Some(peer), tx.clone(), config.clone()));
+    })
+    .await;
+    drop(listener);
+    while connections.join_next().await.is_some() {}
+}
+
+/// A thread for [`ServerBuilder::acceptor_threads`], waiting for the request queue to accept
+/// into. It's started before the server exists, so failures surface from the constructor.
+struct AcceptorThread {
+    start: std::sync::mpsc::Sender<(Lanes, Arc<Config>)>,
+}
+
+impl AcceptorThread {
+    fn spawn(index: usize, listener: TcpListener, core: Option<usize>) -> std::io::Result<Self> {
+        let listener = listener.into_std()?;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let (start, started) = std::sync::mpsc::channel::<(Lanes, Arc<Config>)>();
+        std::thread::Builder::new()
+            .name(format!("async_tiny-acceptor-{}", index))
+            .spawn(move || {
+                let Ok((tx, config)) = started.recv() else {
+                    return;
+                };
+                if let Some(id) = core {
+                    core_affinity::set_for_current(core_affinity::CoreId { id });
+                }
+                runtime.block_on(async move {
+                    match TcpListener::from_std(listener) {
+                        Ok(listener) => thread_accept_loop(listener, tx, config).await,
                         Err(e) => {
-                            if !silent {
-                                eprintln!("Accept error: {}", e);
-                            }
-                            continue;
-                        }
-                    };
-
-                    let io = TokioIo::new(stream);
-                    let tx = tx_clone.clone();
-
-                    tokio::spawn(async move {
-                        let service =
-                            hyper::service::service_fn(move |req: HyperRequest<HyperBody>| {
-                                let tx = tx.clone();
-                                async move {
-                                    let url = path_and_query(req.uri());
-                                    let (parts, body) = req.into_parts();
-                                    let collected = match body.collect().await {
-                                        Ok(c) => c.to_bytes(),
-                                        Err(_) => Bytes::new(),
-                                    };
-
-                                    let (resp_tx, resp_rx) = oneshot::channel::<Response>();
-
-                                    let request = Request {
-                                        method: parts.method,
-                                        headers: parts.headers,
-                                        url,
-                                        body: collected,
-                                        respond_tx: Some(resp_tx),
-                                    };
-
-                                    if tx.send(request).await.is_err() {
-                                        return Ok::<_, Infallible>(response_text(
-                                            StatusCode::SERVICE_UNAVAILABLE,
-                                            "Service Unavailable",
-                                        ));
-                                    }
-
-                                    let resp = match resp_rx.await {
-                                        Ok(r) => to_hyper_response(r),
-                                        Err(_) => response_text(
-                                            StatusCode::INTERNAL_SERVER_ERROR,
-                                            "Internal Server Error",
-                                        ),
-                                    };
-
-                                    Ok::<_, Infallible>(resp)
-                                }
-                            });
-
-                        if let Err(err) = hyper::server::conn::http1::Builder::new()
-                            .serve_connection(io, service)
-                            .await
-                        {
-                            if !silent {
-                                eprintln!("Connection error: {:?}", err);
-                            }
+                            config.report(ConnectionError::new(ConnectionErrorKind::Accept, e))
                         }
-                    });
-                }
+                    }
+                });
+            })?;
+        Ok(Self { start })
+    }
+}
+
+/// Accepts connections until the server stops taking requests, handing each to `serve`.
+async fn accept_each<F>(listener: &TcpListener, tx: &Lanes, config: &Config, mut serve: F)
+where
+    F: FnMut(TokioIo<tokio::net::TcpStream>, SocketAddr),
+{
+    loop {
+        // Once the `Server` is dropped or drained nobody will answer, so stop accepting and let
+        // the listener close rather than queueing connections for a 503.
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = tx.closed() => break,
+        };
+        let (stream, peer) = match accepted {
+            Ok(s) => s,
+            Err(e) => {
+                config.report(ConnectionError::new(ConnectionErrorKind::Accept, e));
+                continue;
             }
+        };
+
+        if let Err(e) = config.socket_options.apply(&stream) {
+            let mut error = ConnectionError::new(ConnectionErrorKind::SocketOptions, e);
+            error.peer = Some(peer);
+            config.report(error);
+        }
+
+        serve(TokioIo::new(stream), peer);
+    }
+}
+
+pub(crate) async fn serve_connection<I>(
+    io: TokioIo<I>,
+    peer: Option<SocketAddr>,
+    tx: Lanes,
+    config: Arc<Config>,
+) where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+    let connection_id = NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed);
+    let sequence = AtomicU64::new(0);
+
+    let builder = config.http1();
+    let reporter = config.clone();
+    let service = hyper::service::service_fn(move |req: HyperRequest<HyperBody>| {
+        let conn = ConnInfo {
+            peer,
+            id: connection_id,
+            sequence: sequence.fetch_add(1, Ordering::Relaxed),
+        };
+        let config = config.clone();
+        let policy = config.header_policy.as_ref();
+        let request_id =
+            policy.and_then(|p| p.request_id_for(req.headers(), conn.id, conn.sequence));
+        let tx = tx.clone();
+        async move {
+            let mut resp = handle(req, conn, tx, config.clone()).await?;
+            config.default_headers.apply(resp.headers_mut(), None);
+            if let Some(policy) = &config.header_policy {
+                policy.apply(resp.headers_mut(), request_id);
+            }
+            Ok::<_, Infallible>(resp)
+        }
+    });
+
+    if let Err(err) = builder.serve_connection(io, service).with_upgrades().await {
+        reporter.report(ConnectionError::from_hyper(&err).on(peer, connection_id));
+    }
+}
+
+/// Controls a running [`Server`]; get one with [`Server::handle`]. Clones control the same
+/// server.
+#[derive(Clone)]
+pub struct ServerHandle {
+    maintenance: maintenance::Switch,
+}
+
+impl ServerHandle {
+    /// Answer everything but `maintenance`'s allowlist with `503` from now on, replacing any
+    /// earlier maintenance settings. See [`maintenance`].
+    pub fn set_maintenance(&self, maintenance: Maintenance) {
+        self.maintenance.set(Some(maintenance));
+    }
+
+    /// Serve requests normally again.
+    pub fn clear_maintenance(&self) {
+        self.maintenance.set(None);
+    }
+
+    pub fn in_maintenance(&self) -> bool {
+        self.maintenance.is_on()
+    }
+}
+
+/// Where a request came from: the peer, the connection, and its position on that connection.
+struct ConnInfo {
+    /// `None` for transports without addresses, like named pipes.
+    peer: Option<SocketAddr>,
+    id: u64,
+    sequence: u64,
+}
+
+async fn handle(
+    req: HyperRequest<HyperBody>,
+    conn: ConnInfo,
+    tx: Lanes,
+    config: Arc<Config>,
+) -> Result<HyperResponse<ResponseBody>, Infallible> {
+    let started = Instant::now();
+    let (mut parts, body) = req.into_parts();
+    if let Some(honeypot) = &config.honeypot {
+        if honeypot.is_banned(conn.peer) {
+            let resp = config.error_pages.response(403).close_connection();
+            return Ok(to_hyper_response(resp));
+        }
+    }
+    if let Err(reason) = config.parser_profile.check(&parts) {
+        let message = format!("rejected by parser profile: {}", reason);
+        let error = ConnectionError::new(ConnectionErrorKind::Parse, message);
+        config.report(error.on(conn.peer, conn.id));
+        let resp = config.error_pages.response(400).close_connection();
+        return Ok(to_hyper_response(resp));
+    }
+    let (target_form, url) = target::normalize(&parts.uri, &mut parts.headers);
+    if let Some(honeypot) = config.honeypot.as_ref().filter(|h| h.is_decoy(&url)) {
+        honeypot.record(&honeypot::Hit {
+            peer: conn.peer,
+            method: &parts.method,
+            url: &url,
+            headers: &parts.headers,
+            connection_id: conn.id,
+            time: SystemTime::now(),
         });
+        if !config.tarpit.as_ref().is_some_and(|t| t.traps_path(&url)) {
+            let resp = config.error_pages.response(404);
+            return Ok(to_hyper_response(reject(resp, &parts)));
+        }
+    }
+    if let Some(tarpit) = config.tarpit.as_ref().filter(|t| t.traps_path(&url)) {
+        return Ok(tarpit.respond(config.error_pages.response(404)));
+    }
+    if let Some(resp) = config
+        .maintenance
+        .check(conn.peer, &url, &config.error_pages)
+    {
+        return Ok(to_hyper_response(reject(resp, &parts)));
+    }
+    let head = RequestHead {
+        method: &parts.method,
+        url: &url,
+        headers: &parts.headers,
+        connection_id: conn.id,
+    };
+    if let Some(resp) = config.fast_path(&head) {
+        return Ok(to_hyper_response(reject(resp, &parts)));
+    }
+    if let Some(policy) = &config.method_policy {
+        if !policy.allows(&parts.method) {
+            let resp = policy.reject(config.error_pages.response(405));
+            return Ok(to_hyper_response(reject(resp, &parts)));
+        }
+    }
+    if parts.method == Method::OPTIONS && target_form == TargetForm::Asterisk {
+        // Asks about the server rather than a resource, so there's nothing for the app to route.
+        let resp = method_policy::server_options(config.method_policy.as_ref());
+        return Ok(to_hyper_response(reject(resp, &parts)));
+    }
+    let cors = config.cors.as_ref().map(|cors| cors.policy_for(&url));
+    if let Some(resp) = cors.and_then(|cors| cors.preflight(&parts.method, &parts.headers)) {
+        return Ok(to_hyper_response(reject(resp, &parts)));
+    }
+    let origin = cors.and_then(|_| parts.headers.get(ORIGIN).cloned());
+    #[cfg(feature = "dev")]
+    if let Some(live_reload) = &config.live_reload {
+        if live_reload.is_endpoint(&url) {
+            let resp = live_reload.poll(&url).await;
+            return Ok(to_hyper_response(reject(resp, &parts)));
+        }
+    }
+    if tx.is_closed() {
+        // Don't buffer a body nobody will read.
+        let resp = config.error_pages.response(503).close_connection();
+        return Ok(to_hyper_response(resp));
+    }
+    if let Some(limits) = &config.rate_limit {
+        if let Err(wait) = limits.check(conn.peer, &parts.headers).await {
+            let mut resp = config.error_pages.response(429);
+            // Whole seconds, rounded up so a client that honours it isn't refused again.
+            let secs = wait.as_secs_f64().ceil().min(u32::MAX as f64) as u64;
+            resp.headers.insert(RETRY_AFTER, HeaderValue::from(secs));
+            if let Some(tarpit) = config.tarpit.as_ref().filter(|t| t.traps_rate_limited()) {
+                return Ok(tarpit.respond(resp));
+            }
+            return Ok(to_hyper_response(reject(resp, &parts)));
+        }
+    }
 
-        Ok(Server { rx, _join: join })
+    let head = RequestHead {
+        method: &parts.method,
+        url: &url,
+        headers: &parts.headers,
+        connection_id: conn.id,
+    };
+    let mut limit = config.max_body_size;
+    if let Some(on_head) = &config.on_head {
+        match on_head(&head) {
+            Decision::Allow => {}
+            Decision::Limit(max) => limit = Some(max),
+            Decision::Reject(resp) => {
+                return Ok(to_hyper_response(reject(resp, &parts)));
+            }
+        }
+    }
+    let priority = config.priority(&head);
+    if config
+        .load_shed
+        .as_ref()
+        .is_some_and(|shedder| shedder.should_shed(priority))
+    {
+        let mut resp = config.error_pages.response(503);
+        resp.headers
+            .insert(RETRY_AFTER, HeaderValue::from_static("1"));
+        return Ok(to_hyper_response(reject(resp, &parts)));
     }
 
-    /// Await the next incoming request from any connection.
-    pub async fn next(&mut self) -> Option<Request> {
-        self.rx.recv().await
+    let (collected, body_digest) =
+        match read_body(body, &url, &parts.headers, &conn, &config, limit).await {
+            Ok(body) => body,
+            Err(resp) => return Ok(to_hyper_response(resp)),
+        };
+
+    let deadline = config.deadline(started, &parts.headers);
+
+    let entry = config
+        .access_log
+        .as_ref()
+        .and_then(|log| log.begin(conn.peer, &parts.method, &url, &parts.headers, &collected));
+
+    let route = config.latency.as_ref().map(|stats| stats.route_for(&url));
+
+    let (resp_tx, resp_rx) = oneshot::channel::<Response>();
+
+    let request = Request {
+        method: parts.method,
+        version: parts.version,
+        headers: parts.headers,
+        url,
+        target_form,
+        body: collected,
+        body_digest,
+        peer: conn.peer,
+        connection_id: conn.id,
+        sequence: conn.sequence,
+        deadline,
+        enqueued: Instant::now(),
+        dequeued: None,
+        respond_tx: Some(resp_tx),
+        on_response: config.on_response.clone(),
+        original_url: None,
+        upgrade: parts.extensions.remove(),
+    };
+
+    let resp = if tx.send(priority, request).await.is_err() {
+        // The receiver is gone or draining: refuse, and don't invite more requests on this
+        // connection.
+        config.error_pages.response(503).close_connection()
+    } else {
+        match resp_rx.await {
+            Ok(r) => r,
+            // The request was dropped without a response.
+            Err(_) => config.error_pages.response(500),
+        }
+    };
+
+    let resp = match cors {
+        Some(cors) => cors.apply(origin.as_ref(), resp),
+        None => resp,
+    };
+
+    #[cfg(feature = "dev")]
+    let resp = match &config.live_reload {
+        Some(live_reload) => live_reload.inject(resp),
+        None => resp,
+    };
+
+    let resp = match config.max_requests_per_connection {
+        Some(max) if conn.sequence + 1 >= max => resp.close_connection(),
+        _ => resp,
+    };
+
+    if let Some(entry) = entry {
+        entry.finish(&resp, started.elapsed());
+    }
+    if let (Some(stats), Some(route)) = (&config.latency, route) {
+        stats.record_route(route, started.elapsed());
     }
+
+    Ok(to_hyper_response(resp))
 }
 
 /// A tiny_http-like request handed to your loop.
 pub struct Request {
     method: Method,
+    version: Version,
     headers: HeaderMap,
     url: String,
+    target_form: TargetForm,
     body: Bytes,
+    body_digest: Option<Vec<u8>>,
+    peer: Option<SocketAddr>,
+    connection_id: u64,
+    sequence: u64,
+    deadline: Option<Instant>,
+    enqueued: Instant,
+    dequeued: Option<Instant>,
     respond_tx: Option<oneshot::Sender<Response>>,
+    on_response: Option<Arc<ResponseHook>>,
+    /// The URL before a [`Mount`] stripped its prefix.
+    original_url: Option<String>,
+    upgrade: Option<hyper::upgrade::OnUpgrade>,
 }
 
 impl Request {
@@ -140,24 +963,334 @@ impl Request {
         &self.url
     }
 
+    /// The URL as the client sent it, before any [`Mount`] prefix was stripped from
+    /// [`url`](Self::url).
+    pub fn original_url(&self) -> &str {
+        self.original_url.as_deref().unwrap_or(&self.url)
+    }
+
     pub fn method(&self) -> &Method {
         &self.method
     }
 
+    /// The HTTP version the client spoke.
+    ///
+    /// Responses to `HTTP/1.0` clients are never chunked, and their connection is closed
+    /// after each response unless they sent `Connection: keep-alive`.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// How the client wrote the target that [`Request::url`] came from.
+    pub fn target_form(&self) -> TargetForm {
+        self.target_form
+    }
+
     pub fn headers(&self) -> &HeaderMap {
         &self.headers
     }
 
+    /// The first value of header `name`, if present and printable ASCII.
+    ///
+    /// ```
+    /// use async_tiny::{Header, Method, Request};
+    ///
+    /// let req = Request::fake(&Method::GET, "/", b"")
+    ///     .with_header(Header::new("Accept", "text/html;q=0.9, application/json").unwrap())
+    ///     .with_header(Header::new("Content-Length", "0").unwrap())
+    ///     .with_header(Header::new("If-None-Match", "\"a\", W/\"b\"").unwrap())
+    ///     .with_header(Header::new("User-Agent", "curl/8.5.0").unwrap());
+    ///
+    /// assert_eq!(req.header("user-agent"), req.user_agent());
+    /// assert_eq!(req.content_length(), Some(0));
+    /// assert_eq!(req.if_none_match().collect::<Vec<_>>(), ["\"a\"", "W/\"b\""]);
+    /// let accept: Vec<_> = req.accept().iter().map(|m| m.essence().to_string()).collect();
+    /// assert_eq!(accept, ["application/json", "text/html"]);
+    /// ```
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)?.to_str().ok()
+    }
+
+    /// `Content-Length` as sent; the body itself is already fully read.
+    pub fn content_length(&self) -> Option<u64> {
+        self.header(CONTENT_LENGTH.as_str())?.trim().parse().ok()
+    }
+
+    pub fn user_agent(&self) -> Option<&str> {
+        self.header(USER_AGENT.as_str())
+    }
+
+    /// The entity tags listed in `If-None-Match`, across all its lines, including `*`.
+    pub fn if_none_match(&self) -> impl Iterator<Item = &str> {
+        self.headers
+            .get_all(IF_NONE_MATCH)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+    }
+
+    /// The media ranges in `Accept`, most preferred first: by `q` (which stays available as a
+    /// parameter), then in the order sent. Ranges with `q=0` are refused and left out.
+    pub fn accept(&self) -> Vec<MediaType> {
+        let mut ranges: Vec<(f32, MediaType)> = self
+            .headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter_map(MediaType::parse)
+            .map(|m| {
+                let q = m.param("q").and_then(|q| q.parse().ok()).unwrap_or(1.0);
+                (q, m)
+            })
+            .filter(|(q, _)| *q > 0.0)
+            .collect();
+        // Stable, so equal preferences keep their order.
+        ranges.sort_by(|a, b| b.0.total_cmp(&a.0));
+        ranges.into_iter().map(|(_, m)| m).collect()
+    }
+
     pub fn body(&self) -> &Bytes {
         &self.body
     }
 
+    /// The body as a reader, for decoders that take [`std::io::Read`] or
+    /// [`tokio::io::AsyncRead`], such as protobuf or MessagePack ones.
+    ///
+    /// The bytes are exactly as the client sent them, whatever the `Content-Type`: only the
+    /// `text` methods decode anything.
+    ///
+    /// ```
+    /// use std::io::Read;
+    ///
+    /// use async_tiny::{Method, Request};
+    ///
+    /// let request = Request::fake(&Method::POST, "/rpc", b"\x00\x00\x00\x00\x02\x08\xff");
+    /// let mut frame = Vec::new();
+    /// request.body_reader().read_to_end(&mut frame).unwrap();
+    /// assert_eq!(frame, b"\x00\x00\x00\x00\x02\x08\xff");
+    /// ```
+    pub fn body_reader(&self) -> std::io::Cursor<Bytes> {
+        std::io::Cursor::new(self.body.clone())
+    }
+
+    /// Decodes the body as CBOR; see [`cbor`].
+    #[cfg(feature = "cbor")]
+    pub fn cbor<T: serde::de::DeserializeOwned>(&self) -> Result<T, cbor::Error> {
+        cbor::from_slice(&self.body)
+    }
+
+    /// Decodes the body as MessagePack; see [`msgpack`].
+    ///
+    /// The `Content-Type` isn't checked, since devices rarely agree on one.
+    #[cfg(feature = "msgpack")]
+    pub fn msgpack<'a, T: serde::Deserialize<'a>>(&'a self) -> Result<T, msgpack::Error> {
+        msgpack::from_slice(&self.body)
+    }
+
+    /// The body's digest, when [`ServerBuilder::body_digest`] is set.
+    pub fn body_digest(&self) -> Option<&[u8]> {
+        self.body_digest.as_deref()
+    }
+
+    /// Decodes the body using the `charset` parameter of `Content-Type`, defaulting to UTF-8.
+    ///
+    /// UTF-8, US-ASCII, ISO-8859-1 and Windows-1252 are supported. Unknown charsets and bytes
+    /// that are invalid in the charset are errors.
+    pub fn body_text(&self) -> Result<String, TextError> {
+        self.body_charset()?.decode(&self.body)
+    }
+
+    /// Like [`Request::body_text`], but invalid bytes become U+FFFD and unknown charsets
+    /// fall back to UTF-8.
+    pub fn body_text_lossy(&self) -> String {
+        self.body_charset()
+            .unwrap_or(Charset::Utf8)
+            .decode_lossy(&self.body)
+    }
+
+    /// The parsed `Content-Type` header, if there is a well-formed one.
+    pub fn content_type(&self) -> Option<MediaType> {
+        let value = self.headers.get(CONTENT_TYPE)?.to_str().ok()?;
+        MediaType::parse(value)
+    }
+
+    fn body_charset(&self) -> Result<Charset, TextError> {
+        let content_type = self.content_type();
+        match content_type.as_ref().and_then(MediaType::charset) {
+            Some(label) => Charset::from_label(label)
+                .ok_or_else(|| TextError::UnsupportedCharset(label.to_string())),
+            None => Ok(Charset::Utf8),
+        }
+    }
+
+    /// The address of the client, or of the proxy in front of it.
+    ///
+    /// `None` for fake requests and for transports without addresses, like named pipes.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.peer
+    }
+
+    /// A stable key for sending this client to the same backend every time; see [`affinity`].
+    ///
+    /// Clients are told apart by the value of the cookie named `cookie` when it's given and
+    /// present, since many clients can share an address, and by [`Request::remote_addr`]'s IP
+    /// otherwise. Behind a reverse proxy that address is the proxy's, so pass a cookie.
+    pub fn affinity_key(&self, cookie: Option<&str>) -> Option<u64> {
+        let cookie = cookie.and_then(|name| affinity::cookie(&self.headers, name));
+        affinity::key(self.peer.map(|addr| addr.ip()), cookie)
+    }
+
+    /// A `303 See Other` to `target`, resolved against the URL the client requested, for
+    /// post/redirect/get: `"edit"` from `/posts/7/` goes to `/posts/7/edit`, `"../"` from
+    /// `/posts/7` to `/`. Absolute paths and URLs are used as they are.
+    ///
+    /// ```
+    /// use async_tiny::{Header, Method, Request};
+    ///
+    /// let req = Request::fake(&Method::POST, "/posts/7/comments?page=2", b"text=hi");
+    /// let location = |r: async_tiny::Response| r.headers()["location"].to_str().unwrap().to_string();
+    /// assert_eq!(location(req.redirect("../7")), "/posts/7");
+    /// assert_eq!(location(req.redirect("?page=3")), "/posts/7/comments?page=3");
+    /// assert_eq!(req.redirect("/").status_code(), 303);
+    ///
+    /// let req = req
+    ///     .with_header(Header::new("Host", "example.com").unwrap())
+    ///     .with_header(Header::new("Referer", "https://example.com/posts?sort=new").unwrap());
+    /// assert_eq!(location(req.redirect_to_referer("/")), "/posts?sort=new");
+    /// ```
+    pub fn redirect(&self, target: &str) -> Response {
+        Response::see_other(&redirect::resolve(self.original_url(), target))
+    }
+
+    /// A `303 See Other` back to the page in `Referer`, or to `fallback` (resolved like
+    /// [`Request::redirect`]) when there's none or it's on another host, so the redirect
+    /// can't be pointed at another site.
+    pub fn redirect_to_referer(&self, fallback: &str) -> Response {
+        let back = match (self.header(REFERER.as_str()), self.header(HOST.as_str())) {
+            (Some(referer), Some(host)) => redirect::same_origin_referer(referer, host),
+            _ => None,
+        };
+        match back {
+            Some(path) => Response::see_other(&path),
+            None => self.redirect(fallback),
+        }
+    }
+
+    /// An ID unique to the connection this request arrived on, for the lifetime of the process.
+    ///
+    /// Keep-alive requests from the same client share an ID. Fake requests use `0`.
+    pub fn connection_id(&self) -> u64 {
+        self.connection_id
+    }
+
+    /// Zero-based position of this request among those sent on its connection.
+    ///
+    /// A value above `0` means the client reused the connection (keep-alive or pipelining).
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// When the handler should give up on this request, if a budget is configured.
+    ///
+    /// Use it to bound downstream calls, e.g. with `tokio::time::timeout_at`.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// How long the request waited between being read and being handed out by
+    /// [`Server::next`], or so far if it hasn't been handed out yet.
+    ///
+    /// A large value with a fast handler means the loop can't keep up, not that the handler
+    /// is slow. See also [`ServerBuilder::slow_queue_warning`].
+    pub fn queued_duration(&self) -> Duration {
+        self.dequeued.unwrap_or_else(Instant::now) - self.enqueued
+    }
+
     pub fn respond(mut self, response: Response) -> Result<(), RespondError> {
         let tx = self
             .respond_tx
             .take()
             .ok_or(RespondError::AlreadyResponded)?;
-        tx.send(response).map_err(|_| RespondError::ChannelClosed)
+        let response = match &self.on_response {
+            Some(hook) => hook(&self, response),
+            None => response,
+        };
+        tx.send(response).map_err(|_| RespondError::ClientGone)
+    }
+
+    /// Whether the client has disconnected, so a response could no longer be delivered.
+    ///
+    /// The connection is cleaned up as soon as the client goes; this only tells the handler
+    /// that whatever it's still doing for the request is wasted.
+    pub fn is_client_gone(&self) -> bool {
+        self.respond_tx.as_ref().is_some_and(|tx| tx.is_closed())
+    }
+
+    /// Wait until the client disconnects, e.g. to abandon slow work in a `select!`:
+    ///
+    /// ```no_run
+    /// # async fn expensive_report() -> String { String::new() }
+    /// # async fn handle(mut request: async_tiny::Request) {
+    /// use async_tiny::Response;
+    ///
+    /// tokio::select! {
+    ///     report = expensive_report() => {
+    ///         let _ = request.respond(Response::from_string(report));
+    ///     }
+    ///     _ = request.client_gone() => {}
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// Never resolves for a request that has already been answered, or a fake one.
+    pub async fn client_gone(&mut self) {
+        match &mut self.respond_tx {
+            Some(tx) => tx.closed().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Takes the request's [upgrade] to another protocol, if the client asked
+    /// for one. `None` for other requests, fake ones, and once taken.
+    pub fn on_upgrade(&mut self) -> Option<upgrade::OnUpgrade> {
+        self.upgrade.take().map(upgrade::OnUpgrade)
+    }
+
+    /// A copy of this request that takes over answering it, for [`Server::serve`], which
+    /// hands the original to the handler. Headers are copied only when a response hook needs
+    /// to see them.
+    fn split_reply(&mut self) -> Request {
+        let hook = self.on_response.is_some();
+        Request {
+            method: self.method.clone(),
+            version: self.version,
+            headers: if hook {
+                self.headers.clone()
+            } else {
+                HeaderMap::new()
+            },
+            url: if hook {
+                self.url.clone()
+            } else {
+                String::new()
+            },
+            target_form: self.target_form,
+            body: self.body.clone(),
+            body_digest: self.body_digest.clone(),
+            peer: self.peer,
+            connection_id: self.connection_id,
+            sequence: self.sequence,
+            deadline: self.deadline,
+            enqueued: self.enqueued,
+            dequeued: self.dequeued,
+            respond_tx: self.respond_tx.take(),
+            on_response: self.on_response.clone(),
+            original_url: self.original_url.clone(),
+            upgrade: None,
+        }
     }
 
     /// Creates a fake Request for testing purposes.
@@ -179,34 +1312,70 @@ impl Request {
     pub fn fake(method: &Method, url: &str, body: &[u8]) -> Self {
         Request {
             method: method.clone(),
+            version: Version::HTTP_11,
             headers: HeaderMap::new(),
             url: url.to_string(),
+            target_form: url
+                .parse::<Uri>()
+                .map_or(TargetForm::Origin, |uri| TargetForm::of(&uri)),
             body: Bytes::copy_from_slice(body),
+            body_digest: None,
+            peer: None,
+            connection_id: 0,
+            sequence: 0,
+            deadline: None,
+            enqueued: Instant::now(),
+            dequeued: None,
             respond_tx: None,
+            on_response: None,
+            original_url: None,
+            upgrade: None,
         }
     }
-}
 
-impl Drop for Request {
-    fn drop(&mut self) {
-        if let Some(tx) = self.respond_tx.take() {
-            let _ = tx.send(Response::from_status_and_string(500, "No response"));
-        }
+    /// Add a header to a [`Request::fake`] request.
+    pub fn with_header(mut self, header: Header) -> Self {
+        self.headers.append(header.0, header.1);
+        self
+    }
+
+    /// Set the HTTP version of a [`Request::fake`] request, `HTTP/1.1` by default.
+    pub fn with_version(mut self, version: Version) -> Self {
+        self.version = version;
+        self
     }
 }
 
 #[derive(Debug)]
 pub enum RespondError {
     AlreadyResponded,
+    #[deprecated(note = "a closed connection is reported as `ClientGone`")]
     ChannelClosed,
+    /// The client disconnected before the response was ready: the handler was too late,
+    /// rather than something having gone wrong on the server.
+    ClientGone,
 }
 
+impl std::fmt::Display for RespondError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RespondError::AlreadyResponded => write!(f, "request was already responded to"),
+            #[allow(deprecated)]
+            RespondError::ChannelClosed => write!(f, "connection closed before the response"),
+            RespondError::ClientGone => write!(f, "client disconnected before the response"),
+        }
+    }
+}
+
+impl std::error::Error for RespondError {}
+
 /// A tiny response wrapper (status, headers, body).
 #[derive(Clone)]
 pub struct Response {
     status: StatusCode,
     headers: HeaderMap,
     body: Bytes,
+    reason: Option<ReasonPhrase>,
 }
 
 impl Response {
@@ -215,6 +1384,7 @@ impl Response {
             status: StatusCode::OK,
             headers: HeaderMap::new(),
             body: data.into(),
+            reason: None,
         }
     }
 
@@ -222,12 +1392,54 @@ impl Response {
         Self::from_data(Bytes::from(s.into()))
     }
 
+    /// A response sending `data` untouched, as `content_type`. Panics if `content_type`
+    /// isn't valid in a header.
+    pub fn from_data_with_type(data: impl Into<Bytes>, content_type: &str) -> Self {
+        Self::from_data(data).with_content_type(content_type)
+    }
+
+    /// An `application/msgpack` response of already-encoded `data`.
+    pub fn from_msgpack(data: impl Into<Bytes>) -> Self {
+        Self::from_data_with_type(data, "application/msgpack")
+    }
+
+    /// An `application/cbor` response of `value`; see [`cbor`].
+    #[cfg(feature = "cbor")]
+    pub fn cbor<T: serde::Serialize + ?Sized>(value: &T) -> Result<Self, cbor::Error> {
+        Ok(Self::from_data_with_type(
+            cbor::to_vec(value)?,
+            "application/cbor",
+        ))
+    }
+
+    /// An `application/msgpack` response of `value`; see [`msgpack`].
+    #[cfg(feature = "msgpack")]
+    pub fn msgpack<T: serde::Serialize + ?Sized>(value: &T) -> Result<Self, msgpack::Error> {
+        Ok(Self::from_msgpack(msgpack::to_vec(value)?))
+    }
+
+    /// An `application/grpc-web` response. `data` goes out as-is, so it must already be
+    /// framed, with the trailers frame last.
+    pub fn from_grpc_web(data: impl Into<Bytes>) -> Self {
+        Self::from_data_with_type(data, "application/grpc-web")
+    }
+
+    /// A `text/plain` response encoded in `charset` (UTF-8, US-ASCII, ISO-8859-1 or Windows-1252).
+    pub fn from_text_with_charset(text: &str, charset: &str) -> Result<Self, TextError> {
+        let charset = Charset::from_label(charset)
+            .ok_or_else(|| TextError::UnsupportedCharset(charset.to_string()))?;
+        let body = charset.encode(text)?;
+        Ok(Self::from_data(body)
+            .with_content_type(&format!("text/plain; charset={}", charset.label())))
+    }
+
     pub fn from_status_and_string(code: u16, s: impl Into<String>) -> Self {
         let status = StatusCode::from_u16(code).unwrap_or(StatusCode::OK);
         Self {
             status,
             headers: HeaderMap::new(),
             body: Bytes::from(s.into()),
+            reason: None,
         }
     }
 
@@ -237,41 +1449,176 @@ impl Response {
             status,
             headers: HeaderMap::new(),
             body: Bytes::new(),
+            reason: None,
         }
     }
 
+    /// A redirect with `status` (e.g. `301`, `302`, `307` or `308`) to `location`.
+    ///
+    /// Relative locations are resolved by the client against the URL it requested; use
+    /// [`Request::redirect`] to resolve them here instead.
+    pub fn redirect(status: u16, location: &str) -> Self {
+        Self::empty(status).with_header(Header(LOCATION, redirect::location(location)))
+    }
+
+    /// A `303 See Other` to `location`, which has the client follow up with a `GET`.
+    pub fn see_other(location: &str) -> Self {
+        Self::redirect(303, location)
+    }
+
     pub fn with_status_code(mut self, code: u16) -> Self {
         self.status = StatusCode::from_u16(code).unwrap_or(StatusCode::OK);
         self
     }
 
+    /// Send `reason` instead of the standard reason phrase in the HTTP/1.1 status line.
+    ///
+    /// Phrases containing characters not allowed in a status line are ignored.
+    pub fn with_reason(mut self, reason: &str) -> Self {
+        if let Ok(reason) = ReasonPhrase::try_from(reason.as_bytes()) {
+            self.reason = Some(reason);
+        }
+        self
+    }
+
     pub fn with_header(mut self, header: Header) -> Self {
         self.headers.insert(header.0, header.1);
         self
     }
 
+    /// Adds `headers` to the `Vary` header, keeping any names already listed.
+    pub fn with_vary(mut self, headers: &[&str]) -> Self {
+        for name in headers {
+            append_vary(&mut self.headers, name);
+        }
+        self
+    }
+
     pub fn with_content_type(self, value: &str) -> Self {
         let header =
             Header::from_str(&format!("Content-Type: {}", value)).expect("valid content type");
         self.with_header(header)
     }
 
+    /// Close the connection after this response is written, instead of keeping it alive.
+    pub fn close_connection(mut self) -> Self {
+        self.headers
+            .insert(CONNECTION, HeaderValue::from_static("close"));
+        self
+    }
+
+    /// Renders `name` with `engine` into an HTML response.
+    #[cfg(feature = "template")]
+    pub fn render<E: TemplateEngine>(
+        engine: &E,
+        name: &str,
+        ctx: &E::Context,
+    ) -> Result<Self, E::Error> {
+        let html = engine.render(name, ctx)?;
+        Ok(Self::from_string(html).with_content_type("text/html; charset=utf-8"))
+    }
+
+    /// Narrows a `200` response to a `GET` request to the byte ranges in its `Range` header.
+    ///
+    /// A single range yields `206` with `Content-Range`, several ranges a
+    /// `multipart/byteranges` body, and unsatisfiable ranges `416`. With `If-Range`, the
+    /// range is only honored while the response's `ETag` (strongly) or `Last-Modified` still
+    /// matches; otherwise the full `200` goes out, so set those headers before calling this.
+    ///
+    /// ```
+    /// use async_tiny::{Header, Method, Request, Response};
+    ///
+    /// let file = || {
+    ///     Response::from_string("0123456789")
+    ///         .with_header(Header::new("ETag", "\"v2\"").unwrap())
+    ///         .with_header(Header::new("Last-Modified", "Sun, 06 Nov 1994 08:49:37 GMT").unwrap())
+    /// };
+    /// let resume = |if_range: &str| {
+    ///     let req = Request::fake(&Method::GET, "/file", b"")
+    ///         .with_header(Header::new("Range", "bytes=5-").unwrap());
+    ///     let req = match if_range {
+    ///         "" => req,
+    ///         value => req.with_header(Header::new("If-Range", value).unwrap()),
+    ///     };
+    ///     file().with_range(&req).status_code()
+    /// };
+    ///
+    /// assert_eq!(resume(""), 206);
+    /// assert_eq!(resume("\"v2\""), 206);
+    /// // Changed since, or only weakly comparable: send everything.
+    /// assert_eq!(resume("\"v1\""), 200);
+    /// assert_eq!(resume("W/\"v2\""), 200);
+    /// assert_eq!(resume("Sun, 06 Nov 1994 08:49:37 GMT"), 206);
+    /// assert_eq!(resume("Sun, 06 Nov 1994 08:49:36 GMT"), 200);
+    /// assert_eq!(resume("not a validator"), 200);
+    /// ```
+    pub fn with_range(self, req: &Request) -> Self {
+        range::apply(req, self)
+    }
+
     /// Returns the HTTP status code of the response.
     pub fn status_code(&self) -> u16 {
         self.status.as_u16()
     }
 
-    /// Returns the response body as a UTF-8 string.
+    /// Returns the response body as a UTF-8 string, with invalid bytes replaced. Binary
+    /// bodies are better read with [`body_bytes`](Self::body_bytes).
     pub fn body(&self) -> String {
         String::from_utf8_lossy(&self.body).to_string()
     }
 
+    /// The response body as raw bytes.
+    pub fn body_bytes(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// Replaces the body, keeping the status and headers.
+    pub fn with_body(mut self, body: impl Into<Bytes>) -> Self {
+        self.body = body.into();
+        self
+    }
+
     /// Returns a reference to the response headers.
     pub fn headers(&self) -> &HeaderMap {
         &self.headers
     }
 }
 
+/// A response body as sent on the wire.
+pub(crate) enum ResponseBody {
+    Full(Full<Bytes>),
+    Drip(tarpit::Drip),
+}
+
+impl Body for ResponseBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        match self.get_mut() {
+            ResponseBody::Full(full) => Pin::new(full).poll_frame(cx),
+            ResponseBody::Drip(drip) => Pin::new(drip).poll_frame(cx),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        match self {
+            ResponseBody::Full(full) => full.is_end_stream(),
+            ResponseBody::Drip(drip) => drip.is_end_stream(),
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        match self {
+            ResponseBody::Full(full) => full.size_hint(),
+            ResponseBody::Drip(drip) => drip.size_hint(),
+        }
+    }
+}
+
 /// A simple "Name: value" header wrapper (tiny_http style).
 pub struct Header(pub HeaderName, pub HeaderValue);
 
@@ -292,6 +1639,18 @@ pub enum HeaderParseError {
     InvalidValue,
 }
 
+impl std::fmt::Display for HeaderParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeaderParseError::InvalidFormat => write!(f, "header is not in \"Name: value\" form"),
+            HeaderParseError::InvalidName => write!(f, "invalid header name"),
+            HeaderParseError::InvalidValue => write!(f, "invalid header value"),
+        }
+    }
+}
+
+impl std::error::Error for HeaderParseError {}
+
 impl std::str::FromStr for Header {
     type Err = HeaderParseError;
 
@@ -307,32 +1666,350 @@ impl std::str::FromStr for Header {
     }
 }
 
-fn path_and_query(uri: &Uri) -> String {
-    match uri.path_and_query() {
-        Some(pq) => pq.as_str().to_string(),
-        None => uri.path().to_string(),
+/// Buffers a request body, enforcing the size limit across all chunks and reporting progress.
+///
+/// Fails with a ready `413` when the body is too large and `400` when it can't be read
+/// (e.g. broken chunked framing), closing the connection in both cases.
+async fn read_body(
+    mut body: HyperBody,
+    url: &str,
+    headers: &HeaderMap,
+    conn: &ConnInfo,
+    config: &Config,
+    limit: Option<usize>,
+) -> Result<(Bytes, Option<Vec<u8>>), Response> {
+    let too_large = || config.error_pages.response(413).close_connection();
+
+    let declared = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let limit = limit.map(|l| l as u64);
+    if let (Some(len), Some(limit)) = (declared, limit) {
+        if len > limit {
+            return Err(too_large());
+        }
+    }
+
+    // Most bodies arrive as one frame, which is kept as-is. Only a second frame allocates a
+    // buffer, sized up front from Content-Length so it doesn't grow frame by frame.
+    let mut first = Bytes::new();
+    let mut rest: Option<BytesMut> = None;
+    let mut received = 0usize;
+    let mut digest = config.body_digest.as_ref().map(|digest| digest());
+    while let Some(frame) = body.frame().await {
+        let frame = frame.map_err(|_| config.error_pages.response(400).close_connection())?;
+        let Ok(data) = frame.into_data() else {
+            continue;
+        };
+        if limit.is_some_and(|limit| (received + data.len()) as u64 > limit) {
+            return Err(too_large());
+        }
+        received += data.len();
+        if let Some(digest) = &mut digest {
+            digest.update(&data);
+        }
+
+        match &mut rest {
+            Some(buf) => buf.extend_from_slice(&data),
+            None if first.is_empty() => first = data,
+            None => {
+                let hint = declared.map_or(0, |len| len.min(MAX_BODY_PREALLOC) as usize);
+                let mut buf = BytesMut::with_capacity(hint.max(received));
+                buf.extend_from_slice(&first);
+                buf.extend_from_slice(&data);
+                rest = Some(buf);
+            }
+        }
+
+        if let Some(observer) = &config.upload_progress {
+            observer(&UploadProgress {
+                url,
+                headers,
+                connection_id: conn.id,
+                received: received as u64,
+                total: declared,
+            });
+        }
     }
+    let digest = digest.map(|digest| digest.finish());
+    Ok((rest.map_or(first, BytesMut::freeze), digest))
 }
 
-fn to_hyper_response(r: Response) -> HyperResponse<Full<Bytes>> {
-    let mut builder = HyperResponse::builder().status(r.status);
+/// The most a declared Content-Length reserves before the bytes actually arrive.
+const MAX_BODY_PREALLOC: u64 = 1 << 20;
+
+/// Adds `name` to the `Vary` header unless it (or `*`) is already listed.
+pub(crate) fn append_vary(headers: &mut HeaderMap, name: &str) {
+    let mut names: Vec<String> = headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .collect();
+
+    if names
+        .iter()
+        .any(|n| n == "*" || n.eq_ignore_ascii_case(name))
     {
-        let headers = builder.headers_mut().expect("headers mut");
-        for (name, value) in r.headers.iter() {
-            headers.append(name.clone(), value.clone());
-        }
+        return;
+    }
+    if name == "*" {
+        names.clear();
+    }
+    names.push(name.to_string());
+
+    if let Ok(value) = HeaderValue::from_str(&names.join(", ")) {
+        headers.insert(VARY, value);
     }
-    builder.body(Full::new(r.body)).expect("response build")
 }
 
-fn response_text(status: StatusCode, text: &str) -> HyperResponse<Full<Bytes>> {
-    let r = Response::from_status_and_string(status.as_u16(), text).with_header(Header(
-        HeaderName::from_static("content-type"),
-        HeaderValue::from_static("text/plain; charset=utf-8"),
-    ));
-    to_hyper_response(r)
+/// Whether a body follows the head. Hyper can't reuse a connection with an unread body on it,
+/// so responses sent without reading one should close.
+fn has_body(headers: &HeaderMap) -> bool {
+    headers.contains_key(TRANSFER_ENCODING)
+        || headers
+            .get(CONTENT_LENGTH)
+            .is_some_and(|len| len.as_bytes() != b"0")
 }
 
-fn into_io_error<E: std::fmt::Display>(e: E) -> std::io::Error {
-    std::io::Error::new(std::io::ErrorKind::Other, format!("{}", e))
+/// `resp` for a request turned away before its body was read: closing the connection if a
+/// body follows, since it can't be reused with one left unread.
+fn reject(resp: Response, parts: &http::request::Parts) -> Response {
+    if has_body(&parts.headers) {
+        resp.close_connection()
+    } else {
+        resp
+    }
+}
+
+fn to_hyper_response(r: Response) -> HyperResponse<ResponseBody> {
+    // Move the header map over rather than copying it entry by entry.
+    let mut response = HyperResponse::new(ResponseBody::Full(Full::new(r.body)));
+    *response.status_mut() = r.status;
+    *response.headers_mut() = r.headers;
+    if let Some(reason) = r.reason {
+        response.extensions_mut().insert(reason);
+    }
+    response
+}
+
+pub(crate) fn into_io_error<E: std::fmt::Display>(e: E) -> std::io::Error {
+    std::io::Error::other(format!("{}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header_policy::HeaderPolicy;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    /// A server answering every request with its HTTP version, and a way to connect to it.
+    fn version_echo() -> mpsc::Sender<DuplexStream> {
+        let (streams, source) = mpsc::channel(1);
+        let mut server = Server::builder().silent(true).serve_streams(source);
+        tokio::spawn(async move {
+            while let Some(request) = server.next().await {
+                let version = format!("{:?}", request.version());
+                let _ = request.respond(Response::from_string(version));
+            }
+        });
+        streams
+    }
+
+    async fn connect(streams: &mpsc::Sender<DuplexStream>) -> DuplexStream {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        streams.send(server).await.unwrap();
+        client
+    }
+
+    /// Reads until `needle` has arrived, returning everything read so far.
+    async fn read_until(client: &mut DuplexStream, needle: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        while !buf.windows(needle.len()).any(|w| w == needle) {
+            let mut chunk = [0; 1024];
+            let read = tokio::time::timeout(Duration::from_secs(5), client.read(&mut chunk));
+            let n = read.await.expect("no response").unwrap();
+            assert!(n > 0, "closed early: {:?}", String::from_utf8_lossy(&buf));
+            buf.extend_from_slice(&chunk[..n]);
+        }
+        buf
+    }
+
+    #[tokio::test]
+    async fn refused_method_with_body_closes() {
+        let (streams, source) = mpsc::channel(1);
+        let _server = Server::builder()
+            .silent(true)
+            .method_policy(MethodPolicy::new().deny(Method::PUT))
+            .serve_streams(source);
+        let mut client = connect(&streams).await;
+        client
+            .write_all(b"PUT / HTTP/1.1\r\nHost: test\r\nContent-Length: 5\r\n\r\nhello")
+            .await
+            .unwrap();
+        let mut response = Vec::new();
+        let read = client.read_to_end(&mut response);
+        tokio::time::timeout(Duration::from_secs(5), read)
+            .await
+            .expect("connection left open")
+            .unwrap();
+        let response = String::from_utf8(response).unwrap().to_ascii_lowercase();
+        assert!(response.starts_with("http/1.1 405"), "{}", response);
+        assert!(
+            response.contains("\r\nconnection: close\r\n"),
+            "{}",
+            response
+        );
+    }
+
+    #[tokio::test]
+    async fn default_headers_survive_a_later_policy() {
+        let (streams, source) = mpsc::channel(1);
+        let mut server = Server::builder()
+            .silent(true)
+            .default_header("cache-control", "no-store")
+            .default_header("x-frame-options", "DENY")
+            .response_headers(
+                HeaderPolicy::new()
+                    .set("server", "edge")
+                    .strip("x-frame-options"),
+            )
+            .serve_streams(source);
+        tokio::spawn(async move {
+            while let Some(request) = server.next().await {
+                let cached = request.url() == "/cached";
+                let mut response = Response::from_string("ok");
+                if cached {
+                    response =
+                        response.with_header(Header::new("Cache-Control", "max-age=60").unwrap());
+                }
+                let _ = request.respond(response);
+            }
+        });
+        let mut client = connect(&streams).await;
+        for (url, cache_control) in [("/", "no-store"), ("/cached", "max-age=60")] {
+            let head = format!("GET {} HTTP/1.1\r\nHost: test\r\n\r\n", url);
+            client.write_all(head.as_bytes()).await.unwrap();
+            let response = read_until(&mut client, b"\r\n\r\nok").await;
+            let response = String::from_utf8(response).unwrap().to_ascii_lowercase();
+            let expected = format!("\r\ncache-control: {}\r\n", cache_control);
+            assert!(response.contains(&expected), "{}", response);
+            assert!(response.contains("\r\nserver: edge\r\n"), "{}", response);
+            assert!(!response.contains("x-frame-options"), "{}", response);
+        }
+    }
+
+    #[tokio::test]
+    async fn acceptor_threads_serve_their_connections() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let threads = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = threads.clone();
+        let mut server = Server::builder()
+            .silent(true)
+            .acceptors(2)
+            .pin_acceptors([0])
+            .on_head(move |_| {
+                let name = std::thread::current().name().map(str::to_string);
+                seen.lock().unwrap().push(name);
+                Decision::Allow
+            })
+            .http(("127.0.0.1", port))
+            .await
+            .unwrap();
+        let handler = tokio::spawn(async move {
+            while let Some(request) = server.next().await {
+                let _ = request.respond(Response::from_string("ok"));
+            }
+        });
+
+        for _ in 0..4 {
+            let mut client = tokio::net::TcpStream::connect(("127.0.0.1", port))
+                .await
+                .unwrap();
+            client
+                .write_all(b"GET / HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n")
+                .await
+                .unwrap();
+            let mut response = Vec::new();
+            let read = client.read_to_end(&mut response);
+            tokio::time::timeout(Duration::from_secs(5), read)
+                .await
+                .expect("no response")
+                .unwrap();
+            assert!(response.starts_with(b"HTTP/1.1 200"));
+        }
+        let threads = threads.lock().unwrap().clone();
+        assert_eq!(threads.len(), 4);
+        for name in &threads {
+            let name = name.as_deref().unwrap_or_default();
+            assert!(name.starts_with("async_tiny-acceptor-"), "{}", name);
+        }
+
+        // Dropping the server stops the threads, closing their listeners.
+        handler.abort();
+        let closed = async {
+            while tokio::net::TcpStream::connect(("127.0.0.1", port))
+                .await
+                .is_ok()
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), closed)
+            .await
+            .expect("listeners left open");
+    }
+
+    #[tokio::test]
+    async fn http_10_response_is_not_chunked_and_closes() {
+        let streams = version_echo();
+        let mut client = connect(&streams).await;
+        client
+            .write_all(b"POST /upload HTTP/1.0\r\nContent-Length: 5\r\n\r\nhello")
+            .await
+            .unwrap();
+        let mut response = Vec::new();
+        let read = client.read_to_end(&mut response);
+        tokio::time::timeout(Duration::from_secs(5), read)
+            .await
+            .expect("connection left open")
+            .unwrap();
+        let response = String::from_utf8(response).unwrap().to_ascii_lowercase();
+        assert!(response.starts_with("http/1.0 200"), "{}", response);
+        assert!(
+            response.contains("\r\ncontent-length: 8\r\n"),
+            "{}",
+            response
+        );
+        assert!(!response.contains("transfer-encoding"), "{}", response);
+        assert!(!response.contains("keep-alive"), "{}", response);
+        assert!(response.ends_with("\r\n\r\nhttp/1.0"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn http_10_keep_alive_is_honoured() {
+        let streams = version_echo();
+        let mut client = connect(&streams).await;
+        for _ in 0..2 {
+            client
+                .write_all(b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n")
+                .await
+                .unwrap();
+            let response = read_until(&mut client, b"\r\n\r\nHTTP/1.0").await;
+            let response = String::from_utf8(response).unwrap().to_ascii_lowercase();
+            assert!(
+                response.contains("\r\nconnection: keep-alive\r\n"),
+                "{}",
+                response
+            );
+            assert!(!response.contains("transfer-encoding"), "{}", response);
+        }
+    }
 }
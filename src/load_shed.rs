@@ -0,0 +1,222 @@
+//! Refusing some requests with `503` while the process is saturated, enabled via
+//! [`ServerBuilder::load_shed`].
+//!
+//! A background task measures event-loop lag: how late a timer that should fire every
+//! [`interval`](LoadShedder::interval) actually fires. Once the lag passes the start of the
+//! [`lag`](LoadShedder::lag) range, requests are refused at random, more of them the closer
+//! the lag gets to the end of the range. Each [`Priority`] lane is refused in proportion to
+//! its [`weight`](LoadShedder::weight), so by default `High` requests are never shed, `Normal`
+//! ones at half the rate of `Low` ones.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use async_tiny::load_shed::LoadShedder;
+//! use async_tiny::{Priority, Server};
+//!
+//! # fn cpu_usage() -> f64 { 0.0 }
+//! # async fn run() -> std::io::Result<()> {
+//! let shedder = LoadShedder::new()
+//!     .lag(Duration::from_millis(10), Duration::from_millis(100))
+//!     .signal(cpu_usage) // 0.0 idle to 1.0 saturated
+//!     .weight(Priority::Normal, 0.25);
+//! let mut server = Server::builder()
+//!     .priority_prefix("/healthz", Priority::High)
+//!     .priority_prefix("/reports/", Priority::Low)
+//!     .load_shed(shedder.clone())
+//!     .http("0.0.0.0:8080")
+//!     .await?;
+//! // Later, e.g. from a metrics endpoint:
+//! println!("overload {:.2}, shed {}", shedder.overload(), shedder.shed());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Shedding happens before the body is read, and refusals carry `Retry-After: 1`.
+//!
+//! [`ServerBuilder::load_shed`]: crate::ServerBuilder::load_shed
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::Priority;
+
+type Signal = dyn Fn() -> f64 + Send + Sync;
+
+/// When to start refusing requests, and which ones.
+#[derive(Clone)]
+pub struct LoadShedder {
+    interval: Duration,
+    lag_start: Duration,
+    lag_full: Duration,
+    signal: Option<Arc<Signal>>,
+    weights: [f64; 3],
+    state: Arc<State>,
+}
+
+/// What the monitor task measured, shared by clones.
+#[derive(Default)]
+struct State {
+    lag_micros: AtomicU64,
+    /// The last [`LoadShedder::signal`] reading, as `f64` bits.
+    signal: AtomicU64,
+    shed: AtomicU64,
+    rolls: AtomicU64,
+}
+
+impl LoadShedder {
+    /// Sample lag every 10ms and shed between 20ms and 200ms of it.
+    pub fn new() -> Self {
+        Self {
+            interval: Duration::from_millis(10),
+            lag_start: Duration::from_millis(20),
+            lag_full: Duration::from_millis(200),
+            signal: None,
+            weights: [0.0, 0.5, 1.0],
+            state: Arc::default(),
+        }
+    }
+
+    /// How often to measure lag, and read the [`signal`](Self::signal).
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval.max(Duration::from_millis(1));
+        self
+    }
+
+    /// Start shedding when lag reaches `start`, and shed at the full weight from `full` on.
+    pub fn lag(mut self, start: Duration, full: Duration) -> Self {
+        self.lag_start = start;
+        self.lag_full = full.max(start);
+        self
+    }
+
+    /// Also shed on `signal`, read every [`interval`](Self::interval): how saturated the
+    /// process is from `0.0` to `1.0` by some other measure, such as CPU use. The higher of
+    /// it and the lag decides.
+    pub fn signal<F>(mut self, signal: F) -> Self
+    where
+        F: Fn() -> f64 + Send + Sync + 'static,
+    {
+        self.signal = Some(Arc::new(signal));
+        self
+    }
+
+    /// Shed requests in `priority`'s lane at `weight` (`0.0` to `1.0`) times the overload.
+    /// Defaults: `High` 0, `Normal` 0.5, `Low` 1.
+    pub fn weight(mut self, priority: Priority, weight: f64) -> Self {
+        self.weights[priority as usize] = weight.clamp(0.0, 1.0);
+        self
+    }
+
+    /// How late the lag timer fired most recently, decaying gradually after a spike.
+    pub fn event_loop_lag(&self) -> Duration {
+        Duration::from_micros(self.state.lag_micros.load(Ordering::Relaxed))
+    }
+
+    /// How overloaded the process looks, from `0.0` to `1.0`.
+    pub fn overload(&self) -> f64 {
+        let lag = self.event_loop_lag();
+        let lag = if lag <= self.lag_start {
+            0.0
+        } else if self.lag_full == self.lag_start {
+            1.0
+        } else {
+            (lag - self.lag_start).as_secs_f64() / (self.lag_full - self.lag_start).as_secs_f64()
+        };
+        let signal = f64::from_bits(self.state.signal.load(Ordering::Relaxed));
+        lag.max(signal).clamp(0.0, 1.0)
+    }
+
+    /// How many requests have been shed.
+    pub fn shed(&self) -> u64 {
+        self.state.shed.load(Ordering::Relaxed)
+    }
+
+    /// Whether to refuse a request in `priority`'s lane, counting it if so.
+    pub(crate) fn should_shed(&self, priority: Priority) -> bool {
+        let chance = self.overload() * self.weights[priority as usize];
+        if chance <= 0.0 || self.roll() >= chance {
+            return false;
+        }
+        self.state.shed.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// A pseudo-random number in `[0, 1)` (splitmix64 over a shared counter).
+    fn roll(&self) -> f64 {
+        let mut x = self
+            .state
+            .rolls
+            .fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        x ^= x >> 31;
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Measures lag and reads the signal until the server is drained or dropped.
+    pub(crate) async fn monitor(self) {
+        loop {
+            let started = Instant::now();
+            tokio::time::sleep(self.interval).await;
+            let lag = started.elapsed().saturating_sub(self.interval).as_micros() as u64;
+            // Jump up at once so shedding starts promptly, and come down a quarter of the way
+            // per sample so it doesn't flap.
+            let previous = self.state.lag_micros.load(Ordering::Relaxed);
+            let lag = if lag >= previous {
+                lag
+            } else {
+                previous - (previous - lag) / 4
+            };
+            self.state.lag_micros.store(lag, Ordering::Relaxed);
+            if let Some(signal) = &self.signal {
+                let reading = signal();
+                let reading = if reading.is_nan() { 0.0 } else { reading };
+                self.state
+                    .signal
+                    .store(reading.to_bits(), Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl Default for LoadShedder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for LoadShedder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoadShedder")
+            .field("interval", &self.interval)
+            .field("lag", &(self.lag_start..self.lag_full))
+            .field("weights", &self.weights)
+            .field("overload", &self.overload())
+            .field("shed", &self.shed())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Server;
+
+    #[tokio::test]
+    async fn monitor_stops_with_the_server() {
+        let shedder = LoadShedder::new();
+        let server = Server::builder()
+            .silent(true)
+            .load_shed(shedder.clone())
+            .http("127.0.0.1:0")
+            .await
+            .unwrap();
+        assert!(Arc::strong_count(&shedder.state) > 1);
+        drop(server);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(Arc::strong_count(&shedder.state), 1);
+    }
+}
@@ -0,0 +1,127 @@
+//! Taking the site down for maintenance at runtime, via [`ServerHandle::set_maintenance`].
+//!
+//! While maintenance is on, every request outside the allowlist is answered with `503`, a
+//! `Retry-After` header and the maintenance page, before its body is read. Requests already
+//! queued are still delivered.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use async_tiny::maintenance::Maintenance;
+//! use async_tiny::{Response, Server};
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let server = Server::http("0.0.0.0:8080", false).await?;
+//! let handle = server.handle();
+//!
+//! handle.set_maintenance(
+//!     Maintenance::new()
+//!         .page(Response::from_string("<h1>Back soon</h1>").with_content_type("text/html"))
+//!         .retry_after(Duration::from_secs(600))
+//!         .allow_path("/health")
+//!         .allow_ip("10.0.0.5".parse().unwrap()),
+//! );
+//! // ... migrate ...
+//! handle.clear_maintenance();
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`ServerHandle::set_maintenance`]: crate::ServerHandle::set_maintenance
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use http::header::RETRY_AFTER;
+use http::{HeaderValue, StatusCode};
+
+use crate::pattern::Pattern;
+use crate::{ErrorPages, Response};
+
+/// The maintenance page, and who still gets through.
+#[derive(Clone, Default)]
+pub struct Maintenance {
+    page: Option<Response>,
+    retry_after: Option<Duration>,
+    paths: Vec<Pattern>,
+    ips: Vec<IpAddr>,
+}
+
+impl Maintenance {
+    /// The `503` error page for everyone, with no `Retry-After`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer with `page`'s headers and body instead of the `503` error page. Its status is
+    /// replaced with `503`.
+    pub fn page(mut self, page: Response) -> Self {
+        self.page = Some(page);
+        self
+    }
+
+    /// Tell clients to come back after `after` (sent in whole seconds).
+    pub fn retry_after(mut self, after: Duration) -> Self {
+        self.retry_after = Some(after);
+        self
+    }
+
+    /// Keep serving paths matching `pattern`, such as `/health` or `/admin/*`.
+    pub fn allow_path(mut self, pattern: &str) -> Self {
+        self.paths.push(Pattern::parse(pattern));
+        self
+    }
+
+    /// Keep serving clients at `ip`, e.g. the operators checking the work.
+    pub fn allow_ip(mut self, ip: IpAddr) -> Self {
+        self.ips.push(ip);
+        self
+    }
+
+    fn allows(&self, peer: Option<SocketAddr>, url: &str) -> bool {
+        let path = url.split('?').next().unwrap_or(url);
+        peer.is_some_and(|peer| self.ips.contains(&peer.ip()))
+            || self.paths.iter().any(|pattern| pattern.matches(path))
+    }
+
+    fn response(&self, error_pages: &ErrorPages) -> Response {
+        let mut resp = match &self.page {
+            Some(page) => page.clone(),
+            None => error_pages.response(503),
+        };
+        resp.status = StatusCode::SERVICE_UNAVAILABLE;
+        resp.reason = None;
+        if let Some(after) = self.retry_after {
+            let secs = after.as_secs() + u64::from(after.subsec_nanos() > 0);
+            resp.headers.insert(RETRY_AFTER, HeaderValue::from(secs));
+        }
+        resp
+    }
+}
+
+/// Whether maintenance is on, shared by the server's handles and its connections.
+#[derive(Clone, Default)]
+pub(crate) struct Switch(Arc<RwLock<Option<Maintenance>>>);
+
+impl Switch {
+    pub(crate) fn set(&self, maintenance: Option<Maintenance>) {
+        *self.0.write().unwrap() = maintenance;
+    }
+
+    pub(crate) fn is_on(&self) -> bool {
+        self.0.read().unwrap().is_some()
+    }
+
+    /// The maintenance response for this request, unless maintenance is off or it's allowed.
+    pub(crate) fn check(
+        &self,
+        peer: Option<SocketAddr>,
+        url: &str,
+        error_pages: &ErrorPages,
+    ) -> Option<Response> {
+        let maintenance = self.0.read().unwrap();
+        let maintenance = maintenance.as_ref()?;
+        (!maintenance.allows(peer, url)).then(|| maintenance.response(error_pages))
+    }
+}
@@ -0,0 +1,231 @@
+//! Turning errors returned by handlers into responses, in one place.
+//!
+//! A handler given to [`Server::serve`] may return `Result<Response, E>` for any error that
+//! converts into `Box<dyn Error>`: concrete error types, `Box<dyn Error>` itself, `anyhow::Error`
+//! or a `String`. Errors are handed to the server's [`MapError`], which is an [`ErrorMapper`]
+//! unless [`ServerBuilder::map_errors`] sets another.
+//!
+//! ```no_run
+//! use async_tiny::{HttpError, Request, Response, Server};
+//!
+//! async fn app(request: Request) -> Result<Response, Box<dyn std::error::Error>> {
+//!     let id: u64 = request
+//!         .url()
+//!         .trim_start_matches("/users/")
+//!         .parse()
+//!         .map_err(|_| HttpError::new(400, "user ids are numbers"))?;
+//!     let name = std::fs::read_to_string(format!("users/{}.txt", id))?; // NotFound → 404
+//!     Ok(Response::from_string(name))
+//! }
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! Server::http("127.0.0.1:8080", false).await?.serve(app).await;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Server::serve`]: crate::Server::serve
+//! [`ServerBuilder::map_errors`]: crate::ServerBuilder::map_errors
+
+use std::error::Error;
+use std::fmt::{self, Write as _};
+use std::io;
+
+use http::StatusCode;
+
+use crate::multipart::UploadError;
+use crate::{ErrorPages, Response};
+
+/// Converts handler errors into responses.
+///
+/// Implemented for closures taking the error, so a mapper can be written inline:
+///
+/// ```
+/// use async_tiny::{MapError, Response};
+///
+/// let mapper = |error: &(dyn std::error::Error + 'static)| {
+///     Response::from_status_and_string(500, format!("{{\"error\":\"{}\"}}", error))
+/// };
+/// let io = std::io::Error::other("disk full");
+/// assert_eq!(mapper.map_error(&io).body(), "{\"error\":\"disk full\"}");
+/// ```
+pub trait MapError: Send + Sync + 'static {
+    fn map_error(&self, error: &(dyn Error + 'static)) -> Response;
+}
+
+impl<F> MapError for F
+where
+    F: Fn(&(dyn Error + 'static)) -> Response + Send + Sync + 'static,
+{
+    fn map_error(&self, error: &(dyn Error + 'static)) -> Response {
+        self(error)
+    }
+}
+
+/// An error that says which status to answer with, and what to tell the client.
+#[derive(Debug)]
+pub struct HttpError {
+    status: StatusCode,
+    message: String,
+}
+
+impl HttpError {
+    /// Panics if `status` isn't between 100 and 999.
+    pub fn new(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::from_u16(status).expect("valid status code"),
+            message: message.into(),
+        }
+    }
+
+    pub fn status_code(&self) -> u16 {
+        self.status.as_u16()
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for HttpError {}
+
+/// The default [`MapError`]: picks a status from the error and answers with its error page.
+///
+/// The status comes from the first error in the [`source`](Error::source) chain that has
+/// one:
+///
+/// * An [`HttpError`] gives its status, with its message as a plain-text body instead of
+///   the page.
+/// * An [`UploadError`] gives its [`status`](UploadError::status) and JSON body.
+/// * An [`io::Error`] gives `404` for `NotFound`, `403` for `PermissionDenied` and `400` for
+///   `InvalidInput` and `InvalidData`.
+///
+/// Anything else is a `500`. With [`debug`](Self::debug) on, which it is by default when the
+/// `dev` feature is enabled, the body is the error and its sources instead, for reading in
+/// the browser while developing.
+///
+/// ```
+/// use async_tiny::map_error::ErrorMapper;
+/// use async_tiny::{ErrorPages, HttpError, MapError};
+///
+/// let mapper = ErrorMapper::new().error_pages(ErrorPages::plain()).debug(false);
+/// let missing = std::io::Error::from(std::io::ErrorKind::NotFound);
+/// assert_eq!(mapper.map_error(&missing).status_code(), 404);
+///
+/// let response = mapper.map_error(&HttpError::new(409, "name taken"));
+/// assert_eq!((response.status_code(), response.body().as_str()), (409, "name taken"));
+///
+/// let response = ErrorMapper::new().debug(true).map_error(&std::io::Error::other("disk full"));
+/// assert_eq!(response.status_code(), 500);
+/// assert!(response.body().contains("disk full"));
+/// ```
+#[derive(Clone)]
+pub struct ErrorMapper {
+    error_pages: ErrorPages,
+    debug: bool,
+}
+
+impl ErrorMapper {
+    pub fn new() -> Self {
+        Self {
+            error_pages: ErrorPages::default(),
+            debug: cfg!(feature = "dev"),
+        }
+    }
+
+    /// Answer with pages from `pages`. The server's own mapper uses the server's pages.
+    pub fn error_pages(mut self, pages: ErrorPages) -> Self {
+        self.error_pages = pages;
+        self
+    }
+
+    /// Show the error chain in the body instead of the error page.
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    fn status(error: &(dyn Error + 'static)) -> Option<u16> {
+        if let Some(e) = error.downcast_ref::<HttpError>() {
+            return Some(e.status_code());
+        }
+        if let Some(e) = error.downcast_ref::<UploadError>() {
+            return Some(e.status());
+        }
+        match error.downcast_ref::<io::Error>()?.kind() {
+            io::ErrorKind::NotFound => Some(404),
+            io::ErrorKind::PermissionDenied => Some(403),
+            io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => Some(400),
+            _ => None,
+        }
+    }
+}
+
+impl Default for ErrorMapper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MapError for ErrorMapper {
+    fn map_error(&self, error: &(dyn Error + 'static)) -> Response {
+        let mut chain = Some(error);
+        let mut found = None;
+        while let Some(e) = chain {
+            if let Some(status) = Self::status(e) {
+                found = Some((e, status));
+                break;
+            }
+            chain = e.source();
+        }
+        let status = found.map_or(500, |(_, status)| status);
+
+        if self.debug {
+            let mut body = format!("{}\n\n{}", status, error);
+            let mut source = error.source();
+            while let Some(e) = source {
+                let _ = write!(body, "\ncaused by: {}", e);
+                source = e.source();
+            }
+            return Response::from_status_and_string(status, body)
+                .with_content_type("text/plain; charset=utf-8");
+        }
+        if let Some((e, _)) = found {
+            if let Some(e) = e.downcast_ref::<HttpError>() {
+                return Response::from_status_and_string(status, e.message.clone())
+                    .with_content_type("text/plain; charset=utf-8");
+            }
+            if let Some(e) = e.downcast_ref::<UploadError>() {
+                return e.into_response();
+            }
+        }
+        self.error_pages.response(status)
+    }
+}
+
+/// What a [`Server::serve`](crate::Server::serve) handler may return: a [`Response`], or a
+/// `Result` whose error goes through the server's [`MapError`].
+pub trait HandlerOutput {
+    fn into_response(self, errors: &dyn MapError) -> Response;
+}
+
+impl HandlerOutput for Response {
+    fn into_response(self, _: &dyn MapError) -> Response {
+        self
+    }
+}
+
+impl<E> HandlerOutput for Result<Response, E>
+where
+    E: Into<Box<dyn Error>>,
+{
+    fn into_response(self, errors: &dyn MapError) -> Response {
+        self.unwrap_or_else(|e| errors.map_error(e.into().as_ref()))
+    }
+}
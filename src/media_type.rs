@@ -0,0 +1,254 @@
+//! Parsed `Content-Type` values (RFC 9110 §8.3.1).
+
+use std::fmt;
+
+/// A media type such as `multipart/form-data; boundary=xyz`, from
+/// [`Request::content_type`](crate::Request::content_type).
+///
+/// The type, subtype and parameter names are lowercased; parameter values are kept as sent,
+/// minus any quoting.
+///
+/// ```
+/// use async_tiny::MediaType;
+///
+/// let mt = MediaType::parse("Multipart/Form-Data; Boundary=\"----abc\\\"d\"; charset=UTF-8").unwrap();
+/// assert_eq!(mt.essence(), "multipart/form-data");
+/// assert!(mt.is("multipart/form-data"));
+/// assert_eq!(mt.boundary(), Some("----abc\"d"));
+/// assert_eq!(mt.charset(), Some("UTF-8"));
+/// assert!(MediaType::parse("no slash").is_none());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MediaType {
+    essence: String,
+    slash: usize,
+    params: Vec<(String, String)>,
+}
+
+impl MediaType {
+    /// Parses a `Content-Type` value; `None` if it has no `type/subtype`.
+    ///
+    /// Malformed parameters are skipped rather than failing the whole value.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (essence, rest) = match value.find(';') {
+            Some(i) => (&value[..i], &value[i..]),
+            None => (value, ""),
+        };
+        let essence = essence.trim().to_ascii_lowercase();
+        let slash = essence.find('/')?;
+        if slash == 0 || slash + 1 == essence.len() || essence.contains(char::is_whitespace) {
+            return None;
+        }
+
+        Some(Self {
+            essence,
+            slash,
+            params: parse_params(rest),
+        })
+    }
+
+    /// The top-level type, e.g. `text`.
+    pub fn type_(&self) -> &str {
+        &self.essence[..self.slash]
+    }
+
+    /// The subtype, e.g. `html`.
+    pub fn subtype(&self) -> &str {
+        &self.essence[self.slash + 1..]
+    }
+
+    /// `type/subtype` without parameters, e.g. `text/html`.
+    pub fn essence(&self) -> &str {
+        &self.essence
+    }
+
+    /// Whether this is `essence` (case-insensitively), ignoring parameters.
+    pub fn is(&self, essence: &str) -> bool {
+        self.essence.eq_ignore_ascii_case(essence)
+    }
+
+    /// The value of parameter `name`, matched case-insensitively.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// All parameters in the order sent.
+    pub fn params(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    pub fn charset(&self) -> Option<&str> {
+        self.param("charset")
+    }
+
+    /// The multipart boundary.
+    pub fn boundary(&self) -> Option<&str> {
+        self.param("boundary")
+    }
+}
+
+impl fmt::Display for MediaType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.essence)?;
+        for (name, value) in &self.params {
+            let token = !value.is_empty()
+                && value
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+            if token {
+                write!(f, "; {}={}", name, value)?;
+            } else {
+                write!(
+                    f,
+                    "; {}=\"{}\"",
+                    name,
+                    value.replace('\\', "\\\\").replace('"', "\\\"")
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses `; name=value` parameters as they follow a media type, or any header value built
+/// the same way such as `Content-Disposition`. Names are lowercased and values unquoted;
+/// malformed parameters are skipped.
+pub(crate) fn parse_params(mut rest: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    while let Some(after) = rest.strip_prefix(';') {
+        let after = after.trim_start();
+        let name_end = after.find(['=', ';']).unwrap_or(after.len());
+        let name = after[..name_end].trim().to_ascii_lowercase();
+        rest = &after[name_end..];
+        let Some(after_eq) = rest.strip_prefix('=') else {
+            continue;
+        };
+        let (value, remaining) = if let Some(quoted) = after_eq.strip_prefix('"') {
+            unquote(quoted)
+        } else {
+            let end = after_eq.find(';').unwrap_or(after_eq.len());
+            (after_eq[..end].trim().to_string(), &after_eq[end..])
+        };
+        rest = remaining.trim_start_matches(|c: char| c != ';');
+        if !name.is_empty() {
+            params.push((name, value));
+        }
+    }
+    params
+}
+
+/// Reads a quoted-string whose opening quote is already consumed, returning its contents
+/// and what follows the closing quote.
+fn unquote(s: &str) -> (String, &str) {
+    let mut out = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return (out, &s[i + 1..]),
+            '\\' => {
+                if let Some((_, escaped)) = chars.next() {
+                    out.push(escaped);
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    (out, "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(value: &str) -> Vec<(String, String)> {
+        MediaType::parse(value).unwrap().params.clone()
+    }
+
+    fn pair(name: &str, value: &str) -> (String, String) {
+        (name.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn essence() {
+        let mt = MediaType::parse("  Text/HTML  ;charset=utf-8").unwrap();
+        assert_eq!((mt.type_(), mt.subtype()), ("text", "html"));
+        assert_eq!(mt.essence(), "text/html");
+        assert!(mt.is("TEXT/html"));
+        assert!(MediaType::parse("application/vnd.api+json").is_some());
+    }
+
+    #[test]
+    fn invalid_essence() {
+        for value in [
+            "",
+            "text",
+            "text/",
+            "/html",
+            "text /html",
+            "text/ html",
+            "; a=b",
+        ] {
+            assert_eq!(MediaType::parse(value), None, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn parameter_values() {
+        assert_eq!(
+            params("a/b; Name=Value ; other = x"),
+            [pair("name", "Value"), pair("other", "x")]
+        );
+        assert_eq!(
+            params(r#"a/b; q="semi;colon"; e="back\\slash \"quote\"""#),
+            [pair("q", "semi;colon"), pair("e", r#"back\slash "quote""#)]
+        );
+        // An unterminated quoted-string runs to the end.
+        assert_eq!(params(r#"a/b; q="open; x=y"#), [pair("q", "open; x=y")]);
+        // Junk after a closing quote is dropped, up to the next parameter.
+        assert_eq!(
+            params(r#"a/b; q="v"junk; x=y"#),
+            [pair("q", "v"), pair("x", "y")]
+        );
+    }
+
+    #[test]
+    fn malformed_parameters_are_skipped() {
+        assert_eq!(params("a/b;; charset=utf-8"), [pair("charset", "utf-8")]);
+        assert_eq!(
+            params("a/b; flag; charset=utf-8"),
+            [pair("charset", "utf-8")]
+        );
+        assert_eq!(
+            params("a/b; =orphan; charset=utf-8"),
+            [pair("charset", "utf-8")]
+        );
+        assert_eq!(
+            params("a/b; empty=; x=y"),
+            [pair("empty", ""), pair("x", "y")]
+        );
+    }
+
+    #[test]
+    fn first_of_repeated_parameters_wins() {
+        let mt = MediaType::parse("multipart/form-data; boundary=a; Boundary=b").unwrap();
+        assert_eq!(mt.boundary(), Some("a"));
+        assert_eq!(mt.params().count(), 2);
+    }
+
+    #[test]
+    fn display_quotes_when_needed() {
+        for value in [
+            "text/plain; charset=utf-8",
+            r#"multipart/form-data; boundary="a b""#,
+            r#"a/b; q="semi;colon"; e="back\\slash \"quote\"""#,
+            r#"a/b; empty="""#,
+        ] {
+            let mt = MediaType::parse(value).unwrap();
+            assert_eq!(mt.to_string(), value);
+            assert_eq!(MediaType::parse(&mt.to_string()), Some(mt));
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use http::header::ALLOW;
+use http::{HeaderValue, Method};
+
+use crate::Response;
+
+const STANDARD: [Method; 9] = [
+    Method::GET,
+    Method::HEAD,
+    Method::POST,
+    Method::PUT,
+    Method::DELETE,
+    Method::CONNECT,
+    Method::OPTIONS,
+    Method::TRACE,
+    Method::PATCH,
+];
+
+/// Methods the server refuses with `405` before they reach the application.
+///
+/// Set with [`ServerBuilder::method_policy`](crate::ServerBuilder::method_policy).
+/// The same list answers `OPTIONS *`; without a policy that's every standard method.
+///
+/// ```
+/// use async_tiny::{Method, MethodPolicy};
+///
+/// // Refuse TRACE (the default), CONNECT and anything non-standard.
+/// let policy = MethodPolicy::new().deny(Method::CONNECT).deny_custom();
+/// assert!(!policy.allows(&Method::TRACE));
+/// ```
+#[derive(Clone, Debug)]
+pub struct MethodPolicy {
+    denied: Vec<Method>,
+    deny_custom: bool,
+}
+
+impl Default for MethodPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MethodPolicy {
+    /// Deny TRACE only.
+    pub fn new() -> Self {
+        Self {
+            denied: vec![Method::TRACE],
+            deny_custom: false,
+        }
+    }
+
+    /// Deny nothing; build up from here with [`MethodPolicy::deny`].
+    pub fn allow_all() -> Self {
+        Self {
+            denied: Vec::new(),
+            deny_custom: false,
+        }
+    }
+
+    pub fn deny(mut self, method: Method) -> Self {
+        if !self.denied.contains(&method) {
+            self.denied.push(method);
+        }
+        self
+    }
+
+    pub fn allow(mut self, method: Method) -> Self {
+        self.denied.retain(|m| *m != method);
+        self
+    }
+
+    /// Deny methods outside the nine defined by RFC 9110 and RFC 5789 (PATCH).
+    pub fn deny_custom(mut self) -> Self {
+        self.deny_custom = true;
+        self
+    }
+
+    pub fn allows(&self, method: &Method) -> bool {
+        if self.denied.contains(method) {
+            return false;
+        }
+        !self.deny_custom || STANDARD.contains(method)
+    }
+
+    /// The standard methods this policy lets through, in `Allow` header form.
+    pub(crate) fn allow_header(&self) -> HeaderValue {
+        let allowed: Vec<&str> = STANDARD
+            .iter()
+            .filter(|m| self.allows(m))
+            .map(Method::as_str)
+            .collect();
+        HeaderValue::from_str(&allowed.join(", ")).expect("valid allow header")
+    }
+
+    /// Adds the `Allow` header to a `405` response.
+    pub(crate) fn reject(&self, mut response: Response) -> Response {
+        response.headers.insert(ALLOW, self.allow_header());
+        response
+    }
+}
+
+/// The answer to `OPTIONS *`: what the server as a whole accepts, per RFC 9110 §9.3.7.
+pub(crate) fn server_options(policy: Option<&MethodPolicy>) -> Response {
+    let allow = match policy {
+        Some(policy) => policy.allow_header(),
+        None => MethodPolicy::allow_all().allow_header(),
+    };
+    let mut response = Response::empty(200);
+    response.headers.insert(ALLOW, allow);
+    response
+}
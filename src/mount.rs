@@ -0,0 +1,152 @@
+//! Composing handlers for [`Server::serve`](crate::Server::serve) under path prefixes.
+//!
+//! ```no_run
+//! use async_tiny::{Mount, Request, Response, Server};
+//!
+//! async fn api(request: Request) -> Response {
+//!     // Mounted under /api, so a request for /api/users arrives as /users.
+//!     match request.url() {
+//!         "/users" => Response::from_string("[]"),
+//!         _ => Response::from_status_and_string(404, "no such endpoint"),
+//!     }
+//! }
+//!
+//! async fn site(request: Request) -> Response {
+//!     Response::from_string(format!("page {}", request.url()))
+//! }
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let app = Mount::new("/api", api)
+//!     .mount("/admin", Mount::new("/reports", site).into_handler())
+//!     .fallback(site);
+//! Server::http("127.0.0.1:8080", false).await?.serve(app.into_handler()).await;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A prefix matches whole path segments: `/api` matches `/api` and `/api/users` but not
+//! `/apis`. The longest matching prefix wins. The handler sees [`Request::url`] with the
+//! prefix removed (`/api` itself becomes `/`), and [`Request::original_url`] still holds what
+//! the client sent, however deeply mounts are nested.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::{Request, Response};
+
+/// The future a mounted handler returns, boxed so handlers of different types can be mixed.
+pub type BoxResponse = Pin<Box<dyn Future<Output = Response> + Send>>;
+
+type Handler = Arc<dyn Fn(Request) -> BoxResponse + Send + Sync>;
+
+/// Handlers by path prefix, with a fallback for everything else.
+#[derive(Clone)]
+pub struct Mount {
+    mounts: Vec<(String, Handler)>,
+    fallback: Option<Handler>,
+}
+
+impl Mount {
+    /// Send requests under `prefix` to `handler`.
+    pub fn new<F, Fut>(prefix: &str, handler: F) -> Self
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Response> + Send + 'static,
+    {
+        Self {
+            mounts: Vec::new(),
+            fallback: None,
+        }
+        .mount(prefix, handler)
+    }
+
+    /// Also send requests under `prefix` to `handler`.
+    pub fn mount<F, Fut>(mut self, prefix: &str, handler: F) -> Self
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Response> + Send + 'static,
+    {
+        let prefix = format!("/{}", prefix.trim_matches('/'));
+        self.mounts.push((prefix, boxed(handler)));
+        // Longest first, so the first match is the most specific.
+        self.mounts
+            .sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        self
+    }
+
+    /// Send requests no prefix matches to `handler`, with their URL unchanged. Without one,
+    /// they're answered with `404`.
+    pub fn fallback<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Response> + Send + 'static,
+    {
+        self.fallback = Some(boxed(handler));
+        self
+    }
+
+    /// Route `request` to its handler.
+    ///
+    /// ```
+    /// use async_tiny::{Method, Mount, Request, Response};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let echo = |request: Request| async move {
+    ///     Response::from_string(format!("{} {}", request.url(), request.original_url()))
+    /// };
+    /// let app = Mount::new("/api", echo).mount("/api/v2", echo);
+    /// let get = |url| app.handle(Request::fake(&Method::GET, url, b""));
+    ///
+    /// assert_eq!(get("/api/users?page=2").await.body(), "/users?page=2 /api/users?page=2");
+    /// assert_eq!(get("/api/v2/users").await.body(), "/users /api/v2/users");
+    /// assert_eq!(get("/api").await.body(), "/ /api");
+    /// assert_eq!(get("/apis").await.status_code(), 404);
+    /// # }
+    /// ```
+    pub fn handle(&self, mut request: Request) -> BoxResponse {
+        for (prefix, handler) in &self.mounts {
+            if let Some(rest) = strip(&request.url, prefix) {
+                let original = std::mem::replace(&mut request.url, rest);
+                request.original_url.get_or_insert(original);
+                return handler(request);
+            }
+        }
+        match &self.fallback {
+            Some(handler) => handler(request),
+            None => Box::pin(std::future::ready(Response::from_status_and_string(
+                404,
+                "Not Found",
+            ))),
+        }
+    }
+
+    /// This mount as a handler, for [`Server::serve`](crate::Server::serve) or for mounting
+    /// inside another `Mount`.
+    pub fn into_handler(self) -> impl Fn(Request) -> BoxResponse + Send + Sync + 'static {
+        move |request| self.handle(request)
+    }
+}
+
+fn boxed<F, Fut>(handler: F) -> Handler
+where
+    F: Fn(Request) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Response> + Send + 'static,
+{
+    Arc::new(move |request| Box::pin(handler(request)))
+}
+
+/// `url` with `prefix` removed, if `prefix` covers whole segments of its path.
+pub(crate) fn strip(url: &str, prefix: &str) -> Option<String> {
+    if prefix == "/" {
+        return Some(url.to_string());
+    }
+    let rest = url.strip_prefix(prefix)?;
+    match rest.as_bytes().first() {
+        None => Some("/".to_string()),
+        Some(b'/') => Some(rest.to_string()),
+        Some(b'?') => Some(format!("/{}", rest)),
+        Some(_) => None,
+    }
+}
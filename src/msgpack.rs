@@ -0,0 +1,66 @@
+//! MessagePack bodies, enabled with the `msgpack` feature: [`Request::msgpack`] and
+//! [`Response::msgpack`].
+//!
+//! Encoding and decoding are [`rmp_serde`]'s, with structs written as maps keyed by field
+//! name so other languages' decoders see the names. Integers take the smallest encoding that
+//! fits. Decoding also accepts structs written as arrays, as compact encoders produce.
+//!
+//! ```
+//! use async_tiny::{Method, Request, Response};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize, Debug, PartialEq)]
+//! struct Reading {
+//!     sensor: String,
+//!     celsius: f32,
+//! }
+//!
+//! let reading = Reading { sensor: "attic".to_string(), celsius: 21.5 };
+//! let response = Response::msgpack(&reading).unwrap();
+//! assert_eq!(response.headers()["content-type"], "application/msgpack");
+//! assert_eq!(
+//!     response.body_bytes().as_ref(),
+//!     b"\x82\xa6sensor\xa5attic\xa7celsius\xca\x41\xac\x00\x00",
+//! );
+//!
+//! let request = Request::fake(&Method::POST, "/readings", response.body_bytes());
+//! assert_eq!(request.msgpack::<Reading>().unwrap(), reading);
+//! ```
+//!
+//! [`Request::msgpack`]: crate::Request::msgpack
+//! [`Response::msgpack`]: crate::Response::msgpack
+
+use std::fmt;
+use std::io::Cursor;
+
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+
+/// Why a value couldn't be encoded or decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "msgpack: {}", self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// `value` encoded as MessagePack.
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    rmp_serde::to_vec_named(value).map_err(|e| Error(e.to_string()))
+}
+
+/// Decodes one value from `bytes`, which must hold nothing else.
+pub fn from_slice<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> Result<T, Error> {
+    // rmp-serde doesn't say where a value borrowed from a slice ends, so skip over it once
+    // to find out.
+    let mut skip = rmp_serde::Deserializer::new(Cursor::new(bytes));
+    IgnoredAny::deserialize(&mut skip).map_err(|e| Error(e.to_string()))?;
+    if skip.position() != bytes.len() as u64 {
+        return Err(Error("trailing bytes after the value".to_string()));
+    }
+    rmp_serde::from_slice(bytes).map_err(|e| Error(e.to_string()))
+}
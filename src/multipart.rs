@@ -0,0 +1,247 @@
+//! Multipart response bodies (RFC 2046 §5.1), e.g. `multipart/mixed` for batch APIs.
+//!
+//! ```
+//! use async_tiny::multipart::{Multipart, Part};
+//! use async_tiny::Response;
+//!
+//! let response: Response = Multipart::mixed()
+//!     .boundary("batch_1")
+//!     .part(Part::new(r#"{"id":1}"#).with_content_type("application/json"))
+//!     .part(Part::new("not found").with_content_type("text/plain"))
+//!     .into();
+//!
+//! let content_type = response.headers().get("content-type").unwrap();
+//! assert_eq!(content_type, "multipart/mixed; boundary=batch_1");
+//! assert!(response.body().starts_with("--batch_1\r\ncontent-type: application/json\r\n\r\n"));
+//! assert!(response.body().ends_with("\r\n--batch_1--\r\n"));
+//! ```
+//!
+//! Bodies are buffered like every other response, so a `multipart/x-mixed-replace` body
+//! built here is sent whole: fine for a short burst of frames, not for a live camera feed.
+//!
+//! For the other direction, HTML form uploads, see [`UploadPolicy`].
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use http::header::CONTENT_TYPE;
+use http::{HeaderMap, HeaderValue};
+
+use crate::{Header, Response};
+
+mod form;
+pub use self::form::{Field, FileRule, Form, UploadError, UploadPolicy};
+
+/// A multipart body under construction.
+pub struct Multipart {
+    subtype: String,
+    boundary: String,
+    parts: Vec<Part>,
+}
+
+/// One part of a [`Multipart`] body: its own headers and bytes.
+pub struct Part {
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl Multipart {
+    /// A `multipart/<subtype>` body, e.g. `related` or `x-mixed-replace`, with a generated
+    /// boundary.
+    pub fn new(subtype: &str) -> Self {
+        Self {
+            subtype: subtype.to_ascii_lowercase(),
+            boundary: boundary(),
+            parts: Vec::new(),
+        }
+    }
+
+    /// A `multipart/mixed` body: independent parts, e.g. sub-responses of a batch request.
+    pub fn mixed() -> Self {
+        Self::new("mixed")
+    }
+
+    /// Use `boundary` instead of a generated one. If it appears in a part's body, a generated
+    /// one is used after all, since the body couldn't be framed otherwise.
+    pub fn boundary(mut self, boundary: impl Into<String>) -> Self {
+        self.boundary = boundary.into();
+        if self.parts.iter().any(|p| p.contains(&self.boundary)) {
+            self.regenerate_boundary();
+        }
+        self
+    }
+
+    /// Adds `part`, switching to a new generated boundary if its body contains the current
+    /// one.
+    pub fn part(mut self, part: Part) -> Self {
+        let collides = part.contains(&self.boundary);
+        self.parts.push(part);
+        if collides {
+            self.regenerate_boundary();
+        }
+        self
+    }
+
+    fn regenerate_boundary(&mut self) {
+        loop {
+            self.boundary = boundary();
+            if !self.parts.iter().any(|p| p.contains(&self.boundary)) {
+                return;
+            }
+        }
+    }
+
+    /// The `Content-Type` header value, including the boundary.
+    pub fn content_type(&self) -> String {
+        format!("multipart/{}; boundary={}", self.subtype, self.boundary)
+    }
+
+    /// The encoded body.
+    pub fn to_bytes(&self) -> Bytes {
+        let size = self
+            .parts
+            .iter()
+            .map(|p| p.body.len() + 64 * (p.headers.len() + 1))
+            .sum::<usize>();
+        let mut body = BytesMut::with_capacity(size + 2 * self.boundary.len() + 8);
+        for part in &self.parts {
+            body.put_slice(b"--");
+            body.put_slice(self.boundary.as_bytes());
+            body.put_slice(b"\r\n");
+            for (name, value) in &part.headers {
+                body.put_slice(name.as_str().as_bytes());
+                body.put_slice(b": ");
+                body.put_slice(value.as_bytes());
+                body.put_slice(b"\r\n");
+            }
+            body.put_slice(b"\r\n");
+            body.put_slice(&part.body);
+            body.put_slice(b"\r\n");
+        }
+        body.put_slice(b"--");
+        body.put_slice(self.boundary.as_bytes());
+        body.put_slice(b"--\r\n");
+        body.freeze()
+    }
+}
+
+impl From<Multipart> for Response {
+    fn from(multipart: Multipart) -> Self {
+        let content_type = multipart.content_type();
+        Response::from_data(multipart.to_bytes()).with_content_type(&content_type)
+    }
+}
+
+impl Part {
+    pub fn new(body: impl Into<Bytes>) -> Self {
+        Self {
+            headers: HeaderMap::new(),
+            body: body.into(),
+        }
+    }
+
+    pub fn with_header(mut self, header: Header) -> Self {
+        self.headers.insert(header.0, header.1);
+        self
+    }
+
+    /// Panics if `value` isn't a valid header value.
+    pub fn with_content_type(mut self, value: &str) -> Self {
+        let value = HeaderValue::from_str(value).expect("valid content type");
+        self.headers.insert(CONTENT_TYPE, value);
+        self
+    }
+
+    /// An embedded HTTP response as a part, for batch APIs in the style of OData and Google's:
+    /// `Content-Type: application/http` with the status line, headers and body inside.
+    pub fn http(response: &Response) -> Self {
+        let mut body = BytesMut::with_capacity(response.body.len() + 256);
+        let reason = match &response.reason {
+            Some(reason) => reason.as_bytes(),
+            None => response.status.canonical_reason().unwrap_or("").as_bytes(),
+        };
+        body.put_slice(format!("HTTP/1.1 {} ", response.status.as_u16()).as_bytes());
+        body.put_slice(reason);
+        body.put_slice(b"\r\n");
+        for (name, value) in &response.headers {
+            body.put_slice(name.as_str().as_bytes());
+            body.put_slice(b": ");
+            body.put_slice(value.as_bytes());
+            body.put_slice(b"\r\n");
+        }
+        body.put_slice(b"\r\n");
+        body.put_slice(&response.body);
+        Self::new(body.freeze()).with_content_type("application/http")
+    }
+
+    /// Whether `boundary` occurs in the body, where it would end the part early.
+    fn contains(&self, boundary: &str) -> bool {
+        let boundary = boundary.as_bytes();
+        !boundary.is_empty() && self.body.windows(boundary.len()).any(|w| w == boundary)
+    }
+}
+
+/// A random boundary, so clients can't plant one in content that ends up in a part.
+fn boundary() -> String {
+    format!("async_tiny_{:016x}{:016x}", random(), random())
+}
+
+/// 64 unpredictable bits, from the OS-seeded keys behind std's `HashMap`.
+pub(crate) fn random() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boundary_of(multipart: &Multipart) -> String {
+        multipart.boundary.clone()
+    }
+
+    #[test]
+    fn generated_boundaries_differ() {
+        let a = boundary_of(&Multipart::mixed());
+        let b = boundary_of(&Multipart::mixed());
+        assert_ne!(a, b);
+        assert_eq!(a.len(), "async_tiny_".len() + 32);
+    }
+
+    #[test]
+    fn a_part_containing_the_boundary_changes_it() {
+        let multipart = Multipart::new("byteranges");
+        let planted = boundary_of(&multipart);
+        let forged = format!(
+            "\r\n--{}\r\ncontent-type: text/html\r\n\r\n<script>",
+            planted
+        );
+        let multipart = multipart
+            .part(Part::new("first"))
+            .part(Part::new(forged.clone()));
+        assert_ne!(boundary_of(&multipart), planted);
+        let body = multipart.to_bytes();
+        let delimiter = format!("--{}", boundary_of(&multipart));
+        let delimiters = body
+            .windows(delimiter.len())
+            .filter(|w| *w == delimiter.as_bytes())
+            .count();
+        assert_eq!(delimiters, 3);
+        assert!(multipart.content_type().ends_with(&boundary_of(&multipart)));
+    }
+
+    #[test]
+    fn a_chosen_boundary_is_kept_unless_it_collides() {
+        let kept = Multipart::mixed().part(Part::new("a")).boundary("batch_1");
+        assert_eq!(boundary_of(&kept), "batch_1");
+
+        let replaced = Multipart::mixed()
+            .part(Part::new("see --batch_1 below"))
+            .boundary("batch_1");
+        assert_ne!(boundary_of(&replaced), "batch_1");
+        let replaced = Multipart::mixed()
+            .boundary("batch_1")
+            .part(Part::new("see --batch_1 below"));
+        assert_ne!(boundary_of(&replaced), "batch_1");
+    }
+}
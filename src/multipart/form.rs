@@ -0,0 +1,700 @@
+//! `multipart/form-data` request bodies (RFC 7578), with limits on uploaded files.
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use http::header::CONTENT_TYPE;
+use http::{HeaderMap, HeaderName, HeaderValue};
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+
+use crate::media_type::parse_params;
+use crate::{json, MediaType, Request, Response};
+
+/// The fields of a parsed form, in the order sent.
+#[derive(Debug)]
+pub struct Form {
+    fields: Vec<Field>,
+}
+
+/// One form field: a plain value, or a file when it has a filename.
+#[derive(Debug)]
+pub struct Field {
+    name: String,
+    filename: Option<String>,
+    content_type: MediaType,
+    headers: HeaderMap,
+    data: Bytes,
+    path: Option<PathBuf>,
+}
+
+/// Limits for uploaded files: applied to every file, or only to those of one field with
+/// [`UploadPolicy::field`].
+#[derive(Clone, Debug, Default)]
+pub struct FileRule {
+    max_size: Option<usize>,
+    max_count: Option<usize>,
+    content_types: Vec<String>,
+}
+
+/// How [`UploadPolicy::parse`] reads and checks a form.
+///
+/// ```
+/// use async_tiny::multipart::{FileRule, UploadPolicy};
+/// use async_tiny::{Header, Method, Request};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let policy = UploadPolicy::new().max_files(4).field(
+///     "avatar",
+///     FileRule::new().max_size(1024).max_count(1).content_types(&["image/png", "image/jpeg"]),
+/// );
+///
+/// let body = "--xyz\r\n\
+///     Content-Disposition: form-data; name=\"nick\"\r\n\r\n\
+///     alice\r\n\
+///     --xyz\r\n\
+///     Content-Disposition: form-data; name=\"avatar\"; filename=\"me.gif\"\r\n\
+///     Content-Type: image/gif\r\n\r\n\
+///     GIF89a\r\n\
+///     --xyz--\r\n";
+/// let req = Request::fake(&Method::POST, "/profile", body.as_bytes())
+///     .with_header(Header::new("Content-Type", "multipart/form-data; boundary=xyz").unwrap());
+///
+/// let err = policy.parse(&req).await.unwrap_err();
+/// assert_eq!(err.field(), Some("avatar"));
+/// let response = err.into_response();
+/// assert_eq!(response.status_code(), 422);
+/// assert!(response.body().contains("\"error\":\"content_type_not_allowed\""));
+///
+/// let form = UploadPolicy::new().parse(&req).await.unwrap();
+/// assert_eq!(form.text("nick"), Some("alice"));
+/// assert_eq!(form.get("avatar").unwrap().filename(), Some("me.gif"));
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct UploadPolicy {
+    files: FileRule,
+    fields: Vec<(String, FileRule)>,
+    max_files: Option<usize>,
+    spool: Option<PathBuf>,
+}
+
+/// Why a form was refused.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum UploadError {
+    /// The request isn't `multipart/form-data` with exactly one, non-empty boundary.
+    NotMultipart,
+    /// The body isn't valid `multipart/form-data`, or a part lacks a `name`.
+    Malformed,
+    FileTooLarge {
+        field: String,
+        max: usize,
+    },
+    /// More files than allowed, in total (`field` is `None`) or for one field.
+    TooManyFiles {
+        field: Option<String>,
+        max: usize,
+    },
+    ContentTypeNotAllowed {
+        field: String,
+        content_type: String,
+    },
+    /// Writing a file to the spool directory failed.
+    Spool(io::Error),
+}
+
+impl Form {
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// The first field named `name`.
+    pub fn get(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    /// Every field named `name`, e.g. the files of a multi-file input.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Field> + 'a {
+        self.fields.iter().filter(move |f| f.name == name)
+    }
+
+    /// The value of the first plain field named `name`, if it's valid UTF-8.
+    pub fn text(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|f| f.name == name && f.filename.is_none())
+            .and_then(Field::text)
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &Field> {
+        self.fields.iter().filter(|f| f.is_file())
+    }
+}
+
+impl Field {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The filename the client gave, as sent. Never use it as a path without sanitizing it.
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.filename.is_some()
+    }
+
+    /// The part's `Content-Type`, `text/plain` when it didn't send one (RFC 7578 §4.4).
+    pub fn content_type(&self) -> &MediaType {
+        &self.content_type
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// The contents; empty for files written to the spool directory, see [`Field::path`].
+    pub fn data(&self) -> &Bytes {
+        &self.data
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.data).ok()
+    }
+
+    /// Where the file was written, with [`UploadPolicy::spool_dir`]. The file is left for the
+    /// application to move or delete.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+}
+
+impl FileRule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Refuse files larger than `bytes`.
+    pub fn max_size(mut self, bytes: usize) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Refuse more than `n` files.
+    pub fn max_count(mut self, n: usize) -> Self {
+        self.max_count = Some(n);
+        self
+    }
+
+    /// Accept only these media types, e.g. `image/png`, or `image/*` for a whole type.
+    pub fn content_types(mut self, types: &[&str]) -> Self {
+        self.content_types = types.iter().map(|t| t.to_ascii_lowercase()).collect();
+        self
+    }
+
+    fn allows(&self, content_type: &MediaType) -> bool {
+        self.content_types.is_empty()
+            || self
+                .content_types
+                .iter()
+                .any(|allowed| match allowed.strip_suffix("/*") {
+                    Some(type_) => content_type.type_() == type_,
+                    None => content_type.is(allowed),
+                })
+    }
+}
+
+impl UploadPolicy {
+    /// No limits beyond [`ServerBuilder::max_body_size`](crate::ServerBuilder::max_body_size).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The rule for files of fields without their own [`UploadPolicy::field`] rule.
+    pub fn files(mut self, rule: FileRule) -> Self {
+        self.files = rule;
+        self
+    }
+
+    /// The rule for files in field `name`, instead of the [`files`](Self::files) rule.
+    pub fn field(mut self, name: &str, rule: FileRule) -> Self {
+        self.fields.push((name.to_string(), rule));
+        self
+    }
+
+    /// Refuse forms with more than `n` files across all fields.
+    pub fn max_files(mut self, n: usize) -> Self {
+        self.max_files = Some(n);
+        self
+    }
+
+    /// Write each file to a new file in `dir` (named by the server, not the client) instead
+    /// of keeping it in memory, e.g. to move it into place afterwards.
+    pub fn spool_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.spool = Some(dir.into());
+        self
+    }
+
+    /// Parse `req`'s body as `multipart/form-data` and check it against this policy.
+    ///
+    /// Every limit is checked before anything is spooled, so a refused form leaves no files
+    /// behind.
+    pub async fn parse(&self, req: &Request) -> Result<Form, UploadError> {
+        let content_type = req.content_type().ok_or(UploadError::NotMultipart)?;
+        if !content_type.is("multipart/form-data") {
+            return Err(UploadError::NotMultipart);
+        }
+        // Two boundaries could be read differently by a proxy in front, which would then
+        // check other parts than the ones parsed here.
+        let mut boundaries = content_type
+            .params()
+            .filter(|(name, _)| *name == "boundary");
+        let boundary = match (boundaries.next(), boundaries.next()) {
+            (Some((_, boundary)), None) if !boundary.is_empty() => boundary,
+            _ => return Err(UploadError::NotMultipart),
+        };
+        let mut fields = Vec::new();
+        for (headers, data) in split(req.body(), boundary).ok_or(UploadError::Malformed)? {
+            fields.push(field(headers, data).ok_or(UploadError::Malformed)?);
+        }
+        self.check(&fields)?;
+
+        if let Some(dir) = &self.spool {
+            let mut written = Vec::new();
+            for field in fields.iter_mut().filter(|f| f.is_file()) {
+                let path = dir.join(spool_name());
+                if let Err(e) = spool(&path, &field.data).await {
+                    for path in written {
+                        let _ = tokio::fs::remove_file(path).await;
+                    }
+                    return Err(UploadError::Spool(e));
+                }
+                written.push(path.clone());
+                field.path = Some(path);
+                field.data = Bytes::new();
+            }
+        }
+        Ok(Form { fields })
+    }
+
+    fn check(&self, fields: &[Field]) -> Result<(), UploadError> {
+        let files: Vec<&Field> = fields.iter().filter(|f| f.is_file()).collect();
+        if let Some(max) = self.max_files {
+            if files.len() > max {
+                return Err(UploadError::TooManyFiles { field: None, max });
+            }
+        }
+        for (i, file) in files.iter().enumerate() {
+            let rule = self
+                .fields
+                .iter()
+                .find(|(name, _)| *name == file.name)
+                .map_or(&self.files, |(_, rule)| rule);
+            if let Some(max) = rule.max_size {
+                if file.data.len() > max {
+                    return Err(UploadError::FileTooLarge {
+                        field: file.name.clone(),
+                        max,
+                    });
+                }
+            }
+            if !rule.allows(&file.content_type) {
+                return Err(UploadError::ContentTypeNotAllowed {
+                    field: file.name.clone(),
+                    content_type: file.content_type.essence().to_string(),
+                });
+            }
+            if let Some(max) = rule.max_count {
+                let seen = files[..=i].iter().filter(|f| f.name == file.name).count();
+                if seen > max {
+                    return Err(UploadError::TooManyFiles {
+                        field: Some(file.name.clone()),
+                        max,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl UploadError {
+    /// `415` for a body that isn't a form, `400` for a broken one, `500` for a spool failure
+    /// and `422` for everything the policy refused.
+    pub fn status(&self) -> u16 {
+        match self {
+            UploadError::NotMultipart => 415,
+            UploadError::Malformed => 400,
+            UploadError::Spool(_) => 500,
+            _ => 422,
+        }
+    }
+
+    /// A stable, machine-readable name for the error, e.g. `file_too_large`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            UploadError::NotMultipart => "not_multipart",
+            UploadError::Malformed => "malformed",
+            UploadError::FileTooLarge { .. } => "file_too_large",
+            UploadError::TooManyFiles { .. } => "too_many_files",
+            UploadError::ContentTypeNotAllowed { .. } => "content_type_not_allowed",
+            UploadError::Spool(_) => "spool_failed",
+        }
+    }
+
+    /// The field at fault, when there is one.
+    pub fn field(&self) -> Option<&str> {
+        match self {
+            UploadError::FileTooLarge { field, .. }
+            | UploadError::ContentTypeNotAllowed { field, .. } => Some(field),
+            UploadError::TooManyFiles { field, .. } => field.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// A JSON response such as
+    /// `{"error":"file_too_large","field":"avatar","message":"..."}` with [`status`](Self::status).
+    /// Spool failures don't reveal the underlying error.
+    pub fn into_response(&self) -> Response {
+        let message = match self {
+            UploadError::Spool(_) => "The upload could not be stored.".to_string(),
+            other => other.to_string(),
+        };
+        let field = self.field().map_or("null".to_string(), json::string);
+        let body = format!(
+            "{{\"error\":{},\"field\":{},\"message\":{}}}",
+            json::string(self.code()),
+            field,
+            json::string(&message)
+        );
+        Response::from_status_and_string(self.status(), body).with_content_type("application/json")
+    }
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UploadError::NotMultipart => write!(f, "expected a multipart/form-data body"),
+            UploadError::Malformed => write!(f, "malformed multipart/form-data body"),
+            UploadError::FileTooLarge { field, max } => {
+                write!(f, "file in field '{}' is larger than {} bytes", field, max)
+            }
+            UploadError::TooManyFiles {
+                field: Some(field),
+                max,
+            } => {
+                write!(f, "more than {} files in field '{}'", max, field)
+            }
+            UploadError::TooManyFiles { field: None, max } => {
+                write!(f, "more than {} files", max)
+            }
+            UploadError::ContentTypeNotAllowed {
+                field,
+                content_type,
+            } => write!(f, "{} is not allowed in field '{}'", content_type, field),
+            UploadError::Spool(e) => write!(f, "could not spool upload: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for UploadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UploadError::Spool(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// The parts of a multipart body: each one's headers and contents.
+fn split(body: &Bytes, boundary: &str) -> Option<Vec<(HeaderMap, Bytes)>> {
+    let delimiter = format!("--{}", boundary);
+    let delimiter = delimiter.as_bytes();
+    let mut pos = find(body, delimiter, 0)? + delimiter.len();
+    let mut parts = Vec::new();
+    loop {
+        if body[pos..].starts_with(b"--") {
+            return Some(parts);
+        }
+        // Transport padding may follow a delimiter (RFC 2046 §5.1.1).
+        while matches!(body.get(pos), Some(b' ' | b'\t')) {
+            pos += 1;
+        }
+        if !body[pos..].starts_with(b"\r\n") {
+            return None;
+        }
+        pos += 2;
+
+        let (head, content_start) = if body[pos..].starts_with(b"\r\n") {
+            (&body[pos..pos], pos + 2)
+        } else {
+            let end = find(body, b"\r\n\r\n", pos)?;
+            (&body[pos..end], end + 4)
+        };
+        let mut headers = HeaderMap::new();
+        for line in head.split(|&b| b == b'\n') {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            let colon = line.iter().position(|&b| b == b':')?;
+            let name = HeaderName::from_bytes(line[..colon].trim_ascii()).ok()?;
+            let value = HeaderValue::from_bytes(line[colon + 1..].trim_ascii()).ok()?;
+            headers.append(name, value);
+        }
+
+        let mut closing = Vec::with_capacity(delimiter.len() + 2);
+        closing.extend_from_slice(b"\r\n");
+        closing.extend_from_slice(delimiter);
+        let end = find(body, &closing, content_start)?;
+        parts.push((headers, body.slice(content_start..end)));
+        pos = end + closing.len();
+    }
+}
+
+fn field(headers: HeaderMap, data: Bytes) -> Option<Field> {
+    let disposition = headers.get("content-disposition")?.to_str().ok()?;
+    let (kind, params) = match disposition.find(';') {
+        Some(i) => (&disposition[..i], &disposition[i..]),
+        None => (disposition, ""),
+    };
+    if !kind.trim().eq_ignore_ascii_case("form-data") {
+        return None;
+    }
+    let params = parse_params(params);
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.clone())
+    };
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(MediaType::parse)
+        .unwrap_or_else(|| MediaType::parse("text/plain").expect("valid media type"));
+    Some(Field {
+        name: param("name")?,
+        filename: param("filename"),
+        content_type,
+        headers,
+        data,
+        path: None,
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|i| i + from)
+}
+
+/// A fresh name with 128 random bits, so other users of a shared spool directory can't
+/// guess it.
+fn spool_name() -> String {
+    format!("upload-{:016x}{:016x}", super::random(), super::random())
+}
+
+/// Write `data` to a new file at `path`, never following or replacing what is already there.
+async fn spool(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .await?;
+    let written = match file.write_all(data).await {
+        Ok(()) => file.flush().await,
+        Err(e) => Err(e),
+    };
+    if written.is_err() {
+        let _ = tokio::fs::remove_file(path).await;
+    }
+    written
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Header, Method};
+
+    fn post(content_type: &str, body: &str) -> Request {
+        Request::fake(&Method::POST, "/upload", body.as_bytes())
+            .with_header(Header::new("Content-Type", content_type).unwrap())
+    }
+
+    async fn parse(body: &str) -> Result<Form, UploadError> {
+        UploadPolicy::new()
+            .parse(&post("multipart/form-data; boundary=xyz", body))
+            .await
+    }
+
+    #[tokio::test]
+    async fn fields_and_files() {
+        let form = parse(
+            "preamble\r\n--xyz \t\r\n\
+             Content-Disposition: form-data; name=\"a\"\r\n\r\n\
+             one\r\nline\r\n\
+             --xyz\r\n\
+             Content-Disposition: form-data; name=\"f\"; filename=\"x.bin\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n\
+             \r\n--xy\r\n\
+             --xyz\r\n\
+             Content-Disposition: form-data; name=\"empty\"\r\n\r\n\
+             \r\n\
+             --xyz--\r\nepilogue",
+        )
+        .await
+        .unwrap();
+        assert_eq!(form.text("a"), Some("one\r\nline"));
+        let file = form.get("f").unwrap();
+        assert_eq!(file.data().as_ref(), b"\r\n--xy");
+        assert!(file.content_type().is("application/octet-stream"));
+        assert_eq!(form.text("empty"), Some(""));
+        assert!(form.get("a").unwrap().content_type().is("text/plain"));
+    }
+
+    #[tokio::test]
+    async fn missing_or_duplicate_boundary() {
+        let body = "--xyz\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n--xyz--\r\n";
+        for content_type in [
+            "multipart/form-data",
+            "multipart/form-data; boundary=",
+            "multipart/form-data; boundary=xyz; boundary=abc",
+            "multipart/mixed; boundary=xyz",
+            "application/x-www-form-urlencoded",
+        ] {
+            let err = UploadPolicy::new()
+                .parse(&post(content_type, body))
+                .await
+                .unwrap_err();
+            assert!(matches!(err, UploadError::NotMultipart), "{}", content_type);
+            assert_eq!(err.status(), 415);
+        }
+    }
+
+    #[tokio::test]
+    async fn truncated_body() {
+        for body in [
+            "",
+            "--xyz\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1",
+            "--xyz\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n--xy",
+            "--xyz\r\nContent-Disposition: form-data; name=\"a\"\r\n",
+            "--xyzContent-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n--xyz--",
+        ] {
+            let err = parse(body).await.unwrap_err();
+            assert!(matches!(err, UploadError::Malformed), "{:?}", body);
+            assert_eq!(err.status(), 400);
+        }
+    }
+
+    #[tokio::test]
+    async fn parts_need_form_data_disposition_and_name() {
+        for head in [
+            "Content-Type: text/plain",
+            "Content-Disposition: attachment; name=\"a\"",
+            "Content-Disposition: form-data",
+            "Content-Disposition: form-data; filename=\"a.txt\"",
+            "No-Colon",
+        ] {
+            let body = format!("--xyz\r\n{}\r\n\r\n1\r\n--xyz--\r\n", head);
+            let err = parse(&body).await.unwrap_err();
+            assert!(matches!(err, UploadError::Malformed), "{}", head);
+        }
+    }
+
+    #[tokio::test]
+    async fn quoted_and_escaped_filenames() {
+        let form = parse(
+            "--xyz\r\n\
+             Content-Disposition: form-data; name=\"a\"; filename=\"semi;colon \\\"q\\\".txt\"\r\n\r\n\
+             1\r\n\
+             --xyz\r\n\
+             Content-Disposition: form-data; name=b; filename=plain.txt\r\n\r\n\
+             2\r\n\
+             --xyz\r\n\
+             Content-Disposition: form-data; name=\"c\"; filename=\"../../etc/passwd\"\r\n\r\n\
+             3\r\n\
+             --xyz--\r\n",
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            form.get("a").unwrap().filename(),
+            Some("semi;colon \"q\".txt")
+        );
+        assert_eq!(form.get("b").unwrap().filename(), Some("plain.txt"));
+        // Handed over as sent; sanitizing it is the application's job.
+        assert_eq!(form.get("c").unwrap().filename(), Some("../../etc/passwd"));
+    }
+
+    #[tokio::test]
+    async fn oversized_and_too_many_files() {
+        let req = post(
+            "multipart/form-data; boundary=xyz",
+            "--xyz\r\n\
+             Content-Disposition: form-data; name=\"f\"; filename=\"1\"\r\n\r\n\
+             12345\r\n\
+             --xyz\r\n\
+             Content-Disposition: form-data; name=\"f\"; filename=\"2\"\r\n\r\n\
+             1\r\n\
+             --xyz--\r\n",
+        );
+
+        let err = UploadPolicy::new()
+            .files(FileRule::new().max_size(4))
+            .parse(&req)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UploadError::FileTooLarge { max: 4, .. }));
+        assert_eq!((err.status(), err.field()), (422, Some("f")));
+
+        let policy = UploadPolicy::new().field("f", FileRule::new().max_size(5).max_count(1));
+        let err = policy.parse(&req).await.unwrap_err();
+        assert!(matches!(err, UploadError::TooManyFiles { max: 1, .. }));
+        assert_eq!(err.field(), Some("f"));
+
+        let err = UploadPolicy::new()
+            .max_files(1)
+            .parse(&req)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UploadError::TooManyFiles { field: None, .. }));
+
+        assert!(UploadPolicy::new()
+            .files(FileRule::new().max_size(5))
+            .parse(&req)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn spooling_never_replaces_an_existing_file() {
+        let dir = std::env::temp_dir().join(spool_name());
+        std::fs::create_dir(&dir).unwrap();
+        let planted = dir.join("planted");
+        std::fs::write(&planted, "mine").unwrap();
+
+        let err = spool(&planted, b"theirs").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read(&planted).unwrap(), b"mine");
+
+        let req = post(
+            "multipart/form-data; boundary=xyz",
+            "--xyz\r\nContent-Disposition: form-data; name=\"f\"; filename=\"a.txt\"\r\n\r\nhello\r\n--xyz--\r\n",
+        );
+        let form = UploadPolicy::new()
+            .spool_dir(&dir)
+            .parse(&req)
+            .await
+            .unwrap();
+        let path = form.fields[0].path.clone().unwrap();
+        assert_ne!(spool_name(), spool_name());
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
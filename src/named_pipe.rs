@@ -0,0 +1,65 @@
+//! Windows named pipe transport.
+
+use std::io;
+use std::sync::Arc;
+
+use hyper_util::rt::TokioIo;
+use tokio::net::windows::named_pipe::{NamedPipeServer, ServerOptions};
+
+use crate::builder::Config;
+use crate::conn_error::{ConnectionError, ConnectionErrorKind};
+use crate::lanes::Lanes;
+use crate::{serve_connection, Server};
+
+pub(crate) fn start(name: &str, config: Config) -> io::Result<Server> {
+    // Creating the first instance up front surfaces errors (and a pipe already owned by
+    // another process) from the constructor, like a failed TCP bind.
+    let first = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(name)?;
+    if !config.silent {
+        eprintln!("async_tiny listening on {}", name);
+    }
+
+    let name = name.to_string();
+    Ok(Server::spawn(config, |tx, config| {
+        vec![tokio::spawn(accept_loop(
+            first,
+            name,
+            tx.clone(),
+            config.clone(),
+        ))]
+    }))
+}
+
+async fn accept_loop(mut pipe: NamedPipeServer, name: String, tx: Lanes, config: Arc<Config>) {
+    loop {
+        let connected = tokio::select! {
+            connected = pipe.connect() => connected,
+            _ = tx.closed() => break,
+        };
+
+        // Each client gets its own pipe instance, so create the next one before handing this
+        // one off. If that fails there's nothing left to listen on.
+        let next = match ServerOptions::new().create(&name) {
+            Ok(next) => next,
+            Err(e) => {
+                config.report(ConnectionError::new(ConnectionErrorKind::Accept, e));
+                break;
+            }
+        };
+        let client = std::mem::replace(&mut pipe, next);
+
+        match connected {
+            Ok(()) => {
+                tokio::spawn(serve_connection(
+                    TokioIo::new(client),
+                    None,
+                    tx.clone(),
+                    config.clone(),
+                ));
+            }
+            Err(e) => config.report(ConnectionError::new(ConnectionErrorKind::Accept, e)),
+        }
+    }
+}
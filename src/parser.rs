@@ -0,0 +1,237 @@
+//! Request parser policy.
+//!
+//! Whatever the profile, Hyper's parser rejects obsolete line folding (obs-fold), bare CR,
+//! control characters in header values, malformed header lines and repeated spaces in the
+//! request line with `400`, and accepts bare LF as a line ending. Hyper 1.x doesn't make these configurable for requests, so the
+//! profiles add checks on top of what it accepts.
+
+use http::header::{CONTENT_LENGTH, HOST, TRANSFER_ENCODING};
+use http::request::Parts;
+use http::Version;
+use hyper::server::conn::http1;
+
+/// How strictly incoming requests are vetted, set with [`ServerBuilder::parser_profile`].
+///
+/// Requests failing a check get `400` and the connection is closed.
+///
+/// [`ServerBuilder::parser_profile`]: crate::ServerBuilder::parser_profile
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParserProfile {
+    /// Whatever Hyper's parser accepts.
+    #[default]
+    Lenient,
+    /// Also rejects HTTP/1.1 requests with a missing or repeated `Host`, and requests carrying
+    /// both `Content-Length` and `Transfer-Encoding` (RFC 9112 §3.2, §6.3).
+    Strict,
+    /// `Strict`, plus rejects any transfer coding other than a plain `chunked` and control
+    /// characters or non-ASCII bytes in header values. At most 64 headers are accepted
+    /// (`431` beyond that).
+    Paranoid,
+}
+
+impl ParserProfile {
+    pub(crate) fn configure(self, builder: &mut http1::Builder) {
+        if self == ParserProfile::Paranoid {
+            builder.max_headers(64);
+        }
+    }
+
+    /// Checks the profile applies after Hyper has parsed the request head.
+    pub(crate) fn check(self, parts: &Parts) -> Result<(), &'static str> {
+        if self == ParserProfile::Lenient {
+            return Ok(());
+        }
+
+        let hosts = parts.headers.get_all(HOST).iter().count();
+        if hosts > 1 || (hosts == 0 && parts.version == Version::HTTP_11) {
+            return Err("missing or repeated Host header");
+        }
+
+        // Hyper drops Content-Length when it follows Transfer-Encoding, so only the reverse
+        // order is visible here.
+        let te: Vec<_> = parts.headers.get_all(TRANSFER_ENCODING).iter().collect();
+        if !te.is_empty() && parts.headers.contains_key(CONTENT_LENGTH) {
+            return Err("both Content-Length and Transfer-Encoding");
+        }
+
+        if self != ParserProfile::Paranoid {
+            return Ok(());
+        }
+
+        if !te.is_empty() && (te.len() > 1 || !te[0].as_bytes().eq_ignore_ascii_case(b"chunked")) {
+            return Err("unsupported Transfer-Encoding");
+        }
+
+        let bad_value = parts.headers.values().any(|v| {
+            v.as_bytes()
+                .iter()
+                .any(|&b| b >= 0x80 || (b < 0x20 && b != b'\t') || b == 0x7f)
+        });
+        if bad_value {
+            return Err("invalid bytes in header value");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use http::HeaderValue;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::{Response, Server};
+
+    fn parts(version: Version, headers: &[(&str, &[u8])]) -> Parts {
+        let mut request = http::Request::builder().version(version).uri("/");
+        for (name, value) in headers {
+            request = request.header(*name, HeaderValue::from_bytes(value).unwrap());
+        }
+        request.body(()).unwrap().into_parts().0
+    }
+
+    fn check(profile: ParserProfile, headers: &[(&str, &[u8])]) -> Result<(), &'static str> {
+        profile.check(&parts(Version::HTTP_11, headers))
+    }
+
+    const HOST_ONLY: &[(&str, &[u8])] = &[("host", b"example.com")];
+
+    #[test]
+    fn lenient_checks_nothing() {
+        assert!(check(ParserProfile::Lenient, &[]).is_ok());
+        let smuggle: &[(&str, &[u8])] =
+            &[("content-length", b"5"), ("transfer-encoding", b"chunked")];
+        assert!(check(ParserProfile::Lenient, smuggle).is_ok());
+    }
+
+    #[test]
+    fn strict_wants_exactly_one_host_on_http_11() {
+        for profile in [ParserProfile::Strict, ParserProfile::Paranoid] {
+            assert!(check(profile, HOST_ONLY).is_ok());
+            assert!(check(profile, &[]).is_err());
+            assert!(check(profile, &[("host", b"a.example"), ("host", b"b.example")]).is_err());
+            assert!(profile.check(&parts(Version::HTTP_10, &[])).is_ok());
+        }
+    }
+
+    #[test]
+    fn strict_refuses_content_length_with_transfer_encoding() {
+        let both: &[(&str, &[u8])] = &[
+            ("host", b"example.com"),
+            ("content-length", b"5"),
+            ("transfer-encoding", b"chunked"),
+        ];
+        assert!(check(ParserProfile::Strict, both).is_err());
+        assert!(check(ParserProfile::Strict, &both[..2]).is_ok());
+        assert!(check(ParserProfile::Strict, &[both[0], both[2]]).is_ok());
+    }
+
+    #[test]
+    fn strict_allows_what_paranoid_refuses() {
+        let gzip: &[(&str, &[u8])] = &[
+            ("host", b"example.com"),
+            ("transfer-encoding", b"gzip, chunked"),
+        ];
+        let latin1: &[(&str, &[u8])] = &[("host", b"example.com"), ("x-name", b"caf\xe9")];
+        assert!(check(ParserProfile::Strict, gzip).is_ok());
+        assert!(check(ParserProfile::Strict, latin1).is_ok());
+        assert!(check(ParserProfile::Paranoid, gzip).is_err());
+        assert!(check(ParserProfile::Paranoid, latin1).is_err());
+    }
+
+    #[test]
+    fn paranoid_only_takes_one_plain_chunked() {
+        let te = |value: &'static [u8]| -> Vec<(&'static str, &'static [u8])> {
+            vec![("host", b"example.com"), ("transfer-encoding", value)]
+        };
+        assert!(check(ParserProfile::Paranoid, &te(b"chunked")).is_ok());
+        assert!(check(ParserProfile::Paranoid, &te(b"Chunked")).is_ok());
+        assert!(check(ParserProfile::Paranoid, &te(b"gzip")).is_err());
+        assert!(check(ParserProfile::Paranoid, &te(b"chunked, chunked")).is_err());
+        let mut twice = te(b"chunked");
+        twice.push(("transfer-encoding", b"chunked"));
+        assert!(check(ParserProfile::Paranoid, &twice).is_err());
+    }
+
+    #[test]
+    fn paranoid_refuses_non_ascii_header_values() {
+        let value = |v: &'static [u8]| [("host", b"example.com" as &[u8]), ("x-name", v)];
+        assert!(check(ParserProfile::Paranoid, &value(b"tab\tseparated")).is_ok());
+        assert!(check(ParserProfile::Paranoid, &value("café".as_bytes())).is_err());
+        assert!(check(ParserProfile::Paranoid, &value(b"\xff")).is_err());
+    }
+
+    /// Sends `head` over a real socket to a server with `profile`, returning the status line.
+    async fn status_of(profile: ParserProfile, head: &[u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (streams, source) = mpsc::channel(1);
+        let mut server = Server::builder()
+            .silent(true)
+            .parser_profile(profile)
+            .serve_streams(source);
+        tokio::spawn(async move {
+            while let Some(request) = server.next().await {
+                let _ = request.respond(Response::from_string("ok"));
+            }
+        });
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let _ = streams.send(stream).await;
+        });
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(head).await.unwrap();
+        let mut response = Vec::new();
+        let mut chunk = [0; 1024];
+        while !response.windows(2).any(|w| w == b"\r\n") {
+            let read = tokio::time::timeout(Duration::from_secs(5), client.read(&mut chunk));
+            let n = read.await.expect("no response").unwrap();
+            if n == 0 {
+                break;
+            }
+            response.extend_from_slice(&chunk[..n]);
+        }
+        let response = String::from_utf8_lossy(&response);
+        response.lines().next().unwrap_or_default().to_string()
+    }
+
+    #[tokio::test]
+    async fn hyper_refuses_obs_fold_bare_cr_and_control_bytes() {
+        let heads: [&[u8]; 3] = [
+            b"GET / HTTP/1.1\r\nHost: example.com\r\nX-Folded: a\r\n b\r\n\r\n",
+            b"GET / HTTP/1.1\r\nHost: example.com\rX-Bare: cr\r\n\r\n",
+            b"GET / HTTP/1.1\r\nHost: example.com\r\nX-Ctl: a\x01b\r\n\r\n",
+        ];
+        for head in heads {
+            let status = status_of(ParserProfile::Lenient, head).await;
+            assert!(status.starts_with("HTTP/1.1 400"), "{:?}: {}", head, status);
+        }
+        let ok = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        let status = status_of(ParserProfile::Lenient, ok).await;
+        assert!(status.starts_with("HTTP/1.1 200"), "{}", status);
+    }
+
+    #[tokio::test]
+    async fn paranoid_answers_too_many_headers_with_431() {
+        let head = |count: usize| {
+            let mut head = b"GET / HTTP/1.1\r\nHost: example.com\r\n".to_vec();
+            for i in 1..count {
+                head.extend_from_slice(format!("X-Header-{}: {}\r\n", i, i).as_bytes());
+            }
+            head.extend_from_slice(b"\r\n");
+            head
+        };
+        let status = status_of(ParserProfile::Paranoid, &head(64)).await;
+        assert!(status.starts_with("HTTP/1.1 200"), "{}", status);
+        let status = status_of(ParserProfile::Paranoid, &head(65)).await;
+        assert!(status.starts_with("HTTP/1.1 431"), "{}", status);
+        let status = status_of(ParserProfile::Strict, &head(65)).await;
+        assert!(status.starts_with("HTTP/1.1 200"), "{}", status);
+    }
+}
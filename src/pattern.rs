@@ -0,0 +1,78 @@
+//! Route patterns such as `/users/:id` or `/static/*`, shared by the per-route middleware.
+
+/// A route pattern such as `/users/:id` or `/static/*`.
+#[derive(Clone, Debug)]
+pub(crate) struct Pattern {
+    raw: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Literal(String),
+    Param,
+    Rest,
+}
+
+impl Pattern {
+    pub(crate) fn parse(raw: &str) -> Self {
+        let segments = raw
+            .trim_matches('/')
+            .split('/')
+            .map(|s| match s {
+                "*" => Segment::Rest,
+                s if s.starts_with(':') => Segment::Param,
+                s => Segment::Literal(s.to_string()),
+            })
+            .collect();
+        Self {
+            raw: raw.to_string(),
+            segments,
+        }
+    }
+
+    /// The pattern as written.
+    pub(crate) fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    pub(crate) fn matches(&self, path: &str) -> bool {
+        let mut parts = path.trim_matches('/').split('/');
+        for segment in &self.segments {
+            match segment {
+                Segment::Rest => return true,
+                Segment::Param => {
+                    if parts.next().is_none_or(str::is_empty) {
+                        return false;
+                    }
+                }
+                Segment::Literal(lit) => {
+                    if parts.next() != Some(lit.as_str()) {
+                        return false;
+                    }
+                }
+            }
+        }
+        parts.next().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_literals_params_and_rest() {
+        let user = Pattern::parse("/users/:id");
+        assert!(user.matches("/users/42"));
+        assert!(user.matches("/users/42/"));
+        assert!(!user.matches("/users"));
+        assert!(!user.matches("/users/42/posts"));
+
+        let assets = Pattern::parse("/static/*");
+        assert!(assets.matches("/static/app.js"));
+        assert!(assets.matches("/static/css/site.css"));
+        assert!(!assets.matches("/other/app.js"));
+        assert_eq!(assets.as_str(), "/static/*");
+    }
+}
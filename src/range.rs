@@ -0,0 +1,369 @@
+//! `Range` request handling for buffered responses (RFC 9110 §14).
+
+use std::ops::Range;
+use std::time::{Duration, SystemTime};
+
+use bytes::Bytes;
+
+use http::header::{
+    ACCEPT_RANGES, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE,
+};
+use http::{HeaderMap, HeaderValue, Method, StatusCode};
+
+use crate::multipart::{Multipart, Part};
+use crate::{date, etag, Header, Request, Response};
+
+/// More ranges than this in a single header is treated as abuse and the range is ignored.
+const MAX_RANGES: usize = 32;
+
+pub(crate) fn apply(req: &Request, mut response: Response) -> Response {
+    if response.status != StatusCode::OK {
+        return response;
+    }
+    response
+        .headers
+        .insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+
+    // Range only applies to GET (RFC 9110 §14.2).
+    if req.method() != Method::GET {
+        return response;
+    }
+
+    let len = response.body.len() as u64;
+    match window(req.headers(), &response.headers, len) {
+        Window::Full => response,
+        Window::Unsatisfiable => unsatisfiable(len),
+        Window::Partial(ranges) => {
+            let body = std::mem::take(&mut response.body);
+            let pieces = ranges
+                .into_iter()
+                .map(|range| {
+                    let piece = body.slice(range.start as usize..range.end as usize);
+                    (range, piece)
+                })
+                .collect();
+            partial(response, pieces, len)
+        }
+    }
+}
+
+/// Which bytes of a representation a request asks for.
+pub(crate) enum Window {
+    /// All of it: no `Range`, one to ignore, or an `If-Range` that no longer matches.
+    Full,
+    Partial(Vec<Range<u64>>),
+    /// None of the ranges overlap the representation.
+    Unsatisfiable,
+}
+
+/// Evaluates `Range` and `If-Range` in `headers` against a representation of `len` bytes
+/// whose validators (`ETag`, `Last-Modified`) are in `validators`.
+pub(crate) fn window(headers: &HeaderMap, validators: &HeaderMap, len: u64) -> Window {
+    let header = match headers.get(RANGE).and_then(|v| v.to_str().ok()) {
+        Some(h) => h,
+        None => return Window::Full,
+    };
+
+    // A resumed download of a representation that has since changed gets the whole thing
+    // rather than a slice of the new one spliced onto the old (RFC 9110 §13.1.5).
+    if let Some(if_range) = headers.get(IF_RANGE) {
+        if !if_range_matches(if_range, validators) {
+            return Window::Full;
+        }
+    }
+
+    match parse(header, len) {
+        None => Window::Full,
+        Some(ranges) if ranges.is_empty() => Window::Unsatisfiable,
+        Some(ranges) => Window::Partial(ranges),
+    }
+}
+
+/// `416 Range Not Satisfiable` for a representation of `len` bytes.
+pub(crate) fn unsatisfiable(len: u64) -> Response {
+    let mut r = Response::empty(416);
+    r.headers
+        .insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    r.headers.insert(CONTENT_RANGE, content_range(None, len));
+    r
+}
+
+/// A `206` carrying `pieces` of a representation of `len` bytes, with the headers of
+/// `response` (whose body is ignored); several pieces become a `multipart/byteranges` body.
+pub(crate) fn partial(
+    mut response: Response,
+    mut pieces: Vec<(Range<u64>, Bytes)>,
+    len: u64,
+) -> Response {
+    if pieces.len() == 1 {
+        let (range, body) = pieces.remove(0);
+        response.status = StatusCode::PARTIAL_CONTENT;
+        response
+            .headers
+            .insert(CONTENT_RANGE, content_range(Some(&range), len));
+        response.body = body;
+        return response;
+    }
+
+    let part_type = response.headers.get(CONTENT_TYPE).cloned();
+    let mut multipart = Multipart::new("byteranges");
+    for (range, body) in pieces {
+        let mut part = Part::new(body);
+        if let Some(ct) = &part_type {
+            part = part.with_header(Header(CONTENT_TYPE, ct.clone()));
+        }
+        multipart = multipart
+            .part(part.with_header(Header(CONTENT_RANGE, content_range(Some(&range), len))));
+    }
+
+    let mut headers = response.headers;
+    headers.remove(CONTENT_TYPE);
+    let mut r = Response {
+        status: StatusCode::PARTIAL_CONTENT,
+        headers,
+        body: multipart.to_bytes(),
+        reason: None,
+    };
+    r.headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_str(&multipart.content_type()).expect("valid boundary"),
+    );
+    r
+}
+
+/// Whether an `If-Range` value still matches the response's `ETag` or `Last-Modified`.
+///
+/// Entity tags use strong comparison, so weak tags never match. A date matches only an
+/// identical `Last-Modified` at least a second old, since anything newer may have changed
+/// again within the same second.
+fn if_range_matches(value: &HeaderValue, headers: &HeaderMap) -> bool {
+    let Ok(value) = value.to_str() else {
+        return false;
+    };
+    let value = value.trim();
+
+    if value.starts_with('"') || value.starts_with("W/") {
+        return headers
+            .get(ETAG)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|tag| etag::strong_eq(tag, value));
+    }
+
+    let Some(date) = date::parse_http_date(value) else {
+        return false;
+    };
+    headers
+        .get(LAST_MODIFIED)
+        .and_then(date::from_header)
+        .is_some_and(|modified| {
+            modified == date && modified + Duration::from_secs(1) <= SystemTime::now()
+        })
+}
+
+/// Parses a `bytes=` range header against a body of `len` bytes.
+///
+/// Returns `None` when the header should be ignored (bad syntax, other units, too many
+/// ranges) and an empty list when no range is satisfiable. Overlapping and adjacent ranges
+/// are coalesced, so the parts never add up to more than the body.
+fn parse(header: &str, len: u64) -> Option<Vec<Range<u64>>> {
+    let specs = header.trim().strip_prefix("bytes=")?;
+    let mut ranges = Vec::new();
+
+    for spec in specs.split(',') {
+        let (start, end) = spec.trim().split_once('-')?;
+        let (start, end) = (start.trim(), end.trim());
+
+        let range = if start.is_empty() {
+            let suffix: u64 = end.parse().ok()?;
+            if suffix == 0 || len == 0 {
+                continue;
+            }
+            len.saturating_sub(suffix)..len
+        } else {
+            let start: u64 = start.parse().ok()?;
+            let end = if end.is_empty() {
+                len
+            } else {
+                let end: u64 = end.parse().ok()?;
+                if end < start {
+                    return None;
+                }
+                end.saturating_add(1).min(len)
+            };
+            if start >= len {
+                continue;
+            }
+            start..end
+        };
+        ranges.push(range);
+    }
+
+    if ranges.len() > MAX_RANGES {
+        return None;
+    }
+    Some(coalesce(ranges))
+}
+
+/// Merges ranges that overlap or touch. Ranges are left in the order they were asked for
+/// unless some had to be merged, in which case they come out sorted (RFC 9110 §14.6).
+fn coalesce(mut ranges: Vec<Range<u64>>) -> Vec<Range<u64>> {
+    let mut sorted = ranges.clone();
+    sorted.sort_by_key(|r| r.start);
+    if sorted.windows(2).all(|w| w[0].end < w[1].start) {
+        return ranges;
+    }
+    ranges.clear();
+    for range in sorted {
+        match ranges.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => ranges.push(range),
+        }
+    }
+    ranges
+}
+
+fn content_range(range: Option<&Range<u64>>, len: u64) -> HeaderValue {
+    let value = match range {
+        Some(r) => format!("bytes {}-{}/{}", r.start, r.end - 1, len),
+        None => format!("bytes */{}", len),
+    };
+    HeaderValue::from_str(&value).expect("valid content-range")
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = b"0123456789";
+
+    fn get(range: &str) -> Request {
+        Request::fake(&Method::GET, "/file", b"").with_header(Header::new("Range", range).unwrap())
+    }
+
+    fn file() -> Response {
+        Response::from_data(BODY.to_vec())
+            .with_header(Header::new("Content-Type", "text/plain").unwrap())
+            .with_header(Header::new("ETag", "\"v1\"").unwrap())
+    }
+
+    #[test]
+    fn parse_forms() {
+        assert_eq!(parse("bytes=2-4", 10), Some(vec![2..5]));
+        assert_eq!(parse("bytes=7-", 10), Some(vec![7..10]));
+        assert_eq!(parse("bytes=-3", 10), Some(vec![7..10]));
+        // A suffix longer than the body is the whole body; an end past it is clamped.
+        assert_eq!(parse("bytes=-30", 10), Some(vec![0..10]));
+        assert_eq!(parse("bytes=8-30", 10), Some(vec![8..10]));
+        assert_eq!(parse(" bytes= 0-0 , 9-9 ", 10), Some(vec![0..1, 9..10]));
+    }
+
+    #[test]
+    fn parse_unsatisfiable() {
+        assert_eq!(parse("bytes=10-", 10), Some(vec![]));
+        assert_eq!(parse("bytes=-0", 10), Some(vec![]));
+        assert_eq!(parse("bytes=0-", 0), Some(vec![]));
+        assert_eq!(parse("bytes=20-30, -0", 10), Some(vec![]));
+    }
+
+    #[test]
+    fn parse_ignores_malformed() {
+        for header in [
+            "bytes=5-2",
+            "bytes=a-b",
+            "bytes=1",
+            "bytes=-",
+            "bytes=1-2,,3-4",
+            "bytes=--1",
+            "items=0-1",
+            "0-1",
+            "bytes=18446744073709551616-",
+        ] {
+            assert_eq!(parse(header, 10), None, "{}", header);
+        }
+        let too_many = format!("bytes={}", vec!["0-0"; MAX_RANGES + 1].join(","));
+        assert_eq!(parse(&too_many, 10), None);
+    }
+
+    #[test]
+    fn parse_coalesces_overlaps() {
+        assert_eq!(parse("bytes=0-4,2-6", 10), Some(vec![0..7]));
+        assert_eq!(parse("bytes=5-,0-4", 10), Some(vec![0..10]));
+        assert_eq!(parse("bytes=8-9,0-1,1-3", 10), Some(vec![0..4, 8..10]));
+        // Without overlaps, the requested order is kept.
+        assert_eq!(parse("bytes=8-9,0-1", 10), Some(vec![8..10, 0..2]));
+        let amplified = format!("bytes={}", vec!["0-"; MAX_RANGES].join(","));
+        assert_eq!(parse(&amplified, 10), Some(vec![0..10]));
+    }
+
+    #[test]
+    fn apply_only_narrows_get() {
+        for method in [Method::HEAD, Method::POST, Method::PUT] {
+            let req = Request::fake(&method, "/file", b"")
+                .with_header(Header::new("Range", "bytes=0-1").unwrap());
+            let response = apply(&req, file());
+            assert_eq!(response.status, StatusCode::OK, "{}", method);
+            assert_eq!(response.body.as_ref(), BODY, "{}", method);
+        }
+    }
+
+    #[test]
+    fn single_range_is_206() {
+        let response = apply(&get("bytes=-3"), file());
+        assert_eq!(response.status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()["content-range"], "bytes 7-9/10");
+        assert_eq!(response.body_bytes().as_ref(), b"789");
+    }
+
+    #[test]
+    fn unsatisfiable_is_416() {
+        let response = apply(&get("bytes=10-"), file());
+        assert_eq!(response.status, StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()["content-range"], "bytes */10");
+    }
+
+    #[test]
+    fn malformed_range_gets_the_whole_body() {
+        let response = apply(&get("bytes=5-2"), file());
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.body_bytes().as_ref(), BODY);
+        assert_eq!(response.headers()["accept-ranges"], "bytes");
+    }
+
+    #[test]
+    fn several_ranges_are_multipart() {
+        let response = apply(&get("bytes=0-1,8-"), file());
+        assert_eq!(response.status, StatusCode::PARTIAL_CONTENT);
+        let content_type = response.headers()["content-type"].to_str().unwrap();
+        assert!(content_type.starts_with("multipart/byteranges; boundary="));
+        let body = String::from_utf8(response.body_bytes().to_vec()).unwrap();
+        let body = body.to_ascii_lowercase();
+        let first = body.find("content-range: bytes 0-1/10\r\n").unwrap();
+        let second = body.find("content-range: bytes 8-9/10\r\n").unwrap();
+        assert!(first < second);
+        assert_eq!(body.matches("content-type: text/plain\r\n").count(), 2);
+    }
+
+    #[test]
+    fn overlapping_ranges_are_not_amplified() {
+        let header = format!("bytes={}", vec!["0-"; MAX_RANGES].join(","));
+        let response = apply(&get(&header), file());
+        assert_eq!(response.status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()["content-range"], "bytes 0-9/10");
+        assert_eq!(response.body_bytes().as_ref(), BODY);
+    }
+
+    #[test]
+    fn if_range_mismatch_gets_the_whole_body() {
+        let stale = get("bytes=0-1").with_header(Header::new("If-Range", "\"v0\"").unwrap());
+        let response = apply(&stale, file());
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.body_bytes().as_ref(), BODY);
+
+        let fresh = get("bytes=0-1").with_header(Header::new("If-Range", "\"v1\"").unwrap());
+        assert_eq!(apply(&fresh, file()).status, StatusCode::PARTIAL_CONTENT);
+
+        let weak = get("bytes=0-1").with_header(Header::new("If-Range", "W/\"v1\"").unwrap());
+        assert_eq!(apply(&weak, file()).status, StatusCode::OK);
+    }
+}
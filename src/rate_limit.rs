@@ -0,0 +1,346 @@
+//! Token-bucket rate limiting, enabled via [`ServerBuilder::rate_limit`].
+//!
+//! Requests over their quota are answered with `429 Too Many Requests` and a `Retry-After`
+//! header before their body is read, and never reach the application.
+//!
+//! ```no_run
+//! use async_tiny::rate_limit::{Buckets, Quota, RateLimit};
+//! use async_tiny::Server;
+//!
+//! # fn is_issued(key: &str) -> bool { key.len() == 32 }
+//! # async fn run() -> std::io::Result<()> {
+//! let limits = RateLimit::new(Quota::per_second(10).burst(20))
+//!     // Tenants identified by API key get their own, larger allowance.
+//!     .api_key("x-api-key", Quota::per_minute(1200), is_issued)
+//!     // One allowance for everyone calling the free tier's host, combined.
+//!     .host("free.example.com", Quota::per_minute(600), Buckets::Shared);
+//! let mut server = Server::builder().rate_limit(limits).http("0.0.0.0:8080").await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Buckets live in memory unless [`RateLimit::store`] puts them in a shared [`Store`], which
+//! lets several instances enforce one limit.
+//!
+//! Each request draws from one bucket. Its quota comes from the first matching
+//! [`RateLimit::host`] rule, then [`RateLimit::api_key`] if the request carries a valid key,
+//! then the default. Clients are told apart by API key when they send a valid one and by IP
+//! address otherwise; behind a reverse proxy every client has the proxy's address, so use keys
+//! there.
+//!
+//! [`ServerBuilder::rate_limit`]: crate::ServerBuilder::rate_limit
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use http::header::HOST;
+use http::{HeaderMap, HeaderName};
+
+use crate::store::Store;
+
+/// Don't bother sweeping fewer buckets than this.
+const MIN_SWEEP: usize = 1024;
+
+/// How many requests a bucket allows: a steady rate plus a burst on top.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quota {
+    per_second: f64,
+    burst: u32,
+}
+
+impl Quota {
+    /// `n` requests a second, with a burst of `n`.
+    pub fn per_second(n: u32) -> Self {
+        Self::per(n, Duration::from_secs(1))
+    }
+
+    /// `n` requests a minute, with a burst of `n`.
+    pub fn per_minute(n: u32) -> Self {
+        Self::per(n, Duration::from_secs(60))
+    }
+
+    /// `n` requests an hour, with a burst of `n`.
+    pub fn per_hour(n: u32) -> Self {
+        Self::per(n, Duration::from_secs(60 * 60))
+    }
+
+    /// `n` requests every `period`, with a burst of `n`.
+    pub fn per(n: u32, period: Duration) -> Self {
+        Self {
+            per_second: f64::from(n) / period.as_secs_f64(),
+            burst: n.max(1),
+        }
+    }
+
+    /// Allow up to `n` requests back to back after a quiet spell.
+    pub fn burst(mut self, n: u32) -> Self {
+        self.burst = n.max(1);
+        self
+    }
+}
+
+/// Whether clients matched by a rule draw from one bucket between them or one each.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Buckets {
+    /// One bucket for every request the rule matches: a quota for the tenant as a whole.
+    Shared,
+    /// One bucket per client, as for the default quota.
+    PerClient,
+}
+
+type KeyCheck = dyn Fn(&str) -> bool + Send + Sync;
+
+/// Rate limits, set with [`ServerBuilder::rate_limit`](crate::ServerBuilder::rate_limit).
+pub struct RateLimit {
+    default: Quota,
+    hosts: Vec<(String, Quota, Buckets)>,
+    api_key: Option<(HeaderName, Quota, Arc<KeyCheck>)>,
+    buckets: Mutex<Table>,
+    store: Option<Box<dyn Store>>,
+}
+
+impl RateLimit {
+    /// Limit each client to `quota`.
+    pub fn new(quota: Quota) -> Self {
+        Self {
+            default: quota,
+            hosts: Vec::new(),
+            api_key: None,
+            buckets: Mutex::default(),
+            store: None,
+        }
+    }
+
+    /// Use `quota` for requests to `host` (matched against `Host`, ignoring case and port).
+    pub fn host(mut self, host: &str, quota: Quota, buckets: Buckets) -> Self {
+        self.hosts.push((host.to_ascii_lowercase(), quota, buckets));
+        self
+    }
+
+    /// Identify clients by the value of the `header` they send, and give those whose key
+    /// `is_valid` accepts `quota`.
+    ///
+    /// Requests with a key `is_valid` refuses are limited by IP address with the default
+    /// quota, as if they sent none; otherwise a client could dodge its limit by sending a new
+    /// made-up key each time. `is_valid` runs for every request carrying the header, so it
+    /// should be a lookup in memory, such as a set of issued keys.
+    ///
+    /// # Panics
+    ///
+    /// If `header` isn't a valid header name.
+    pub fn api_key<F>(mut self, header: &str, quota: Quota, is_valid: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        let header = HeaderName::from_bytes(header.as_bytes()).expect("valid header name");
+        self.api_key = Some((header, quota, Arc::new(is_valid)));
+        self
+    }
+
+    /// Count requests in `store` instead of in memory, so every instance sharing it shares
+    /// the limits.
+    ///
+    /// Stored limits use fixed windows rather than token buckets: a quota of `n` with a burst
+    /// of `b` allows `b` requests in each window of `b / n` seconds. If the store fails,
+    /// requests are let through.
+    pub fn store(mut self, store: impl Store + 'static) -> Self {
+        self.store = Some(Box::new(store));
+        self
+    }
+
+    /// Takes a token for this request, or says how long until one is available.
+    pub(crate) async fn check(
+        &self,
+        peer: Option<SocketAddr>,
+        headers: &HeaderMap,
+    ) -> Result<(), Duration> {
+        let key = self.api_key.as_ref().and_then(|(header, _, is_valid)| {
+            let key = headers.get(header)?.to_str().ok()?;
+            is_valid(key).then_some(key)
+        });
+        let host = headers
+            .get(HOST)
+            .and_then(|v| v.to_str().ok())
+            .map(strip_port);
+        let rule = host.and_then(|host| {
+            self.hosts
+                .iter()
+                .position(|(h, _, _)| h.eq_ignore_ascii_case(host))
+        });
+
+        let client = match (key, peer) {
+            (Some(key), _) => format!("key:{}", key),
+            (None, Some(peer)) => format!("ip:{}", peer.ip()),
+            (None, None) => "local".to_string(),
+        };
+        let (quota, bucket) = match rule {
+            Some(i) => {
+                let (_, quota, buckets) = &self.hosts[i];
+                match buckets {
+                    Buckets::Shared => (*quota, format!("host{}", i)),
+                    Buckets::PerClient => (*quota, format!("host{}/{}", i, client)),
+                }
+            }
+            None => match (&self.api_key, key) {
+                (Some((_, quota, _)), Some(_)) => (*quota, client),
+                _ => (self.default, client),
+            },
+        };
+
+        if let Some(store) = &self.store {
+            return check_window(store.as_ref(), &bucket, quota).await;
+        }
+
+        let now = Instant::now();
+        let mut table = self.buckets.lock().unwrap();
+        if let Some(bucket) = table.map.get_mut(&bucket) {
+            return bucket.take(now);
+        }
+        table.insert(bucket, Bucket::full(quota, now)).take(now)
+    }
+}
+
+/// The in-memory buckets. Idle ones are swept out each time the table doubles in size since
+/// the last sweep, so a flood of new clients costs amortized constant time per request.
+#[derive(Default)]
+struct Table {
+    map: HashMap<String, Bucket>,
+    /// How many buckets to let in before the next sweep.
+    sweep_at: usize,
+}
+
+impl Table {
+    fn insert(&mut self, key: String, bucket: Bucket) -> &mut Bucket {
+        if self.map.len() >= self.sweep_at {
+            let now = bucket.updated;
+            self.map.retain(|_, b| !b.idle(now));
+            self.sweep_at = (self.map.len() * 2).max(MIN_SWEEP);
+        }
+        self.map.entry(key).or_insert(bucket)
+    }
+}
+
+/// Counts the request against the current fixed window for `bucket` in `store`.
+async fn check_window(store: &dyn Store, bucket: &str, quota: Quota) -> Result<(), Duration> {
+    if quota.per_second <= 0.0 {
+        return Err(Duration::MAX);
+    }
+    let window = f64::from(quota.burst) / quota.per_second;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    let index = (now / window).floor();
+    let key = format!("ratelimit:{}:{}", bucket, index as u64);
+    let ttl = Duration::from_secs_f64(window);
+    match store.increment(&key, Some(ttl)).await {
+        Ok(count) if count > u64::from(quota.burst) => {
+            Err(Duration::from_secs_f64((index + 1.0) * window - now))
+        }
+        _ => Ok(()),
+    }
+}
+
+struct Bucket {
+    quota: Quota,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(quota: Quota, now: Instant) -> Self {
+        Self {
+            quota,
+            tokens: f64::from(quota.burst),
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * self.quota.per_second).min(f64::from(self.quota.burst));
+        self.updated = now;
+    }
+
+    fn take(&mut self, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        if self.quota.per_second <= 0.0 {
+            return Err(Duration::MAX);
+        }
+        Err(Duration::from_secs_f64(
+            (1.0 - self.tokens) / self.quota.per_second,
+        ))
+    }
+
+    /// Refilled to the brim, so dropping it changes nothing.
+    fn idle(&self, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens + elapsed * self.quota.per_second >= f64::from(self.quota.burst)
+    }
+}
+
+/// `example.com:8080` to `example.com`, leaving bracketed IPv6 addresses intact.
+fn strip_port(host: &str) -> &str {
+    match host.rfind(':') {
+        Some(i) if !host[i..].contains(']') => &host[..i],
+        _ => host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> RateLimit {
+        RateLimit::new(Quota::per_minute(2))
+            .api_key("x-api-key", Quota::per_minute(5), |key| key == "issued")
+    }
+
+    fn with_key(key: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", key.parse().unwrap());
+        headers
+    }
+
+    #[tokio::test]
+    async fn made_up_keys_share_the_ip_bucket() {
+        let limits = limits();
+        let peer = Some("192.0.2.1:4000".parse().unwrap());
+        assert!(limits.check(peer, &with_key("a")).await.is_ok());
+        assert!(limits.check(peer, &with_key("b")).await.is_ok());
+        assert!(limits.check(peer, &with_key("c")).await.is_err());
+        assert!(limits.check(peer, &HeaderMap::new()).await.is_err());
+        assert_eq!(limits.buckets.lock().unwrap().map.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn valid_keys_get_their_own_quota() {
+        let limits = limits();
+        let peer = Some("192.0.2.1:4000".parse().unwrap());
+        for _ in 0..5 {
+            assert!(limits.check(peer, &with_key("issued")).await.is_ok());
+        }
+        assert!(limits.check(peer, &with_key("issued")).await.is_err());
+        assert!(limits.check(peer, &HeaderMap::new()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn sweeps_only_as_the_table_doubles() {
+        let limits = RateLimit::new(Quota::per_hour(1));
+        for i in 0..MIN_SWEEP * 3 {
+            let peer = Some(SocketAddr::from(([10, 0, (i >> 8) as u8, i as u8], 4000)));
+            assert!(limits.check(peer, &HeaderMap::new()).await.is_ok());
+        }
+        // Nothing was idle, so the table grew past each threshold and doubled it.
+        let table = limits.buckets.lock().unwrap();
+        assert_eq!(table.map.len(), MIN_SWEEP * 3);
+        assert_eq!(table.sweep_at, MIN_SWEEP * 4);
+    }
+}
@@ -0,0 +1,84 @@
+//! Building `Location` values for [`Request::redirect`](crate::Request::redirect) and
+//! friends.
+
+use http::HeaderValue;
+
+/// `target` resolved against the request URL `base` (path and query), as in RFC 3986 §5.2.
+///
+/// Absolute URLs (`https://…`, `//host/…`) are returned unchanged.
+pub(crate) fn resolve(base: &str, target: &str) -> String {
+    if target.contains("://") || target.starts_with("//") {
+        return target.to_string();
+    }
+    let base_path = base.split(['?', '#']).next().unwrap_or("");
+    let base_path = if base_path.is_empty() { "/" } else { base_path };
+
+    let split = target.find(['?', '#']).unwrap_or(target.len());
+    let (path, rest) = target.split_at(split);
+    if path.is_empty() {
+        // `?page=2` or `#top`: same path, new query or fragment.
+        return format!("{}{}", base_path, rest);
+    }
+    let merged = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        let dir = &base_path[..base_path.rfind('/').map_or(0, |i| i + 1)];
+        format!("{}{}", dir, path)
+    };
+    format!("{}{}", remove_dot_segments(&merged), rest)
+}
+
+fn remove_dot_segments(path: &str) -> String {
+    let mut out: Vec<&str> = Vec::new();
+    let segments: Vec<&str> = path.split('/').skip(1).collect();
+    for (i, segment) in segments.iter().enumerate() {
+        let last = i + 1 == segments.len();
+        match *segment {
+            "." => {
+                if last {
+                    out.push("");
+                }
+            }
+            ".." => {
+                out.pop();
+                if last {
+                    out.push("");
+                }
+            }
+            s => out.push(s),
+        }
+    }
+    format!("/{}", out.join("/"))
+}
+
+/// The path and query of `referer` if it points at `host`, so a redirect back to it can't
+/// send the client to another site.
+pub(crate) fn same_origin_referer(referer: &str, host: &str) -> Option<String> {
+    let (_, rest) = referer.split_once("://")?;
+    let (authority, path) = match rest.find(['/', '?', '#']) {
+        Some(i) => rest.split_at(i),
+        None => (rest, "/"),
+    };
+    let authority = authority.rsplit('@').next().unwrap_or(authority);
+    if !authority.eq_ignore_ascii_case(host) {
+        return None;
+    }
+    let path = path.split('#').next().unwrap_or("");
+    Some(match path.strip_prefix('?') {
+        Some(_) => format!("/{}", path),
+        None => path.to_string(),
+    })
+}
+
+/// `location` as a header value, percent-encoding anything that isn't printable ASCII.
+pub(crate) fn location(location: &str) -> HeaderValue {
+    let mut encoded = String::with_capacity(location.len());
+    for &b in location.as_bytes() {
+        if (0x21..0x7f).contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    HeaderValue::from_str(&encoded).expect("percent-encoded location")
+}
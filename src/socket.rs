@@ -0,0 +1,157 @@
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use socket2::{Domain, Protocol, SockRef, Socket, TcpKeepalive, Type};
+use tokio::net::{TcpListener, TcpStream};
+
+/// TCP socket options, set with [`ServerBuilder::socket_options`].
+///
+/// Listener options (`reuse_address`, `reuse_port`, `backlog` and the buffer sizes) are applied
+/// before binding; `nodelay` and `keepalive` are applied to every accepted connection. Buffer
+/// sizes are inherited by accepted connections on most platforms.
+///
+/// ```
+/// use std::time::Duration;
+/// use async_tiny::SocketOptions;
+///
+/// let options = SocketOptions::new()
+///     .backlog(4096)
+///     .nodelay(true)
+///     .keepalive(Duration::from_secs(60));
+/// # let _ = options;
+/// ```
+///
+/// [`ServerBuilder::socket_options`]: crate::ServerBuilder::socket_options
+#[derive(Clone, Debug)]
+pub struct SocketOptions {
+    reuse_address: bool,
+    reuse_port: bool,
+    backlog: u32,
+    nodelay: bool,
+    keepalive: Option<Duration>,
+    recv_buffer_size: Option<usize>,
+    send_buffer_size: Option<usize>,
+}
+
+impl Default for SocketOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SocketOptions {
+    /// What Tokio uses: `SO_REUSEADDR` outside Windows and a backlog of 1024.
+    pub fn new() -> Self {
+        Self {
+            reuse_address: !cfg!(windows),
+            reuse_port: false,
+            backlog: 1024,
+            nodelay: false,
+            keepalive: None,
+            recv_buffer_size: None,
+            send_buffer_size: None,
+        }
+    }
+
+    /// `SO_REUSEADDR`, for rebinding a port with connections still in `TIME_WAIT`.
+    pub fn reuse_address(mut self, enabled: bool) -> Self {
+        self.reuse_address = enabled;
+        self
+    }
+
+    /// `SO_REUSEPORT`, for several processes sharing one port. Ignored on platforms without it.
+    pub fn reuse_port(mut self, enabled: bool) -> Self {
+        self.reuse_port = enabled;
+        self
+    }
+
+    /// How many connections the kernel queues before they're accepted.
+    pub fn backlog(mut self, backlog: u32) -> Self {
+        self.backlog = backlog;
+        self
+    }
+
+    /// `TCP_NODELAY`: send small responses right away instead of coalescing them.
+    pub fn nodelay(mut self, enabled: bool) -> Self {
+        self.nodelay = enabled;
+        self
+    }
+
+    /// Send TCP keepalive probes after a connection has been idle for `idle`.
+    pub fn keepalive(mut self, idle: Duration) -> Self {
+        self.keepalive = Some(idle);
+        self
+    }
+
+    /// `SO_RCVBUF`, in bytes.
+    pub fn recv_buffer_size(mut self, size: usize) -> Self {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// `SO_SNDBUF`, in bytes.
+    pub fn send_buffer_size(mut self, size: usize) -> Self {
+        self.send_buffer_size = Some(size);
+        self
+    }
+
+    pub(crate) fn bind(&self, addr: SocketAddr) -> io::Result<TcpListener> {
+        self.bind_with(addr, None)
+    }
+
+    /// One wildcard listener for both families, or one per family where that isn't supported.
+    pub(crate) fn bind_dual_stack(&self, port: u16) -> io::Result<Vec<TcpListener>> {
+        let v6 = SocketAddr::from((Ipv6Addr::UNSPECIFIED, port));
+        let v4 = SocketAddr::from((Ipv4Addr::UNSPECIFIED, port));
+
+        match self.bind_with(v6, Some(false)) {
+            Ok(listener) => return Ok(vec![listener]),
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse => return Err(e),
+            Err(_) => {}
+        }
+
+        let v4_listener = self.bind(v4)?;
+        // With port 0, bind the IPv6 side to the port the IPv4 side was given.
+        let v6 = SocketAddr::from((Ipv6Addr::UNSPECIFIED, v4_listener.local_addr()?.port()));
+        match self.bind_with(v6, Some(true)) {
+            Ok(v6_listener) => Ok(vec![v4_listener, v6_listener]),
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse => Err(e),
+            // No IPv6 on this host.
+            Err(_) => Ok(vec![v4_listener]),
+        }
+    }
+
+    fn bind_with(&self, addr: SocketAddr, only_v6: Option<bool>) -> io::Result<TcpListener> {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+        if let Some(only_v6) = only_v6 {
+            socket.set_only_v6(only_v6)?;
+        }
+        socket.set_reuse_address(self.reuse_address)?;
+        #[cfg(all(
+            unix,
+            not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin"))
+        ))]
+        socket.set_reuse_port(self.reuse_port)?;
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        socket.set_nonblocking(true)?;
+        socket.bind(&addr.into())?;
+        socket.listen(self.backlog.min(i32::MAX as u32) as i32)?;
+        TcpListener::from_std(socket.into())
+    }
+
+    pub(crate) fn apply(&self, stream: &TcpStream) -> io::Result<()> {
+        if self.nodelay {
+            stream.set_nodelay(true)?;
+        }
+        if let Some(idle) = self.keepalive {
+            SockRef::from(stream).set_tcp_keepalive(&TcpKeepalive::new().with_time(idle))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,492 @@
+//! Serving files from a directory, with automatic `ETag` / `If-None-Match` handling and
+//! optional precompressed (`.br` / `.gz`) variants.
+//!
+//! ```no_run
+//! use async_tiny::static_files::StaticFiles;
+//! use async_tiny::{Response, Server};
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let files = StaticFiles::new("./public");
+//! let mut server = Server::http("127.0.0.1:8080", false).await?;
+//!
+//! while let Some(request) = server.next().await {
+//!     let response = match files.serve(&request).await {
+//!         Some(response) => response,
+//!         None => Response::from_status_and_string(404, "Not Found"),
+//!     };
+//!     let _ = request.respond(response);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, ETAG, IF_NONE_MATCH, LAST_MODIFIED};
+use http::{HeaderValue, Method};
+use tokio::sync::Semaphore;
+
+use crate::{date, etag, Header, Request, Response};
+
+/// Serves files below a root directory.
+///
+/// File metadata and the computed `ETag` are cached per path and recomputed whenever the
+/// file's modification time or size changes.
+pub struct StaticFiles {
+    root: PathBuf,
+    content_hash: bool,
+    precompressed: bool,
+    cache: Mutex<HashMap<PathBuf, CachedFile>>,
+    hot: Option<Mutex<HotCache>>,
+    io_slots: Option<Semaphore>,
+}
+
+/// How long a file held in memory is served before its metadata is checked again.
+const HOT_REVALIDATE: Duration = Duration::from_secs(1);
+
+#[derive(Clone)]
+struct CachedFile {
+    modified: SystemTime,
+    len: u64,
+    etag: String,
+}
+
+impl StaticFiles {
+    /// Serve files from `root`. Directories resolve to their `index.html`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            content_hash: false,
+            precompressed: false,
+            cache: Mutex::new(HashMap::new()),
+            hot: None,
+            io_slots: None,
+        }
+    }
+
+    /// Run at most `n` filesystem operations at a time.
+    ///
+    /// Tokio performs file I/O on its blocking thread pool (see [`tokio::task::spawn_blocking`]),
+    /// which other blocking work shares. On a slow disk a burst of requests can occupy every
+    /// thread in that pool, so this caps how many go to the disk at once and queues the rest.
+    pub fn fs_concurrency(mut self, n: usize) -> Self {
+        self.io_slots = Some(Semaphore::new(n.max(1)));
+        self
+    }
+
+    /// Keep the contents of small files in memory, up to `max_bytes` in total, so serving a
+    /// hot asset doesn't touch the filesystem at all.
+    ///
+    /// Files up to a sixteenth of `max_bytes` are kept once served, and the least recently
+    /// served ones make room for new ones. Each file's size and modification time are checked
+    /// at most once a second, so a changed file can be served stale for up to a second.
+    pub fn memory_cache(mut self, max_bytes: usize) -> Self {
+        self.hot = Some(Mutex::new(HotCache::new(max_bytes)));
+        self
+    }
+
+    /// Derive ETags from the file contents instead of size and modification time.
+    ///
+    /// Content hashes survive deploys that touch mtimes but cost a full read when a file changes.
+    /// Requires the `sha2` feature, which is on by default.
+    #[cfg(feature = "sha2")]
+    pub fn content_hash(mut self, enabled: bool) -> Self {
+        self.content_hash = enabled;
+        self
+    }
+
+    /// Serve `app.js.br` or `app.js.gz` in place of `app.js` when they exist next to it and
+    /// the client's `Accept-Encoding` allows, preferring Brotli.
+    ///
+    /// The response keeps the original file's `Content-Type`, adds `Content-Encoding`, and
+    /// carries `Vary: Accept-Encoding`. Each variant gets its own `ETag`. The original must
+    /// exist too; it's what clients without compression support get.
+    pub fn precompressed(mut self, enabled: bool) -> Self {
+        self.precompressed = enabled;
+        self
+    }
+
+    /// Serve the file matching the request path.
+    ///
+    /// Returns `None` for methods other than GET/HEAD and for paths that don't resolve to a
+    /// file, so the caller can fall through to its own handling. Answers `304 Not Modified`
+    /// when `If-None-Match` matches the current ETag, and honors `Range` requests, including
+    /// `If-Range` against the ETag or `Last-Modified` so resumed downloads of a changed file
+    /// start over.
+    pub async fn serve(&self, req: &Request) -> Option<Response> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return None;
+        }
+
+        let path = self.resolve(req.url())?;
+        let hot_key = self
+            .hot
+            .as_ref()
+            .map(|_| (path.clone(), self.accepted(req)));
+        if let Some(key) = &hot_key {
+            if let Some(file) = self.hot_lookup(key).await {
+                return self.respond(req, file);
+            }
+        }
+
+        let meta = self.io(tokio::fs::metadata(&path)).await.ok()?;
+        let (path, meta) = if meta.is_dir() {
+            let index = path.join("index.html");
+            let meta = self.io(tokio::fs::metadata(&index)).await.ok()?;
+            (index, meta)
+        } else {
+            (path, meta)
+        };
+        if !meta.is_file() {
+            return None;
+        }
+        let content_type = content_type(&path);
+
+        let (path, meta, encoding) = match self.precompressed_variant(req, &path).await {
+            Some((variant, meta, encoding)) => (variant, meta, Some(encoding)),
+            None => (path, meta, None),
+        };
+
+        let modified = meta.modified().unwrap_or(UNIX_EPOCH);
+        let len = meta.len();
+
+        let cached = self.cached(&path, modified, len);
+        let (etag, data): (String, Option<Vec<u8>>) = match cached {
+            Some(etag) => (etag, None),
+            None => {
+                let (etag, data) = match self.content_hash {
+                    #[cfg(feature = "sha2")]
+                    true => {
+                        let data = self.io(tokio::fs::read(&path)).await.ok()?;
+                        (etag::from_bytes(&data), Some(data))
+                    }
+                    _ => (etag::from_metadata(len, modified), None),
+                };
+                self.store(&path, modified, len, &etag);
+                (etag, data)
+            }
+        };
+
+        // Keep variants' ETags apart even if a compressed copy matches the original's size.
+        let etag = match encoding {
+            Some(encoding) => format!("{}-{}\"", etag.trim_end_matches('"'), encoding),
+            None => etag,
+        };
+
+        let mut file = LoadedFile {
+            path,
+            content_type,
+            encoding,
+            etag,
+            modified,
+            len,
+            data: data.map(Bytes::from),
+        };
+        if file.data.is_none() && !self.not_modified(req, &file.etag) {
+            file.data = Some(Bytes::from(
+                self.io(tokio::fs::read(&file.path)).await.ok()?,
+            ));
+        }
+        if let (Some(key), Some(hot)) = (hot_key, &self.hot) {
+            if file.data.is_some() {
+                hot.lock().unwrap().insert(key, &file);
+            }
+        }
+        self.respond(req, file)
+    }
+
+    /// The response for `file`: `304` if the client's copy is current, otherwise the file's
+    /// contents, which must then be loaded.
+    fn respond(&self, req: &Request, file: LoadedFile) -> Option<Response> {
+        let etag_header = Header(ETAG, HeaderValue::from_str(&file.etag).ok()?);
+        if self.not_modified(req, &file.etag) {
+            return Some(self.vary(Response::empty(304).with_header(etag_header)));
+        }
+
+        let mut response = Response::from_data(file.data?)
+            .with_content_type(file.content_type)
+            .with_header(etag_header);
+        if file.modified != UNIX_EPOCH {
+            response =
+                response.with_header(Header(LAST_MODIFIED, date::header_value(file.modified)));
+        }
+        if let Some(encoding) = file.encoding {
+            response =
+                response.with_header(Header(CONTENT_ENCODING, HeaderValue::from_static(encoding)));
+        }
+        Some(self.vary(response).with_range(req))
+    }
+
+    fn not_modified(&self, req: &Request, etag: &str) -> bool {
+        req.headers()
+            .get(IF_NONE_MATCH)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| etag::list_matches(v, |candidate| etag::weak_eq(candidate, etag)))
+    }
+
+    /// Runs a filesystem operation, waiting its turn under [`StaticFiles::fs_concurrency`].
+    async fn io<T>(&self, op: impl Future<Output = T>) -> T {
+        let _permit = match &self.io_slots {
+            Some(slots) => slots.acquire().await.ok(),
+            None => None,
+        };
+        op.await
+    }
+
+    /// A file from the memory cache, if it's there and hasn't changed.
+    async fn hot_lookup(&self, key: &HotKey) -> Option<LoadedFile> {
+        let hot = self.hot.as_ref()?;
+        let (file, checked) = hot.lock().unwrap().get(key)?;
+        if checked.elapsed() < HOT_REVALIDATE {
+            return Some(file);
+        }
+        let meta = self.io(tokio::fs::metadata(&file.path)).await.ok();
+        let unchanged = meta.is_some_and(|m| {
+            m.len() == file.len && m.modified().unwrap_or(UNIX_EPOCH) == file.modified
+        });
+        let mut hot = hot.lock().unwrap();
+        if unchanged {
+            hot.revalidated(key);
+            Some(file)
+        } else {
+            hot.remove(key);
+            None
+        }
+    }
+
+    /// Which precompressed variants the client accepts, as part of the memory cache key.
+    fn accepted(&self, req: &Request) -> u8 {
+        if !self.precompressed {
+            return 0;
+        }
+        let accept = req
+            .headers()
+            .get(ACCEPT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        u8::from(accepts_encoding(accept, "br")) | u8::from(accepts_encoding(accept, "gzip")) << 1
+    }
+
+    /// The best precompressed sibling of `path` the client accepts, if any.
+    async fn precompressed_variant(
+        &self,
+        req: &Request,
+        path: &Path,
+    ) -> Option<(PathBuf, std::fs::Metadata, &'static str)> {
+        if !self.precompressed {
+            return None;
+        }
+        let accept = req.headers().get(ACCEPT_ENCODING)?.to_str().ok()?;
+
+        for (encoding, extension) in [("br", "br"), ("gzip", "gz")] {
+            if !accepts_encoding(accept, encoding) {
+                continue;
+            }
+            let mut variant = path.as_os_str().to_owned();
+            variant.push(".");
+            variant.push(extension);
+            let variant = PathBuf::from(variant);
+            if let Ok(meta) = self.io(tokio::fs::metadata(&variant)).await {
+                if meta.is_file() {
+                    return Some((variant, meta, encoding));
+                }
+            }
+        }
+        None
+    }
+
+    fn vary(&self, response: Response) -> Response {
+        if self.precompressed {
+            response.with_vary(&["Accept-Encoding"])
+        } else {
+            response
+        }
+    }
+
+    fn resolve(&self, url: &str) -> Option<PathBuf> {
+        let path = url.split(['?', '#']).next().unwrap_or("");
+        let decoded = percent_decode(path)?;
+
+        let mut resolved = self.root.clone();
+        for component in Path::new(&decoded).components() {
+            match component {
+                Component::Normal(part) => resolved.push(part),
+                Component::RootDir | Component::CurDir => {}
+                Component::ParentDir | Component::Prefix(_) => return None,
+            }
+        }
+        Some(resolved)
+    }
+
+    fn cached(&self, path: &Path, modified: SystemTime, len: u64) -> Option<String> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .get(path)
+            .filter(|c| c.modified == modified && c.len == len)
+            .map(|c| c.etag.clone())
+    }
+
+    fn store(&self, path: &Path, modified: SystemTime, len: u64, etag: &str) {
+        let entry = CachedFile {
+            modified,
+            len,
+            etag: etag.to_string(),
+        };
+        self.cache.lock().unwrap().insert(path.to_path_buf(), entry);
+    }
+}
+
+/// A file ready to be served, with its contents if they've been read.
+#[derive(Clone)]
+struct LoadedFile {
+    path: PathBuf,
+    content_type: &'static str,
+    encoding: Option<&'static str>,
+    etag: String,
+    modified: SystemTime,
+    len: u64,
+    data: Option<Bytes>,
+}
+
+/// Request path and accepted encodings, which together decide which file is served.
+type HotKey = (PathBuf, u8);
+
+/// Small files' contents, bounded by total size, evicting the least recently served.
+struct HotCache {
+    max_bytes: usize,
+    used: usize,
+    tick: u64,
+    entries: HashMap<HotKey, HotEntry>,
+}
+
+struct HotEntry {
+    file: LoadedFile,
+    checked: Instant,
+    last_used: u64,
+}
+
+impl HotCache {
+    fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            used: 0,
+            tick: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, key: &HotKey) -> Option<(LoadedFile, Instant)> {
+        self.tick += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.last_used = self.tick;
+        Some((entry.file.clone(), entry.checked))
+    }
+
+    fn revalidated(&mut self, key: &HotKey) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.checked = Instant::now();
+        }
+    }
+
+    fn insert(&mut self, key: HotKey, file: &LoadedFile) {
+        let Some(size) = file.data.as_ref().map(Bytes::len) else {
+            return;
+        };
+        if size > self.max_bytes / 16 {
+            return;
+        }
+        self.remove(&key);
+        while self.used + size > self.max_bytes {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+        self.tick += 1;
+        self.used += size;
+        let entry = HotEntry {
+            file: file.clone(),
+            checked: Instant::now(),
+            last_used: self.tick,
+        };
+        self.entries.insert(key, entry);
+    }
+
+    fn remove(&mut self, key: &HotKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.used -= entry.file.data.as_ref().map_or(0, Bytes::len);
+        }
+    }
+}
+
+/// Whether an `Accept-Encoding` value allows `encoding`, explicitly or through `*`.
+fn accepts_encoding(header: &str, encoding: &str) -> bool {
+    let mut wildcard = None;
+    for item in header.split(',') {
+        let mut parts = item.split(';');
+        let name = parts.next().unwrap_or("").trim();
+        let q = parts
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if name.eq_ignore_ascii_case(encoding) {
+            return q > 0.0;
+        }
+        if name == "*" {
+            wildcard = Some(q > 0.0);
+        }
+    }
+    wildcard.unwrap_or(false)
+}
+
+pub(crate) fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3)?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+pub(crate) fn content_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match ext.as_deref() {
+        Some("html") | Some("htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") | Some("mjs") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("xml") => "application/xml",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("wasm") => "application/wasm",
+        Some("woff2") => "font/woff2",
+        Some("pdf") => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,215 @@
+//! A small key-value store interface for state that outlives a request, used by
+//! [`Idempotency`](crate::idempotency::Idempotency) and [`RateLimit`](crate::RateLimit).
+//!
+//! [`MemoryStore`] keeps everything in the process. To share that state between instances or
+//! keep it across restarts, use `RedisStore` (with the `store-redis` feature) or implement
+//! [`Store`] over sled or similar.
+//!
+//! ```
+//! use std::time::Duration;
+//! use async_tiny::store::{MemoryStore, Store};
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> std::io::Result<()> {
+//! let store = MemoryStore::new();
+//! store.set("session:abc", "alice".into(), Some(Duration::from_secs(60))).await?;
+//! assert_eq!(store.get("session:abc").await?.as_deref(), Some(&b"alice"[..]));
+//! assert_eq!(store.increment("hits", None).await?, 1);
+//! assert_eq!(store.increment("hits", None).await?, 2);
+//! store.delete("session:abc").await?;
+//! assert_eq!(store.get("session:abc").await?, None);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+#[cfg(feature = "store-redis")]
+mod redis;
+#[cfg(feature = "store-redis")]
+pub use self::redis::RedisStore;
+
+/// What [`Store`] methods return: a boxed future, so stores can be used as `dyn Store`.
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = io::Result<T>> + Send + 'a>>;
+
+/// Byte values by string key, each with an optional time-to-live.
+///
+/// Errors are I/O errors, since that's what a remote store's failures mostly are; wrap others
+/// with [`io::Error::other`].
+pub trait Store: Send + Sync {
+    /// The value under `key`, or `None` if it's missing or expired.
+    fn get<'a>(&'a self, key: &'a str) -> StoreFuture<'a, Option<Bytes>>;
+
+    /// Store `value` under `key`, replacing what was there. With a `ttl` it expires after
+    /// that long; without one it stays until deleted.
+    fn set<'a>(&'a self, key: &'a str, value: Bytes, ttl: Option<Duration>) -> StoreFuture<'a, ()>;
+
+    fn delete<'a>(&'a self, key: &'a str) -> StoreFuture<'a, ()>;
+
+    /// Add one to the decimal counter under `key`, starting from zero, and return the new
+    /// count. A `ttl` counts from when the counter is created.
+    ///
+    /// The default is a `get` followed by a `set`, so concurrent increments from several
+    /// instances can be lost and each one restarts the `ttl`; stores with an atomic increment
+    /// should override it.
+    fn increment<'a>(&'a self, key: &'a str, ttl: Option<Duration>) -> StoreFuture<'a, u64> {
+        Box::pin(async move {
+            let current = self.get(key).await?;
+            let count = current
+                .as_deref()
+                .and_then(|v| std::str::from_utf8(v).ok())
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(0)
+                + 1;
+            self.set(key, Bytes::from(count.to_string()), ttl).await?;
+            Ok(count)
+        })
+    }
+}
+
+/// Lets one store back several features, e.g. `Arc<dyn Store>` shared between
+/// [`Idempotency`](crate::idempotency::Idempotency) and [`RateLimit`](crate::RateLimit).
+impl<T: Store + ?Sized> Store for std::sync::Arc<T> {
+    fn get<'a>(&'a self, key: &'a str) -> StoreFuture<'a, Option<Bytes>> {
+        (**self).get(key)
+    }
+
+    fn set<'a>(&'a self, key: &'a str, value: Bytes, ttl: Option<Duration>) -> StoreFuture<'a, ()> {
+        (**self).set(key, value, ttl)
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> StoreFuture<'a, ()> {
+        (**self).delete(key)
+    }
+
+    fn increment<'a>(&'a self, key: &'a str, ttl: Option<Duration>) -> StoreFuture<'a, u64> {
+        (**self).increment(key, ttl)
+    }
+}
+
+/// A [`Store`] in the process's memory.
+///
+/// Expired entries are swept out each time the store doubles in size since the last sweep,
+/// so the cost is spread over the inserts and memory stays within twice what's live.
+#[derive(Default)]
+pub struct MemoryStore {
+    entries: Mutex<Entries>,
+}
+
+#[derive(Default)]
+struct Entries {
+    map: HashMap<String, Entry>,
+    /// How many entries to let in before the next sweep.
+    sweep_at: usize,
+}
+
+/// Don't bother sweeping stores smaller than this.
+const MIN_SWEEP: usize = 64;
+
+struct Entry {
+    value: Bytes,
+    expires: Option<Instant>,
+}
+
+impl Entry {
+    fn live(&self, now: Instant) -> bool {
+        self.expires.is_none_or(|at| now < at)
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn insert(entries: &mut Entries, key: &str, value: Bytes, ttl: Option<Duration>) {
+        let now = Instant::now();
+        if entries.map.len() >= entries.sweep_at {
+            entries.map.retain(|_, e| e.live(now));
+            entries.sweep_at = (entries.map.len() * 2).max(MIN_SWEEP);
+        }
+        let expires = ttl.map(|ttl| now + ttl);
+        entries
+            .map
+            .insert(key.to_string(), Entry { value, expires });
+    }
+}
+
+impl Store for MemoryStore {
+    fn get<'a>(&'a self, key: &'a str) -> StoreFuture<'a, Option<Bytes>> {
+        let entries = self.entries.lock().unwrap();
+        let value = entries
+            .map
+            .get(key)
+            .filter(|e| e.live(Instant::now()))
+            .map(|e| e.value.clone());
+        Box::pin(std::future::ready(Ok(value)))
+    }
+
+    fn set<'a>(&'a self, key: &'a str, value: Bytes, ttl: Option<Duration>) -> StoreFuture<'a, ()> {
+        Self::insert(&mut self.entries.lock().unwrap(), key, value, ttl);
+        Box::pin(std::future::ready(Ok(())))
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> StoreFuture<'a, ()> {
+        self.entries.lock().unwrap().map.remove(key);
+        Box::pin(std::future::ready(Ok(())))
+    }
+
+    fn increment<'a>(&'a self, key: &'a str, ttl: Option<Duration>) -> StoreFuture<'a, u64> {
+        // Under one lock, so counts are exact.
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        let count = match entries.map.get_mut(key).filter(|e| e.live(now)) {
+            Some(entry) => {
+                let count = std::str::from_utf8(&entry.value)
+                    .ok()
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .unwrap_or(0)
+                    + 1;
+                entry.value = Bytes::from(count.to_string());
+                count
+            }
+            None => {
+                Self::insert(&mut entries, key, Bytes::from_static(b"1"), ttl);
+                1
+            }
+        };
+        Box::pin(std::future::ready(Ok(count)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn expired_entries_are_swept() {
+        let store = MemoryStore::new();
+        let ttl = Some(Duration::from_millis(1));
+        for i in 0..MIN_SWEEP {
+            store
+                .set(&format!("old{}", i), "x".into(), ttl)
+                .await
+                .unwrap();
+        }
+        store.set("kept", "x".into(), None).await.unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        for i in 0..MIN_SWEEP * 2 {
+            store
+                .set(&format!("new{}", i), "x".into(), None)
+                .await
+                .unwrap();
+        }
+        let entries = store.entries.lock().unwrap();
+        assert!(!entries.map.keys().any(|k| k.starts_with("old")));
+        assert!(entries.map.contains_key("kept"));
+    }
+}
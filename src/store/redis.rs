@@ -0,0 +1,127 @@
+//! [`Store`] over Redis, behind the `store-redis` feature.
+
+use std::io;
+use std::time::Duration;
+
+use bytes::Bytes;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+
+use super::{Store, StoreFuture};
+
+/// A [`Store`] in Redis, so every instance pointed at the same server shares sessions, rate
+/// limits and cached responses.
+///
+/// ```no_run
+/// use async_tiny::rate_limit::{Quota, RateLimit};
+/// use async_tiny::store::RedisStore;
+/// use async_tiny::Server;
+///
+/// # async fn run() -> std::io::Result<()> {
+/// let store = RedisStore::connect("redis://127.0.0.1/").await?.prefix("myapp:");
+/// let limits = RateLimit::new(Quota::per_minute(600)).store(store);
+/// let server = Server::builder().rate_limit(limits).http("0.0.0.0:8080").await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The connection reconnects by itself after Redis restarts; operations during the outage
+/// fail with an I/O error.
+#[derive(Clone)]
+pub struct RedisStore {
+    conn: ConnectionManager,
+    prefix: String,
+}
+
+impl RedisStore {
+    /// Connect to the Redis server at `url`, like `redis://127.0.0.1:6379/0`.
+    pub async fn connect(url: &str) -> io::Result<Self> {
+        let client = redis::Client::open(url).map_err(io::Error::other)?;
+        let conn = ConnectionManager::new(client)
+            .await
+            .map_err(io::Error::other)?;
+        Ok(Self::from_connection(conn))
+    }
+
+    /// Use a connection set up elsewhere, e.g. with custom retry settings.
+    pub fn from_connection(conn: ConnectionManager) -> Self {
+        Self {
+            conn,
+            prefix: String::new(),
+        }
+    }
+
+    /// Put `prefix` in front of every key, to keep apps sharing a server apart.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+}
+
+/// Redis wants expiries in whole milliseconds, and at least one.
+fn millis(ttl: Duration) -> u64 {
+    u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX).max(1)
+}
+
+impl Store for RedisStore {
+    fn get<'a>(&'a self, key: &'a str) -> StoreFuture<'a, Option<Bytes>> {
+        Box::pin(async move {
+            let mut conn = self.conn.clone();
+            let value: Option<Vec<u8>> = conn.get(self.key(key)).await.map_err(io::Error::other)?;
+            Ok(value.map(Bytes::from))
+        })
+    }
+
+    fn set<'a>(&'a self, key: &'a str, value: Bytes, ttl: Option<Duration>) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let mut cmd = redis::cmd("SET");
+            cmd.arg(self.key(key)).arg(value.as_ref());
+            if let Some(ttl) = ttl {
+                cmd.arg("PX").arg(millis(ttl));
+            }
+            let mut conn = self.conn.clone();
+            cmd.query_async::<()>(&mut conn)
+                .await
+                .map_err(io::Error::other)
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let mut conn = self.conn.clone();
+            conn.del::<_, ()>(self.key(key))
+                .await
+                .map_err(io::Error::other)
+        })
+    }
+
+    fn increment<'a>(&'a self, key: &'a str, ttl: Option<Duration>) -> StoreFuture<'a, u64> {
+        Box::pin(async move {
+            let key = self.key(key);
+            let mut pipe = redis::pipe();
+            pipe.atomic();
+            if let Some(ttl) = ttl {
+                // Creates the counter with its expiry only if it doesn't exist yet, so the
+                // window isn't pushed back by every hit.
+                pipe.cmd("SET")
+                    .arg(&key)
+                    .arg(0)
+                    .arg("PX")
+                    .arg(millis(ttl))
+                    .arg("NX")
+                    .ignore();
+            }
+            pipe.cmd("INCR").arg(&key);
+            let mut conn = self.conn.clone();
+            let (count,): (u64,) = pipe
+                .query_async(&mut conn)
+                .await
+                .map_err(io::Error::other)?;
+            Ok(count)
+        })
+    }
+}
@@ -0,0 +1,125 @@
+//! The four request-target forms of RFC 9112 §3.2.
+
+use http::header::HOST;
+use http::{HeaderMap, HeaderValue, Uri};
+
+/// How the client wrote the request target, returned by
+/// [`Request::target_form`](crate::Request::target_form).
+///
+/// Whatever the form, [`Request::url`](crate::Request::url) holds the part a router matches on:
+/// the path and query for origin- and absolute-form, `host:port` for authority-form and `*`
+/// for asterisk-form.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TargetForm {
+    /// `/path?query`, what almost every client sends.
+    Origin,
+    /// `http://host/path?query`, sent by clients talking to a proxy. The authority replaces
+    /// the `Host` header, as RFC 9112 §3.2.2 requires.
+    Absolute,
+    /// `host:port`, only used by `CONNECT`.
+    Authority,
+    /// `*`, only used by server-wide `OPTIONS`.
+    Asterisk,
+}
+
+impl TargetForm {
+    pub(crate) fn of(uri: &Uri) -> Self {
+        if uri.scheme().is_some() {
+            TargetForm::Absolute
+        } else if uri.authority().is_some() {
+            TargetForm::Authority
+        } else if uri == "*" {
+            TargetForm::Asterisk
+        } else {
+            TargetForm::Origin
+        }
+    }
+}
+
+/// Splits `uri` into its form and the `url` handed to the app, pointing `Host` at the target's
+/// authority for absolute-form so handlers see the same values however the client wrote it.
+pub(crate) fn normalize(uri: &Uri, headers: &mut HeaderMap) -> (TargetForm, String) {
+    let form = TargetForm::of(uri);
+    let url = match form {
+        TargetForm::Authority => uri.authority().map_or_else(String::new, |a| a.to_string()),
+        _ => match uri.path_and_query() {
+            Some(pq) => pq.as_str().to_string(),
+            None => uri.path().to_string(),
+        },
+    };
+    if form == TargetForm::Absolute {
+        // Any userinfo stays out of `Host`.
+        let host = uri.authority().map(|a| match a.port() {
+            Some(port) => format!("{}:{}", a.host(), port),
+            None => a.host().to_string(),
+        });
+        if let Some(host) = host.and_then(|h| HeaderValue::from_str(&h).ok()) {
+            headers.insert(HOST, host);
+        }
+    }
+    (form, url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalized(target: &str, host: Option<&str>) -> (TargetForm, String, Option<String>) {
+        let uri: Uri = target.parse().unwrap();
+        let mut headers = HeaderMap::new();
+        if let Some(host) = host {
+            headers.insert(HOST, host.parse().unwrap());
+        }
+        let (form, url) = normalize(&uri, &mut headers);
+        let host = headers.get(HOST).map(|h| h.to_str().unwrap().to_string());
+        (form, url, host)
+    }
+
+    #[test]
+    fn origin_form_keeps_host() {
+        assert_eq!(
+            normalized("/a/b?c=d", Some("example.com")),
+            (
+                TargetForm::Origin,
+                "/a/b?c=d".to_string(),
+                Some("example.com".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn absolute_form_replaces_host() {
+        assert_eq!(
+            normalized("http://example.com:8080/a?b", Some("other")),
+            (
+                TargetForm::Absolute,
+                "/a?b".to_string(),
+                Some("example.com:8080".to_string())
+            )
+        );
+        assert_eq!(
+            normalized("https://user:pw@example.com", None),
+            (
+                TargetForm::Absolute,
+                "/".to_string(),
+                Some("example.com".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn authority_and_asterisk_forms() {
+        assert_eq!(
+            normalized("example.com:443", Some("example.com:443")),
+            (
+                TargetForm::Authority,
+                "example.com:443".to_string(),
+                Some("example.com:443".to_string())
+            )
+        );
+        assert_eq!(
+            normalized("*", None),
+            (TargetForm::Asterisk, "*".to_string(), None)
+        );
+    }
+}
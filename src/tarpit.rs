@@ -0,0 +1,174 @@
+//! Answering abusive clients slowly instead of quickly, enabled via [`ServerBuilder::tarpit`].
+//!
+//! A trapped request gets its usual response (`404` for a trap path, `429` over a rate
+//! limit), but the body is sent a few bytes at a time with a pause between each, and the
+//! connection is closed at the end. A scanner waits on every probe instead of moving on.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use async_tiny::rate_limit::{Quota, RateLimit};
+//! use async_tiny::tarpit::Tarpit;
+//! use async_tiny::Server;
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let tarpit = Tarpit::new()
+//!     .path("/wp-login.php")
+//!     .path("/.env")
+//!     .path("/cgi-bin/*")
+//!     .rate_limited()
+//!     .interval(Duration::from_secs(2));
+//! let mut server = Server::builder()
+//!     .rate_limit(RateLimit::new(Quota::per_second(5)))
+//!     .tarpit(tarpit)
+//!     .http("0.0.0.0:8080")
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Each trapped connection is held open, so at most [`Tarpit::max_connections`] are trapped
+//! at once; past that, trapped requests are answered at full speed. A longer error page (see
+//! [`ErrorPages`](crate::ErrorPages)) keeps each one busy for longer.
+//!
+//! [`ServerBuilder::tarpit`]: crate::ServerBuilder::tarpit
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use hyper::body::{Body, Frame, SizeHint};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::{Instant, Sleep};
+
+use crate::pattern::Pattern;
+use crate::{Response, ResponseBody};
+
+/// Which requests to trap, and how slowly to answer them.
+#[derive(Clone, Debug)]
+pub struct Tarpit {
+    paths: Vec<Pattern>,
+    rate_limited: bool,
+    interval: Duration,
+    chunk: usize,
+    slots: Arc<Semaphore>,
+}
+
+impl Tarpit {
+    /// Traps nothing until given paths or [`rate_limited`](Self::rate_limited); sends one
+    /// byte a second to at most 64 connections.
+    pub fn new() -> Self {
+        Self {
+            paths: Vec::new(),
+            rate_limited: false,
+            interval: Duration::from_secs(1),
+            chunk: 1,
+            slots: Arc::new(Semaphore::new(64)),
+        }
+    }
+
+    /// Trap requests for paths matching `pattern`, such as `/wp-admin/*` or `/.git/:file`.
+    /// They're answered with the `404` error page.
+    pub fn path(mut self, pattern: &str) -> Self {
+        self.paths.push(Pattern::parse(pattern));
+        self
+    }
+
+    /// Also trap requests refused by [`ServerBuilder::rate_limit`](crate::ServerBuilder::rate_limit).
+    pub fn rate_limited(mut self) -> Self {
+        self.rate_limited = true;
+        self
+    }
+
+    /// How long to wait before each chunk of the body.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// How many bytes to send each time (minimum 1).
+    pub fn chunk_size(mut self, bytes: usize) -> Self {
+        self.chunk = bytes.max(1);
+        self
+    }
+
+    /// Trap at most `n` connections at once.
+    pub fn max_connections(mut self, n: usize) -> Self {
+        self.slots = Arc::new(Semaphore::new(n));
+        self
+    }
+
+    pub(crate) fn traps_path(&self, url: &str) -> bool {
+        let path = url.split('?').next().unwrap_or(url);
+        self.paths.iter().any(|pattern| pattern.matches(path))
+    }
+
+    pub(crate) fn traps_rate_limited(&self) -> bool {
+        self.rate_limited
+    }
+
+    /// `resp` with its body dripped out, or sent as usual if every slot is taken.
+    pub(crate) fn respond(&self, resp: Response) -> hyper::Response<ResponseBody> {
+        let mut resp = resp.close_connection();
+        let Ok(permit) = self.slots.clone().try_acquire_owned() else {
+            return crate::to_hyper_response(resp);
+        };
+        let data = std::mem::take(&mut resp.body);
+        let mut response = crate::to_hyper_response(resp);
+        *response.body_mut() = ResponseBody::Drip(Drip {
+            data,
+            chunk: self.chunk,
+            interval: self.interval,
+            sleep: Box::pin(tokio::time::sleep(self.interval)),
+            _permit: permit,
+        });
+        response
+    }
+}
+
+impl Default for Tarpit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A body sent `chunk` bytes every `interval`, holding a tarpit slot until it's done.
+pub(crate) struct Drip {
+    data: Bytes,
+    chunk: usize,
+    interval: Duration,
+    sleep: Pin<Box<Sleep>>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Body for Drip {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        let drip = self.get_mut();
+        if drip.data.is_empty() {
+            return Poll::Ready(None);
+        }
+        ready!(drip.sleep.as_mut().poll(cx));
+        let piece = drip.data.split_to(drip.chunk.min(drip.data.len()));
+        let next = Instant::now() + drip.interval;
+        drip.sleep.as_mut().reset(next);
+        Poll::Ready(Some(Ok(Frame::data(piece))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::with_exact(self.data.len() as u64)
+    }
+}
@@ -0,0 +1,131 @@
+//! Templating integration hook, enabled with the `template` feature.
+//!
+//! Implement [`TemplateEngine`] for your engine of choice (askama, tera, ...) and
+//! render straight into a response with [`Response::render`](crate::Response::render).
+//! [`Templates`] is a tiny built-in engine for cases where `{{ name }}` substitution is enough.
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// A template engine that can render a named template with a context.
+pub trait TemplateEngine {
+    /// The data handed to the template.
+    type Context: ?Sized;
+    /// The error produced when rendering fails.
+    type Error;
+
+    /// Render the template called `name` into an HTML string.
+    fn render(&self, name: &str, ctx: &Self::Context) -> Result<String, Self::Error>;
+}
+
+/// Key/value pairs available to a [`Templates`] template.
+#[derive(Clone, Default)]
+pub struct Context {
+    values: HashMap<String, String>,
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a value, replacing any previous value for `key`.
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.values.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+}
+
+/// A minimal engine replacing `{{ key }}` placeholders with HTML-escaped context values.
+///
+/// # Example
+///
+/// ```
+/// use async_tiny::template::{Context, Templates};
+/// use async_tiny::Response;
+///
+/// let templates = Templates::new().with_template("hello", "<h1>Hello, {{ name }}!</h1>");
+/// let ctx = Context::new().with("name", "alice");
+/// let response = Response::render(&templates, "hello", &ctx).unwrap();
+/// assert_eq!(response.body(), "<h1>Hello, alice!</h1>");
+/// ```
+#[derive(Clone, Default)]
+pub struct Templates {
+    templates: HashMap<String, String>,
+}
+
+impl Templates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a template source under `name`.
+    pub fn with_template(mut self, name: impl Into<String>, source: impl Into<String>) -> Self {
+        self.templates.insert(name.into(), source.into());
+        self
+    }
+}
+
+impl TemplateEngine for Templates {
+    type Context = Context;
+    type Error = TemplateError;
+
+    fn render(&self, name: &str, ctx: &Context) -> Result<String, TemplateError> {
+        let source = self
+            .templates
+            .get(name)
+            .ok_or_else(|| TemplateError::NotFound(name.to_string()))?;
+
+        let mut out = String::with_capacity(source.len());
+        let mut rest = source.as_str();
+        while let Some(start) = rest.find("{{") {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let end = after.find("}}").ok_or(TemplateError::Unclosed)?;
+            let key = after[..end].trim();
+            let value = ctx
+                .get(key)
+                .ok_or_else(|| TemplateError::MissingValue(key.to_string()))?;
+            escape_html(value, &mut out);
+            rest = &after[end + 2..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+#[derive(Debug)]
+pub enum TemplateError {
+    NotFound(String),
+    MissingValue(String),
+    Unclosed,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::NotFound(name) => write!(f, "template not found: {}", name),
+            TemplateError::MissingValue(key) => write!(f, "missing template value: {}", key),
+            TemplateError::Unclosed => write!(f, "unclosed template placeholder"),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+fn escape_html(s: &str, out: &mut String) {
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+}
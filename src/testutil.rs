@@ -0,0 +1,436 @@
+//! Generated requests and an in-memory harness for property-testing request handlers.
+//!
+//! Enabled with the `testutil` feature. [`pair`] connects a [`Client`] to a [`Server`] over
+//! `tokio::io::duplex`, and a seeded [`Generator`] produces valid requests (any method,
+//! percent-free targets, extra headers, `Content-Length` or chunked bodies) and malformed
+//! ones the server must reject with a `4xx`.
+//!
+//! ```
+//! use async_tiny::testutil::{pair, Generator};
+//! use async_tiny::{Response, Server};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let (client, mut server) = pair(Server::builder().silent(true));
+//! tokio::spawn(async move {
+//!     while let Some(request) = server.next().await {
+//!         let echo = format!("{} {} {}", request.method(), request.url(), request.body().len());
+//!         let _ = request.respond(Response::from_string(echo));
+//!     }
+//! });
+//!
+//! for seed in 0..50 {
+//!     let mut gen = Generator::new(seed);
+//!
+//!     let request = gen.valid_request();
+//!     let response = client.send(&request.to_bytes()).await.unwrap();
+//!     assert_eq!(response.status, 200, "seed {}", seed);
+//!     if request.method != "HEAD" {
+//!         let expected = format!("{} {} {}", request.method, request.target, request.body.len());
+//!         assert_eq!(response.body, expected.as_bytes(), "seed {}", seed);
+//!     }
+//!
+//!     let response = client.send(&gen.invalid_request()).await.unwrap();
+//!     assert!((400..500).contains(&response.status), "seed {}", seed);
+//! }
+//! # }
+//! ```
+
+use std::fmt::Write as _;
+use std::io;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio::sync::mpsc;
+
+use crate::{Server, ServerBuilder, Version};
+
+/// How long [`Client::send`] waits for a response before giving up.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Starts a server from `builder` and a client connected to it in memory.
+pub fn pair(builder: ServerBuilder) -> (Client, Server) {
+    let (streams, source) = mpsc::channel(16);
+    (Client { streams }, builder.serve_streams(source))
+}
+
+/// Sends raw requests to the server from [`pair`], one fresh connection per request.
+#[derive(Clone, Debug)]
+pub struct Client {
+    streams: mpsc::Sender<DuplexStream>,
+}
+
+impl Client {
+    /// Writes `raw` on a new connection and reads back one response.
+    ///
+    /// Fails with `TimedOut` if the server doesn't answer within five seconds, e.g. because
+    /// `raw` is an incomplete request it's still waiting on.
+    pub async fn send(&self, raw: &[u8]) -> io::Result<RawResponse> {
+        let (mut client, stream) = tokio::io::duplex(64 * 1024);
+        self.streams
+            .send(stream)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::NotConnected, "server stopped"))?;
+
+        let head_only = raw.starts_with(b"HEAD ");
+        let exchange = async {
+            client.write_all(raw).await?;
+            read_response(&mut client, head_only).await
+        };
+        tokio::time::timeout(RESPONSE_TIMEOUT, exchange)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "no response"))?
+    }
+}
+
+/// A response as it came off the wire, with chunked bodies decoded.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RawResponse {
+    /// From the status line, e.g. `HTTP/1.0`.
+    pub version: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl RawResponse {
+    /// The first header called `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+async fn read_response(stream: &mut DuplexStream, head_only: bool) -> io::Result<RawResponse> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    let mut buf = Vec::new();
+    let head_end = loop {
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end + 4;
+        }
+        if read_more(stream, &mut buf).await? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+    };
+
+    let head = std::str::from_utf8(&buf[..head_end]).map_err(|_| invalid("non-UTF-8 head"))?;
+    let mut lines = head.split("\r\n");
+    let mut status_line = lines.next().unwrap_or("").split(' ');
+    let version = status_line.next().unwrap_or("").to_string();
+    let status = status_line
+        .next()
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| invalid("bad status line"))?;
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(n, v)| (n.trim().to_string(), v.trim().to_string()))
+        .collect();
+
+    let mut response = RawResponse {
+        version,
+        status,
+        headers,
+        body: Vec::new(),
+    };
+    let mut rest = buf.split_off(head_end);
+
+    if head_only || status == 204 || status == 304 {
+        return Ok(response);
+    }
+    if let Some(len) = response.header("content-length") {
+        let len: usize = len.parse().map_err(|_| invalid("bad content-length"))?;
+        while rest.len() < len {
+            if read_more(stream, &mut rest).await? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+        rest.truncate(len);
+        response.body = rest;
+    } else if response
+        .header("transfer-encoding")
+        .is_some_and(|te| te.eq_ignore_ascii_case("chunked"))
+    {
+        response.body = read_chunked(stream, rest).await?;
+    } else {
+        stream.read_to_end(&mut rest).await?;
+        response.body = rest;
+    }
+    Ok(response)
+}
+
+async fn read_chunked(stream: &mut DuplexStream, mut buf: Vec<u8>) -> io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = loop {
+            if let Some(end) = buf.windows(2).position(|w| w == b"\r\n") {
+                break end;
+            }
+            if read_more(stream, &mut buf).await? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        };
+        let size = std::str::from_utf8(&buf[..line_end])
+            .ok()
+            .and_then(|line| usize::from_str_radix(line.split(';').next()?.trim(), 16).ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "bad chunk size"))?;
+        buf.drain(..line_end + 2);
+        if size == 0 {
+            return Ok(body);
+        }
+        while buf.len() < size + 2 {
+            if read_more(stream, &mut buf).await? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+        body.extend_from_slice(&buf[..size]);
+        buf.drain(..size + 2);
+    }
+}
+
+async fn read_more(stream: &mut DuplexStream, buf: &mut Vec<u8>) -> io::Result<usize> {
+    let mut chunk = [0u8; 8192];
+    let n = stream.read(&mut chunk).await?;
+    buf.extend_from_slice(&chunk[..n]);
+    Ok(n)
+}
+
+/// A generated, well-formed request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawRequest {
+    pub method: String,
+    /// The request target, e.g. `/a/b?c=d`.
+    pub target: String,
+    /// Headers besides `Host` and the body framing, which [`RawRequest::to_bytes`] adds.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Send the body chunked, in chunks of at most this many bytes, instead of with
+    /// `Content-Length`.
+    pub chunk_size: Option<usize>,
+}
+
+impl RawRequest {
+    /// The request in HTTP/1.1 wire format.
+    ///
+    /// Bodies reach the handler, and responses the client, byte for byte:
+    ///
+    /// ```
+    /// use std::io::Read;
+    ///
+    /// use async_tiny::testutil::{pair, RawRequest};
+    /// use async_tiny::{Response, Server};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (client, mut server) = pair(Server::builder().silent(true));
+    /// tokio::spawn(async move {
+    ///     while let Some(request) = server.next().await {
+    ///         let mut body = Vec::new();
+    ///         request.body_reader().read_to_end(&mut body).unwrap();
+    ///         body.reverse();
+    ///         let _ = request.respond(Response::from_grpc_web(body));
+    ///     }
+    /// });
+    ///
+    /// let every_byte: Vec<u8> = (0..=255).collect();
+    /// let request = RawRequest {
+    ///     method: "POST".to_string(),
+    ///     target: "/pkg.Service/Method".to_string(),
+    ///     headers: vec![("Content-Type".to_string(), "application/grpc-web".to_string())],
+    ///     body: every_byte.clone(),
+    ///     chunk_size: Some(100),
+    /// };
+    /// let response = client.send(&request.to_bytes()).await.unwrap();
+    /// assert_eq!(response.header("content-type"), Some("application/grpc-web"));
+    /// assert_eq!(response.body, every_byte.into_iter().rev().collect::<Vec<u8>>());
+    /// # }
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_bytes_as(Version::HTTP_11)
+    }
+
+    /// The request in the wire format of `version`, `HTTP/1.0` or `HTTP/1.1`.
+    ///
+    /// HTTP/1.0 has no chunked encoding, so the body is always sent with `Content-Length`.
+    /// A 1.0 client gets no chunked responses and its connection closed after the response
+    /// unless it asked for keep-alive:
+    ///
+    /// ```
+    /// use async_tiny::testutil::{pair, Generator};
+    /// use async_tiny::{Response, Server, Version};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (client, mut server) = pair(Server::builder().silent(true));
+    /// tokio::spawn(async move {
+    ///     while let Some(request) = server.next().await {
+    ///         let version = format!("{:?}", request.version());
+    ///         let _ = request.respond(Response::from_string(version));
+    ///     }
+    /// });
+    ///
+    /// let mut request = Generator::new(7).valid_request();
+    /// request.method = "POST".to_string();
+    /// request.chunk_size = Some(3);
+    /// let response = client.send(&request.to_bytes_as(Version::HTTP_10)).await.unwrap();
+    /// assert_eq!(response.version, "HTTP/1.0");
+    /// assert_eq!(response.body, b"HTTP/1.0");
+    /// assert_eq!(response.header("transfer-encoding"), None);
+    /// assert_eq!(response.header("connection"), None);
+    /// # }
+    /// ```
+    pub fn to_bytes_as(&self, version: Version) -> Vec<u8> {
+        let chunk_size = self.chunk_size.filter(|_| version != Version::HTTP_10);
+        let mut head = format!(
+            "{} {} {:?}\r\nHost: test\r\n",
+            self.method, self.target, version
+        );
+        for (name, value) in &self.headers {
+            let _ = write!(head, "{}: {}\r\n", name, value);
+        }
+        match chunk_size {
+            Some(_) => head.push_str("Transfer-Encoding: chunked\r\n"),
+            None if !self.body.is_empty() => {
+                let _ = write!(head, "Content-Length: {}\r\n", self.body.len());
+            }
+            None => {}
+        }
+        head.push_str("\r\n");
+
+        let mut out = head.into_bytes();
+        match chunk_size {
+            Some(size) => {
+                for chunk in self.body.chunks(size.max(1)) {
+                    out.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+                    out.extend_from_slice(chunk);
+                    out.extend_from_slice(b"\r\n");
+                }
+                out.extend_from_slice(b"0\r\n\r\n");
+            }
+            None => out.extend_from_slice(&self.body),
+        }
+        out
+    }
+}
+
+/// A deterministic source of requests: the same seed always yields the same sequence.
+#[derive(Clone, Debug)]
+pub struct Generator {
+    state: u64,
+}
+
+const METHODS: [&str; 8] = [
+    "GET", "HEAD", "POST", "PUT", "DELETE", "PATCH", "OPTIONS", "REPORT",
+];
+const PATH_CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789-._~";
+const TOKEN_CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-";
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        // Never zero, which xorshift can't leave.
+        Self {
+            state: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1,
+        }
+    }
+
+    /// The next pseudo-random number (xorshift64*).
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A number in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n.max(1) as u64) as usize
+    }
+
+    /// A request every compliant server accepts.
+    pub fn valid_request(&mut self) -> RawRequest {
+        let method = METHODS[self.below(METHODS.len())].to_string();
+
+        let mut target = String::new();
+        for _ in 0..=self.below(4) {
+            target.push('/');
+            let len = 1 + self.below(12);
+            target.push_str(&self.word(PATH_CHARS, len));
+        }
+        if self.below(3) == 0 {
+            target.push('?');
+            let (key_len, value_len) = (1 + self.below(8), self.below(8));
+            target.push_str(&self.word(PATH_CHARS, key_len));
+            target.push('=');
+            target.push_str(&self.word(PATH_CHARS, value_len));
+        }
+
+        let headers = (0..self.below(8))
+            .map(|i| {
+                let len = 1 + self.below(8);
+                let name = format!("X-Gen-{}-{}", i, self.word(TOKEN_CHARS, len));
+                let value: String = (0..1 + self.below(32))
+                    .map(|_| (b'!' + self.below(94) as u8) as char)
+                    .collect();
+                (name, value)
+            })
+            .collect();
+
+        let has_body = !matches!(method.as_str(), "GET" | "HEAD" | "OPTIONS");
+        let body: Vec<u8> = if has_body {
+            (0..self.below(2048))
+                .map(|_| self.next_u64() as u8)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let chunk_size = (has_body && self.below(2) == 0).then(|| 1 + self.below(512));
+
+        RawRequest {
+            method,
+            target,
+            headers,
+            body,
+            chunk_size,
+        }
+    }
+
+    /// A complete but malformed request, which the server must answer with a `4xx`.
+    pub fn invalid_request(&mut self) -> Vec<u8> {
+        let valid = self.valid_request();
+        let target = valid.target.as_str();
+        let raw = match self.below(7) {
+            0 => format!("G(T {} HTTP/1.1\r\nHost: test\r\n\r\n", target),
+            1 => format!(
+                "GET {} HTTP/1.1\r\nHost: test\r\nNo Colon Here\r\n\r\n",
+                target
+            ),
+            2 => format!(
+                "GET {} HTTP/1.1\r\nHost: test\r\nX-Bad: a\rb\r\n\r\n",
+                target
+            ),
+            3 => format!(
+                "GET {} HTTP/1.1\r\nHost: test\r\n Folded: x\r\n\r\n",
+                target
+            ),
+            4 => format!(
+                "POST {} HTTP/1.1\r\nHost: test\r\nContent-Length: {}x\r\n\r\n",
+                target,
+                self.below(100)
+            ),
+            5 => format!(
+                "POST {} HTTP/1.1\r\nHost: test\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n\r\n",
+                target
+            ),
+            _ => format!("GET {} HTTP/1.1\r\nBad\0Name: x\r\n\r\n", target),
+        };
+        raw.into_bytes()
+    }
+
+    fn word(&mut self, alphabet: &[u8], len: usize) -> String {
+        (0..len)
+            .map(|_| alphabet[self.below(alphabet.len())] as char)
+            .collect()
+    }
+}
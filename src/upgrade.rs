@@ -0,0 +1,107 @@
+//! Switching a connection to another protocol, via [`Request::on_upgrade`].
+//!
+//! Take the upgrade from a request that asks for one (`Connection: upgrade` with an
+//! `Upgrade` header, or `CONNECT`), answer it with `101 Switching Protocols` (or a `2xx` for
+//! `CONNECT`), then await the [`OnUpgrade`] for the raw connection. Any other status leaves
+//! the connection HTTP, and the `OnUpgrade` fails.
+//!
+//! ```no_run
+//! use async_tiny::{Header, Response, Server};
+//! use tokio::io::{AsyncReadExt, AsyncWriteExt};
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let mut server = Server::http("127.0.0.1:8080", false).await?;
+//! while let Some(mut request) = server.next().await {
+//!     let Some(upgrade) = request.on_upgrade() else {
+//!         let _ = request.respond(Response::empty(426));
+//!         continue;
+//!     };
+//!     let _ = request.respond(
+//!         Response::empty(101)
+//!             .with_header(Header::new("Connection", "upgrade").unwrap())
+//!             .with_header(Header::new("Upgrade", "echo/1").unwrap()),
+//!     );
+//!     tokio::spawn(async move {
+//!         let mut io = upgrade.await?;
+//!         let mut buf = [0; 1024];
+//!         loop {
+//!             let n = io.read(&mut buf).await?;
+//!             if n == 0 {
+//!                 return Ok::<_, std::io::Error>(());
+//!             }
+//!             io.write_all(&buf[..n]).await?;
+//!         }
+//!     });
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Request::on_upgrade`]: crate::Request::on_upgrade
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use hyper_util::rt::TokioIo;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Resolves to the connection once the upgrade response has been written.
+pub struct OnUpgrade(pub(crate) hyper::upgrade::OnUpgrade);
+
+impl Future for OnUpgrade {
+    type Output = io::Result<Upgraded>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx).map(|result| {
+            result
+                .map(|io| Upgraded(TokioIo::new(io)))
+                .map_err(io::Error::other)
+        })
+    }
+}
+
+/// The raw connection after an upgrade, including any bytes the client sent after the
+/// request head.
+pub struct Upgraded(TokioIo<hyper::upgrade::Upgraded>);
+
+impl AsyncRead for Upgraded {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Upgraded {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.0.is_write_vectored()
+    }
+}
@@ -0,0 +1,47 @@
+//! vsock transport, for HTTP between a VM and its host.
+
+use std::io;
+use std::sync::Arc;
+
+use hyper_util::rt::TokioIo;
+use tokio_vsock::{VsockAddr, VsockListener};
+
+use crate::builder::Config;
+use crate::conn_error::{ConnectionError, ConnectionErrorKind};
+use crate::lanes::Lanes;
+use crate::{serve_connection, Server};
+
+pub(crate) fn start(cid: u32, port: u32, config: Config) -> io::Result<Server> {
+    let listener = VsockListener::bind(VsockAddr::new(cid, port))?;
+    if !config.silent {
+        eprintln!("async_tiny listening on vsock://{}:{}", cid, port);
+    }
+
+    Ok(Server::spawn(config, |tx, config| {
+        vec![tokio::spawn(accept_loop(
+            listener,
+            tx.clone(),
+            config.clone(),
+        ))]
+    }))
+}
+
+async fn accept_loop(listener: VsockListener, tx: Lanes, config: Arc<Config>) {
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = tx.closed() => break,
+        };
+        match accepted {
+            Ok((stream, _)) => {
+                tokio::spawn(serve_connection(
+                    TokioIo::new(stream),
+                    None,
+                    tx.clone(),
+                    config.clone(),
+                ));
+            }
+            Err(e) => config.report(ConnectionError::new(ConnectionErrorKind::Accept, e)),
+        }
+    }
+}